@@ -9,6 +9,7 @@ layout(location = 1) in float half_side_length;
 layout(location = 2) in uint texture_for_block_id;
 layout(location = 3) in float half_height;
 layout(location = 4) in vec2 yaw_and_pitch;
+layout(location = 5) in vec3 old_center;
 layout(location = 0) out vec4 texColor;
 
 
@@ -58,7 +59,7 @@ void main() {
         M, L, K, M, K, N
     );
     vec3 vertex_pos = rotation_mat_from_yaw_and_pitch(yaw_and_pitch) * direction_per_vertex[gl_VertexIndex] * get_bone_half_size(half_side_length,half_height);
-    gl_Position = MVP * vec4(center + vertex_pos, 1.0);
+    gl_Position = MVP * vec4(mix(old_center, center, BONE_INTERPOLATION) + vertex_pos, 1.0);
     gl_Position.y = -gl_Position.y;
 //    uint bone_idx = body_part_to_bone_idx[part_variant];
 //    float bone_stride = tex_stride[bone_idx];
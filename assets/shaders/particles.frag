@@ -1,7 +1,7 @@
 #version 450
 layout(location=0) out vec4 FragColor;
-layout(location=0) in float frag_color;
+layout(location=0) in vec3 frag_color;
 void main()
 {
-    FragColor = vec4(frag_color, 1-frag_color,0,1);
+    FragColor = vec4(frag_color,1);
 }
\ No newline at end of file
@@ -5,8 +5,9 @@
 
 layout (location = 0) in vec3 position;
 layout (location = 1) in float energy;
+layout (location = 2) in uint emitter_entity;
 
-layout (location = 0) out float frag_color;
+layout (location = 0) out vec3 frag_color;
 const float point_size = 40;
 const float eye_distance=0.5;//this is meant to emulate the effect of having eyes
 //slightly in front of the camera, rather than directly in the centre.
@@ -17,5 +18,17 @@ void main()
     gl_Position.y = -gl_Position.y;
     float point_distance = length(MV * point4)-eye_distance;
     gl_PointSize = point_size/point_distance;
-    frag_color = energy;
+    // energy is channel+strength (see Particle in particle.rs). Every channel of signals gets its own hue and fades out with strength.
+    // Debris is brown and trails are white
+    const float channel = max(ceil(energy) - 1, 0);
+    const float strength = energy - channel;
+    vec3 hue;
+    if (emitter_entity == PARTICLE_EMITTER_DEBRIS){
+        hue = vec3(0.5, 0.35, 0.2);
+    } else if (emitter_entity == PARTICLE_EMITTER_TRAIL){
+        hue = vec3(1, 1, 1);
+    } else {
+        hue = clamp(abs(mod(channel * 0.618 * 6 + vec3(0, 4, 2), 6) - 3) - 1, 0, 1);
+    }
+    frag_color = hue * strength;
 }
\ No newline at end of file
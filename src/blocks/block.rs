@@ -1,18 +1,20 @@
 use std::fmt::{Display, Formatter};
-use crate::blocks::block_properties::{BLOCKS, NO_OF_TRANSPARENT_BLOCKS};
+use crate::blocks::block_properties::{BLOCKS, BEDROCK, WATER, LAVA, NO_OF_TRANSPARENT_BLOCKS, NO_OF_TRAVERSABLE_BLOCKS, BLOCK_SENSORY_FEATURES, BLOCK_SENSORY_FEATURES_LEN, BLOCK_HEAT_CONDUCTIVITY, BLOCK_HUMIDITY_DIFFUSION_RATE, SENSORY_FEATURE_IS_EDIBLE};
 use crate::blocks::face_orientation::FaceOrientation;
 use crate::render::data::{VertexSource, VertexAttrib};
 use ash::vk::VertexInputAttributeDescription;
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 #[repr(C, packed)]
 pub struct Block {
     block_id:BlockId,
     block_id_copy:BlockId,
-    humidity:u32,
-    temperature:u32,
-    new_humidity:u32,
-    new_temperature:u32,
+    humidity:f32,
+    temperature:f32, // degrees celsius
+    new_humidity:f32,
+    new_temperature:f32,
+    fluid_level:u32,
+    new_fluid_level:u32,
 }
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 #[repr(C, packed)]
@@ -31,6 +33,52 @@ impl VertexSource for Block{
         ]
     }
 }
+impl Block {
+    /**Block with the given id. Humidity and temperature start at 0. Fluids start as sources*/
+    pub fn new(block_id: BlockId) -> Self {
+        Self {
+            block_id,
+            block_id_copy: block_id,
+            humidity: 0.,
+            temperature: 0.,
+            new_humidity: 0.,
+            new_temperature: 0.,
+            fluid_level: 0,
+            new_fluid_level: 0,
+        }
+    }
+    pub fn block_id(&self) -> BlockId {
+        self.block_id
+    }
+    pub fn set_block_id(&mut self, block_id: BlockId) {
+        self.block_id = block_id;
+        self.block_id_copy = block_id;
+    }
+    /**Between 0 and BLOCK_HUMIDITY of water. See climate.rs*/
+    pub fn humidity(&self) -> f32 {
+        self.humidity
+    }
+    /**In degrees celsius. See climate.rs*/
+    pub fn temperature(&self) -> f32 {
+        self.temperature
+    }
+    pub fn set_humidity(&mut self, humidity: f32) {
+        self.humidity = humidity;
+        self.new_humidity = humidity;
+    }
+    pub fn set_temperature(&mut self, temperature: f32) {
+        self.temperature = temperature;
+        self.new_temperature = temperature;
+    }
+    /**Distance of a fluid block from its source (0 for the source itself). Meaningless for other blocks. See fluid.rs*/
+    pub fn fluid_level(&self) -> u32 {
+        self.fluid_level
+    }
+    pub fn set_fluid_level(&mut self, fluid_level: u32) {
+        self.fluid_level = fluid_level;
+        self.new_fluid_level = fluid_level;
+    }
+}
 impl VertexSource for BlockId{
     fn get_attribute_descriptions(binding: u32) -> Vec<VertexInputAttributeDescription> {
         vec![
@@ -63,16 +111,55 @@ impl BlockId {
     pub const fn id(&self) -> u32 {
         self.id
     }
+    /**Same as is_traversable in utils.comp*/
+    pub fn is_traversable(&self) -> bool {
+        self.id < NO_OF_TRAVERSABLE_BLOCKS
+    }
     pub fn is_solid(&self) -> bool {
         self.id > NO_OF_TRANSPARENT_BLOCKS
     }
     pub const fn opacity(&self) -> f32 {
         BLOCKS[self.id as usize].opacity()
     }
+    /**Same as BLOCK_MASS in constants.comp*/
+    pub const fn mass(&self) -> f32 {
+        BLOCKS[self.id as usize].mass()
+    }
 
+    /**Sand, gravel and snow drop into air below them. Same as BLOCK_FALLS_DOWN in constants.comp*/
+    pub const fn falls_down(&self) -> bool {
+        BLOCKS[self.id as usize].falls_down()
+    }
+    /**Blocks that fall down do so once every this many ticks. Same as BLOCK_CHANGE_RATE in constants.comp*/
+    pub const fn change_rate(&self) -> u32 {
+        BLOCKS[self.id as usize].change_rate()
+    }
     pub fn is_air(&self) -> bool {
         self.id == 0
     }
+    /**How fast heat flows between this block and its neighbours, between 0 and 1. Same as BLOCK_HEAT_CONDUCTIVITY in constants.comp*/
+    pub const fn heat_conductivity(&self) -> f32 {
+        BLOCK_HEAT_CONDUCTIVITY[self.id as usize]
+    }
+    /**How fast humidity flows between this block and its neighbours, between 0 and 1. Same as BLOCK_HUMIDITY_DIFFUSION_RATE in constants.comp*/
+    pub const fn humidity_diffusion_rate(&self) -> f32 {
+        BLOCK_HUMIDITY_DIFFUSION_RATE[self.id as usize]
+    }
+    /**Water and lava. Same as is_fluid in constants.comp*/
+    pub fn is_fluid(&self) -> bool {
+        *self == WATER || *self == LAVA
+    }
+    pub const fn sensory_features(&self) -> [bool;BLOCK_SENSORY_FEATURES_LEN] {
+        BLOCK_SENSORY_FEATURES[self.id as usize]
+    }
+    /**Agents gain energy by touching edible blocks*/
+    pub const fn is_edible(&self) -> bool {
+        self.sensory_features()[SENSORY_FEATURE_IS_EDIBLE]
+    }
+    /**Agents can break any solid block except bedrock. Same as is_breakable in constants.comp*/
+    pub fn is_breakable(&self) -> bool {
+        self.id >= NO_OF_TRAVERSABLE_BLOCKS && *self != BEDROCK
+    }
     pub fn texture_id(&self, ort: FaceOrientation) -> u32 {
         BLOCKS[self.id as usize].get_texture_id(ort)
     }
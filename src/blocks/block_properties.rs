@@ -13,10 +13,12 @@ pub struct BlockProp{ // this thing needs to be aligned according to GLSL rules!
     texture_ids:[u32;6],
     opacity:f32, // the higher, the more opaque
     mass:f32,
+    falls_down:u32, // 1 if the block drops into air below it, 0 otherwise
+    change_rate:u32, // blocks that fall down do so once every change_rate ticks of the ambience pipeline
 }
 impl BlockProp{
     const fn new(texture_ids:[u32;6], opacity:f32, mass:f32)->Self{
-        Self{texture_ids, opacity, mass}
+        Self{texture_ids, opacity, mass, falls_down:0, change_rate:0}
     }
 }
 
@@ -27,6 +29,9 @@ impl BlockPropExtra{
     const fn regular(name:&'static str, texture_id:u32, mass:f32)->Self{
         Self::regular_transparent(name,texture_id,1.,mass)
     }
+    const fn regular_falling(name:&'static str, texture_id:u32, mass:f32, change_rate:u32)->Self{
+        Self{name,prop:BlockProp{texture_ids:[texture_id;6],opacity:1.,mass,falls_down:1,change_rate}}
+    }
     const fn top_sides_bottom(name:&'static str, texture_id_top:u32,texture_id_side:u32,texture_id_bottom:u32, mass:f32)->Self{
         Self::top_sides_bottom_transparent(name,texture_id_top,texture_id_side,texture_id_bottom,1., mass)
     }
@@ -45,6 +50,15 @@ impl BlockPropExtra{
     pub const fn opacity(&self)->f32{
         self.prop.opacity
     }
+    pub const fn mass(&self)->f32{
+        self.prop.mass
+    }
+    pub const fn falls_down(&self)->bool{
+        self.prop.falls_down != 0
+    }
+    pub const fn change_rate(&self)->u32{
+        self.prop.change_rate
+    }
 }
 pub const AIR:BlockId = BlockId::new(0);
 pub const WATER:BlockId = BlockId::new(1);
@@ -137,10 +151,10 @@ pub const BLOCKS:[BlockPropExtra;50] = [
     BlockPropExtra::regular("brick", 43, 1.9),
     BlockPropExtra::regular("cobblestone", 35, 2.26796),
     BlockPropExtra::regular("bedrock", 36, 3.1),
-    BlockPropExtra::regular("sand", 38, 1.62),
-    BlockPropExtra::regular("rare_sand", 37, 1.62),
-    BlockPropExtra::regular("gravel", 40, 1.68),
-    BlockPropExtra::regular("wet_gravel", 39, 1.68),
+    BlockPropExtra::regular_falling("sand", 38, 1.62, 1),
+    BlockPropExtra::regular_falling("rare_sand", 37, 1.62, 1),
+    BlockPropExtra::regular_falling("gravel", 40, 1.68, 2),
+    BlockPropExtra::regular_falling("wet_gravel", 39, 1.68, 4),
     BlockPropExtra::top_sides_bottom("oak_wood", 26,25,26, 1.5),
     BlockPropExtra::top_sides_bottom("oak_stem", 26,24,26, 1.5),
     BlockPropExtra::top_sides_bottom("pink_wood", 29,28,29, 1.5),
@@ -149,5 +163,188 @@ pub const BLOCKS:[BlockPropExtra;50] = [
     BlockPropExtra::top_sides_bottom("dark_stem", 32,30,32, 1.5),
     BlockPropExtra::regular("obsidian", 33, 3.1),
     BlockPropExtra::regular("sponge", 34, 0.1),
-    BlockPropExtra::regular("snow", 15, 0.05)
-];
\ No newline at end of file
+    BlockPropExtra::regular_falling("snow", 15, 0.05, 8)
+];
+
+pub const BLOCK_SENSORY_FEATURES_LEN:usize = 6;
+pub const SENSORY_FEATURE_IS_WATER:usize = 0;
+pub const SENSORY_FEATURE_IS_ORGANIC:usize = 1;
+pub const SENSORY_FEATURE_IS_STONE:usize = 2;
+pub const SENSORY_FEATURE_IS_EDIBLE:usize = 3;
+pub const SENSORY_FEATURE_IS_SOIL:usize = 4;
+pub const SENSORY_FEATURE_IS_PROCESSED:usize = 5;
+pub const SENSORY_FEATURE_NAMES:[&str;BLOCK_SENSORY_FEATURES_LEN] = ["is_water", "is_organic", "is_stone", "is_edible", "is_soil", "is_processed"];
+/**Mirrors BLOCK_SENSORY_FEATURES from constants.comp. Each row holds is_water, is_organic, is_stone, is_edible, is_soil, is_processed*/
+pub const BLOCK_SENSORY_FEATURES:[[bool;BLOCK_SENSORY_FEATURES_LEN];50] = [
+    [false, false, false, false, false, false],// air
+    [true, false, false, false, false, false],// water
+    [false, false, true, false, false, false],// lava
+    [false, false, false, false, false, true],// glass
+    [true, false, true, false, false, false],// ice
+    [false, true, false, false, false, false],// swamp_leaves
+    [false, true, false, false, false, false],// golden_leaves
+    [false, true, false, false, false, false],// oak_leaves
+    [false, true, false, false, false, false],// peach_leaves
+    [false, true, false, false, false, false],// aether_leaves
+    [false, true, false, false, false, false],// frost_leaves
+    [false, false, true, false, false, false],// stone
+    [false, false, true, false, false, false],// gold_ore
+    [false, false, true, false, false, false],// iron_ore
+    [false, false, true, false, false, false],// coal_ore
+    [false, false, true, false, false, false],// diamond_ore
+    [false, false, true, false, false, false],// redstone_ore
+    [false, true, false, false, true, false],// dirt
+    [false, true, false, false, true, false],// swamp_dirt
+    [false, true, false, false, true, false],// iron_rich_dirt
+    [false, true, false, false, true, false],// aether_dirt
+    [false, true, false, false, true, false],// frost_dirt
+    [true, true, false, false, true, false],// grass
+    [true, true, false, true, true, false],// berries
+    [true, true, false, true, true, false],// strawberries
+    [true, true, false, true, true, false],// wheat
+    [true, true, false, false, true, false],// swamp_grass
+    [true, true, false, true, true, false],// swamp_berries
+    [true, true, false, true, true, false],// swamp_backberries
+    [true, true, false, false, true, false],// frost_grass
+    [true, true, false, true, true, false],// snow_crocus
+    [true, true, false, true, true, false],// snow_blackberries
+    [false, true, false, false, false, true],// plank
+    [false, false, true, false, false, true],// slab
+    [false, false, true, false, false, true],// brick
+    [false, false, true, false, false, true],// cobblestone
+    [false, false, true, false, false, false],// bedrock
+    [false, false, false, false, false, false],// sand
+    [false, false, false, false, false, false],// rare_sand
+    [false, false, false, false, false, false],// gravel
+    [true, false, false, false, false, false],// wet_gravel
+    [false, true, true, false, false, false],// oak_wood
+    [false, true, true, false, false, false],// oak_stem
+    [false, true, true, false, false, false],// pink_wood
+    [false, true, true, false, false, false],// pink_stem
+    [false, true, true, false, false, false],// dark_wood
+    [false, true, true, false, false, false],// dark_stem
+    [false, false, true, false, false, false],// obsidian
+    [true, true, false, false, false, false],// sponge
+    [true, false, false, false, true, false] // snow
+];
+
+/**How fast heat flows between neighbouring blocks, between 0 and 1. Mirrors BLOCK_HEAT_CONDUCTIVITY from constants.comp*/
+pub const BLOCK_HEAT_CONDUCTIVITY:[f32;50] = [
+    0.3,// air
+    0.6,// water
+    0.8,// lava
+    0.5,// glass
+    0.9,// ice
+    0.2,// swamp_leaves
+    0.2,// golden_leaves
+    0.2,// oak_leaves
+    0.2,// peach_leaves
+    0.2,// aether_leaves
+    0.2,// frost_leaves
+    0.7,// stone
+    0.8,// gold_ore
+    0.8,// iron_ore
+    0.6,// coal_ore
+    0.8,// diamond_ore
+    0.7,// redstone_ore
+    0.4,// dirt
+    0.4,// swamp_dirt
+    0.4,// iron_rich_dirt
+    0.4,// aether_dirt
+    0.4,// frost_dirt
+    0.4,// grass
+    0.4,// berries
+    0.4,// strawberries
+    0.4,// wheat
+    0.4,// swamp_grass
+    0.4,// swamp_berries
+    0.4,// swamp_backberries
+    0.4,// frost_grass
+    0.4,// snow_crocus
+    0.4,// snow_blackberries
+    0.15,// plank
+    0.7,// slab
+    0.5,// brick
+    0.6,// cobblestone
+    0.7,// bedrock
+    0.3,// sand
+    0.3,// rare_sand
+    0.4,// gravel
+    0.5,// wet_gravel
+    0.15,// oak_wood
+    0.15,// oak_stem
+    0.15,// pink_wood
+    0.15,// pink_stem
+    0.15,// dark_wood
+    0.15,// dark_stem
+    0.7,// obsidian
+    0.1,// sponge
+    0.1,// snow
+];
+/**How fast humidity flows between neighbouring blocks, between 0 and 1. Mirrors BLOCK_HUMIDITY_DIFFUSION_RATE from constants.comp*/
+pub const BLOCK_HUMIDITY_DIFFUSION_RATE:[f32;50] = [
+    0.8,// air
+    0.2,// water
+    1.,// lava
+    0.,// glass
+    0.05,// ice
+    0.8,// swamp_leaves
+    0.5,// golden_leaves
+    0.6,// oak_leaves
+    0.6,// peach_leaves
+    0.2,// aether_leaves
+    0.4,// frost_leaves
+    0.05,// stone
+    0.05,// gold_ore
+    0.05,// iron_ore
+    0.4,// coal_ore
+    0.05,// diamond_ore
+    0.5,// redstone_ore
+    0.7,// dirt
+    0.7,// swamp_dirt
+    0.75,// iron_rich_dirt
+    0.1,// aether_dirt
+    0.5,// frost_dirt
+    0.5,// grass
+    0.45,// berries
+    0.34,// strawberries
+    0.5,// wheat
+    0.55,// swamp_grass
+    0.5,// swamp_berries
+    0.5,// swamp_backberries
+    0.34,// frost_grass
+    0.33,// snow_crocus
+    0.3,// snow_blackberries
+    0.1,// plank
+    0.05,// slab
+    0.01,// brick
+    0.5,// cobblestone
+    0.,// bedrock
+    1.,// sand
+    1.,// rare_sand
+    0.6,// gravel
+    0.6,// wet_gravel
+    0.1,// oak_wood
+    0.1,// oak_stem
+    0.1,// pink_wood
+    0.1,// pink_stem
+    0.1,// dark_wood
+    0.1,// dark_stem
+    0.,// obsidian
+    1.,// sponge
+    0.3,// snow
+];
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn only_berries_and_crops_are_edible(){
+        let edible:Vec<BlockId> = (0..BLOCKS.len() as u32).map(BlockId::new).filter(|b|b.is_edible()).collect();
+        assert_eq!(edible, vec![BERRIES, STRAWBERRIES, WHEAT, SWAMP_BERRIES, SWAMP_BACKBERRIES, SNOW_CROCUS, SNOW_BLACKBERRIES]);
+        for &mineral in &[STONE, COBBLESTONE, BEDROCK, SAND, RARE_SAND, GRAVEL, WET_GRAVEL, OBSIDIAN, SNOW]{
+            assert!(!mineral.is_edible(), "{}", BLOCKS[mineral.id() as usize].name());
+        }
+    }
+}
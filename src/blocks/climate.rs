@@ -0,0 +1,224 @@
+use crate::blocks::{Block, BlockId, WorldSize};
+use crate::blocks::block_properties::{AIR, WATER, LAVA, ICE, SNOW};
+use crate::blocks::fluid::{FLUID_SOURCE_LEVEL, WATER_SPREAD};
+use crate::pipelines::world_generation::FREEZING_TEMPERATURE;
+
+/**Heat and humidity diffuse once every this many ticks of the ambience pipeline*/
+pub const CLIMATE_PERIOD:u32 = 16;
+/**Lava keeps this temperature (in degrees celsius) and heats up its surroundings*/
+pub const LAVA_TEMPERATURE:f32 = 1000.;
+/**Water keeps this humidity and moistens its surroundings. Same as BLOCK_HUMIDITY of water*/
+pub const WATER_HUMIDITY:f32 = 255.;
+/**Snow melts above this temperature. It is far above freezing, because snow is
+generated in cold biomes only and should melt only close to lava*/
+pub const SNOW_MELTING_TEMPERATURE:f32 = 100.;
+
+const NEIGHBOURS:[(i32,i32,i32);6] = [(1,0,0),(-1,0,0),(0,1,0),(0,-1,0),(0,0,1),(0,0,-1)];
+
+fn block_at(world_size:&WorldSize, world:&[Block], x:i32, y:i32, z:i32)->Option<Block>{
+    if x < 0 || y < 0 || z < 0 || !world_size.is_position_in_bounds(x as usize, y as usize, z as usize){
+        return None
+    }
+    Some(world[world_size.block_pos_into_world_idx(x as usize, y as usize, z as usize)])
+}
+
+/**Every neighbour exchanges min(rate of this block, rate of the neighbour)/6 of the difference. The exchange is
+symmetric, so diffusion alone never creates nor destroys heat or humidity, and the sum of all exchanges
+is at most the whole difference, so values never overshoot*/
+fn diffusion_weight(rate:f32, neighbour_rate:f32)->f32{
+    rate.min(neighbour_rate) / NEIGHBOURS.len() as f32
+}
+
+/**Temperature and humidity of the block at this position after diffusion. Lava is a source of heat and water
+is a source of humidity. Out of the world there is nothing to exchange with. Mirrors main in update_ambience_climate.comp*/
+pub fn next_climate(world_size:&WorldSize, world:&[Block], x:usize, y:usize, z:usize)->(f32,f32){
+    let block = world[world_size.block_pos_into_world_idx(x, y, z)];
+    let id = block.block_id();
+    let mut temperature = block.temperature();
+    let mut humidity = block.humidity();
+    for &(dx,dy,dz) in NEIGHBOURS.iter(){
+        if let Some(neighbour) = block_at(world_size, world, x as i32 + dx, y as i32 + dy, z as i32 + dz){
+            let neighbour_id = neighbour.block_id();
+            temperature += diffusion_weight(id.heat_conductivity(), neighbour_id.heat_conductivity()) * (neighbour.temperature() - block.temperature());
+            humidity += diffusion_weight(id.humidity_diffusion_rate(), neighbour_id.humidity_diffusion_rate()) * (neighbour.humidity() - block.humidity());
+        }
+    }
+    if id == LAVA{
+        temperature = LAVA_TEMPERATURE;
+    }
+    if id == WATER{
+        humidity = WATER_HUMIDITY;
+    }
+    (temperature, humidity)
+}
+
+/**New block id and fluid level caused by the current temperature, or None if nothing changes. Water freezes below
+FREEZING_TEMPERATURE, but only at its surface (under air), ice melts into a water source above it and snow
+melts into water that flows away. Mirrors main in update_ambience_climate.comp*/
+pub fn next_climate_state(world_size:&WorldSize, world:&[Block], x:usize, y:usize, z:usize)->Option<(BlockId,u32)>{
+    let block = world[world_size.block_pos_into_world_idx(x, y, z)];
+    let id = block.block_id();
+    let temperature = block.temperature();
+    if id == WATER && temperature < FREEZING_TEMPERATURE{
+        let is_surface = block_at(world_size, world, x as i32, y as i32 + 1, z as i32).map(|b|b.block_id() == AIR).unwrap_or(true);
+        if is_surface{
+            return Some((ICE, 0))
+        }
+    }
+    if id == ICE && temperature > FREEZING_TEMPERATURE{
+        return Some((WATER, FLUID_SOURCE_LEVEL))
+    }
+    if id == SNOW && temperature > SNOW_MELTING_TEMPERATURE{
+        return Some((WATER, WATER_SPREAD))
+    }
+    None
+}
+
+/**CPU reference of update_ambience_climate.comp followed by update_ambience_commit.comp. Does nothing unless
+tick is a multiple of CLIMATE_PERIOD. All blocks see the world from before this tick. Returns indices of blocks
+whose id changed, in the order of the world buffer. On the GPU this runs after the fluids (see update_fluids in fluid.rs)*/
+pub fn update_climate(world_size:&WorldSize, world:&mut [Block], tick:u32)->Vec<usize>{
+    if tick % CLIMATE_PERIOD != 0{
+        return vec![]
+    }
+    let mut changes = vec![];
+    for y in 0..world_size.height(){
+        for z in 0..world_size.world_depth(){
+            for x in 0..world_size.world_width(){
+                changes.push((next_climate(world_size, world, x, y, z), next_climate_state(world_size, world, x, y, z)));
+            }
+        }
+    }
+    let mut edited = vec![];
+    for (idx, ((temperature, humidity), state)) in changes.into_iter().enumerate(){
+        let block = &mut world[idx];
+        block.set_temperature(temperature);
+        block.set_humidity(humidity);
+        if let Some((id, level)) = state{
+            block.set_block_id(id);
+            block.set_fluid_level(level);
+            edited.push(idx);
+        }
+    }
+    edited
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::blocks::block_properties::STONE;
+
+    fn uniform_world(id:BlockId, temperature:f32)->(WorldSize,Vec<Block>){
+        let world_size = WorldSize::new(1, 1);
+        let mut block = Block::new(id);
+        block.set_temperature(temperature);
+        (world_size, vec![block;world_size.world_volume()])
+    }
+
+    fn block_mut<'a>(world_size:&WorldSize, world:&'a mut [Block], x:usize, y:usize, z:usize)->&'a mut Block{
+        &mut world[world_size.block_pos_into_world_idx(x, y, z)]
+    }
+
+    /**Stone and air with uneven temperature and humidity and no sources of either*/
+    fn uneven_world()->(WorldSize,Vec<Block>){
+        let (world_size, mut world) = uniform_world(AIR, 0.);
+        for (i,b) in world.iter_mut().enumerate(){
+            if i % 3 == 0{
+                b.set_block_id(STONE);
+            }
+            b.set_temperature((i * 7919 % 1000) as f32 / 10. - 50.);
+            b.set_humidity((i * 104729 % 2550) as f32 / 10.);
+        }
+        (world_size, world)
+    }
+
+    fn sums(world:&[Block])->(f64,f64){
+        world.iter().fold((0.,0.),|(t,h),b|(t + b.temperature() as f64, h + b.humidity() as f64))
+    }
+
+    #[test]
+    fn diffusion_conserves_heat_and_humidity(){
+        let (world_size, mut world) = uneven_world();
+        let (temperature, humidity) = sums(&world);
+        update_climate(&world_size, &mut world, 0);
+        let (new_temperature, new_humidity) = sums(&world);
+        assert!((new_temperature - temperature).abs() < 1e-3 * temperature.abs().max(1.), "{} != {}", new_temperature, temperature);
+        assert!((new_humidity - humidity).abs() < 1e-3 * humidity.abs(), "{} != {}", new_humidity, humidity);
+    }
+
+    #[test]
+    fn diffusion_never_overshoots(){
+        let (world_size, mut world) = uneven_world();
+        let range = |world:&[Block]|world.iter().fold((f32::MAX,f32::MIN),|(lo,hi),b|(lo.min(b.temperature()), hi.max(b.temperature())));
+        // a hot spot surrounded by the coldest blocks
+        block_mut(&world_size, &mut world, 8, 8, 8).set_temperature(500.);
+        let (lo, hi) = range(&world);
+        for tick in 0..4{
+            update_climate(&world_size, &mut world, tick * CLIMATE_PERIOD);
+            let (new_lo, new_hi) = range(&world);
+            assert!(new_lo >= lo && new_hi <= hi, "[{},{}] is out of [{},{}]", new_lo, new_hi, lo, hi);
+        }
+        let (world_size, mut world) = uniform_world(STONE, 0.);
+        block_mut(&world_size, &mut world, 8, 8, 8).set_temperature(600.);
+        update_climate(&world_size, &mut world, 0);
+        let hot = block_mut(&world_size, &mut world, 8, 8, 8).temperature();
+        let neighbour = block_mut(&world_size, &mut world, 9, 8, 8).temperature();
+        assert!(hot > neighbour && neighbour > 0., "{} {}", hot, neighbour);
+    }
+
+    #[test]
+    fn lava_keeps_its_temperature(){
+        let (world_size, mut world) = uniform_world(STONE, 0.);
+        block_mut(&world_size, &mut world, 8, 8, 8).set_block_id(LAVA);
+        update_climate(&world_size, &mut world, 0);
+        assert_eq!(block_mut(&world_size, &mut world, 8, 8, 8).temperature(), LAVA_TEMPERATURE);
+        // the neighbours see the pinned temperature from the next period on
+        update_climate(&world_size, &mut world, CLIMATE_PERIOD);
+        assert_eq!(block_mut(&world_size, &mut world, 8, 8, 8).temperature(), LAVA_TEMPERATURE);
+        assert!(block_mut(&world_size, &mut world, 8, 9, 8).temperature() > 0.);
+    }
+
+    #[test]
+    fn only_surface_water_freezes(){
+        let (world_size, mut world) = uniform_world(AIR, -10.);
+        block_mut(&world_size, &mut world, 4, 1, 4).set_block_id(WATER);
+        block_mut(&world_size, &mut world, 4, 2, 4).set_block_id(WATER);
+        let edited = update_climate(&world_size, &mut world, 0);
+        assert_eq!(edited, vec![world_size.block_pos_into_world_idx(4, 2, 4)]);
+        assert_eq!(block_mut(&world_size, &mut world, 4, 2, 4).block_id(), ICE);
+        assert_eq!(block_mut(&world_size, &mut world, 4, 1, 4).block_id(), WATER);
+    }
+
+    #[test]
+    fn ice_and_snow_melt(){
+        let (world_size, mut world) = uniform_world(STONE, 5.);
+        let ice = (4, 1, 4);
+        let (snow, cold_snow) = ((8, 1, 8), (12, 1, 12));
+        block_mut(&world_size, &mut world, ice.0, ice.1, ice.2).set_block_id(ICE);
+        for &(x,y,z) in &[snow, cold_snow]{
+            block_mut(&world_size, &mut world, x, y, z).set_block_id(SNOW);
+        }
+        block_mut(&world_size, &mut world, snow.0, snow.1, snow.2).set_temperature(SNOW_MELTING_TEMPERATURE * 2.);
+        assert_eq!(next_climate_state(&world_size, &world, ice.0, ice.1, ice.2), Some((WATER, FLUID_SOURCE_LEVEL)));
+        assert_eq!(next_climate_state(&world_size, &world, snow.0, snow.1, snow.2), Some((WATER, WATER_SPREAD)));
+        assert_eq!(next_climate_state(&world_size, &world, cold_snow.0, cold_snow.1, cold_snow.2), None);
+        update_climate(&world_size, &mut world, 0);
+        let ice = *block_mut(&world_size, &mut world, ice.0, ice.1, ice.2);
+        assert_eq!((ice.block_id(), ice.fluid_level()), (WATER, FLUID_SOURCE_LEVEL));
+        let snow = *block_mut(&world_size, &mut world, snow.0, snow.1, snow.2);
+        assert_eq!((snow.block_id(), snow.fluid_level()), (WATER, WATER_SPREAD));
+        assert_eq!(block_mut(&world_size, &mut world, cold_snow.0, cold_snow.1, cold_snow.2).block_id(), SNOW);
+    }
+
+    #[test]
+    fn climate_changes_only_every_climate_period(){
+        let (world_size, mut world) = uneven_world();
+        block_mut(&world_size, &mut world, 4, 1, 4).set_block_id(ICE);
+        let before = sums(&world);
+        for tick in 1..CLIMATE_PERIOD{
+            assert!(update_climate(&world_size, &mut world, tick).is_empty());
+        }
+        assert_eq!(sums(&world), before);
+        assert_eq!(block_mut(&world_size, &mut world, 4, 1, 4).block_id(), ICE);
+    }
+}
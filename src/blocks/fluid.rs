@@ -0,0 +1,244 @@
+use crate::blocks::{Block, BlockId, WorldSize};
+use crate::blocks::block_properties::{AIR, WATER, LAVA, OBSIDIAN, COBBLESTONE};
+
+/**Water flows once every this many ticks of the ambience pipeline*/
+pub const WATER_FLOW_PERIOD:u32 = 2;
+/**Lava flows once every this many ticks of the ambience pipeline*/
+pub const LAVA_FLOW_PERIOD:u32 = 8;
+/**How many blocks water flows sideways from its source (or from the bottom of a waterfall)*/
+pub const WATER_SPREAD:u32 = 7;
+/**How many blocks lava flows sideways from its source (or from the bottom of a lava fall)*/
+pub const LAVA_SPREAD:u32 = 3;
+/**Fluid level of source blocks. Flowing blocks have level equal to their distance from the source*/
+pub const FLUID_SOURCE_LEVEL:u32 = 0;
+
+/**Same as fluid_flow_period in constants.comp*/
+pub fn flow_period(fluid:BlockId)->u32{
+    if fluid == LAVA { LAVA_FLOW_PERIOD } else { WATER_FLOW_PERIOD }
+}
+/**Same as fluid_spread in constants.comp*/
+pub fn spread(fluid:BlockId)->u32{
+    if fluid == LAVA { LAVA_SPREAD } else { WATER_SPREAD }
+}
+fn flows_at(fluid:BlockId, tick:u32)->bool{
+    tick % flow_period(fluid) == 0
+}
+
+const HORIZONTAL_NEIGHBOURS:[(i32,i32);4] = [(1,0),(-1,0),(0,1),(0,-1)];
+
+fn block_at(world_size:&WorldSize, world:&[Block], x:i32, y:i32, z:i32)->Option<Block>{
+    if x < 0 || y < 0 || z < 0 || !world_size.is_position_in_bounds(x as usize, y as usize, z as usize){
+        return None
+    }
+    Some(world[world_size.block_pos_into_world_idx(x as usize, y as usize, z as usize)])
+}
+
+/**Lowest level that the given fluid could have at this position, if it flowed here from some neighbour.
+Fluid falls down from the block above (and starts its sideways spread anew) and flows sideways out of
+blocks that rest on something other than air*/
+fn incoming_level(world_size:&WorldSize, world:&[Block], x:i32, y:i32, z:i32, fluid:BlockId)->Option<u32>{
+    if let Some(above) = block_at(world_size, world, x, y + 1, z){
+        if above.block_id() == fluid{
+            return Some(1)
+        }
+    }
+    let mut level = None;
+    for &(dx,dz) in HORIZONTAL_NEIGHBOURS.iter(){
+        if let Some(neighbour) = block_at(world_size, world, x + dx, y, z + dz){
+            let is_supported = block_at(world_size, world, x + dx, y - 1, z + dz).map(|b|!b.block_id().is_air()).unwrap_or(true);
+            if neighbour.block_id() == fluid && neighbour.fluid_level() < spread(fluid) && is_supported{
+                let candidate = neighbour.fluid_level() + 1;
+                level = Some(level.map(|l:u32|l.min(candidate)).unwrap_or(candidate));
+            }
+        }
+    }
+    level
+}
+
+/**New block id and fluid level of the block at this position, or None if nothing changes. Lava touching water
+hardens into obsidian (source) or cobblestone (flowing). Flowing fluids take the lowest level that flows into them
+and dry up if nothing flows into them. Air is filled by the fluid that flows into it (cobblestone if both fluids do).
+Sources never change on their own. Mirrors main in update_ambience_fluids.comp*/
+pub fn next_fluid_state(world_size:&WorldSize, world:&[Block], x:usize, y:usize, z:usize, tick:u32)->Option<(BlockId,u32)>{
+    let block = world[world_size.block_pos_into_world_idx(x, y, z)];
+    let (x, y, z) = (x as i32, y as i32, z as i32);
+    let id = block.block_id();
+    if id == LAVA{
+        let touches_water = [(1,0,0),(-1,0,0),(0,1,0),(0,-1,0),(0,0,1),(0,0,-1)].iter()
+            .any(|&(dx,dy,dz)|block_at(world_size, world, x + dx, y + dy, z + dz).map(|b|b.block_id() == WATER).unwrap_or(false));
+        if touches_water{
+            return Some((if block.fluid_level() == FLUID_SOURCE_LEVEL { OBSIDIAN } else { COBBLESTONE }, 0))
+        }
+    }
+    if id.is_fluid(){
+        if block.fluid_level() == FLUID_SOURCE_LEVEL || !flows_at(id, tick){
+            return None
+        }
+        return match incoming_level(world_size, world, x, y, z, id){
+            Some(level) if level == block.fluid_level() => None,
+            Some(level) => Some((id, level)),
+            None => Some((AIR, 0)),
+        }
+    }
+    if id.is_air(){
+        let water = if flows_at(WATER, tick) { incoming_level(world_size, world, x, y, z, WATER) } else { None };
+        let lava = if flows_at(LAVA, tick) { incoming_level(world_size, world, x, y, z, LAVA) } else { None };
+        return match (water, lava){
+            (Some(_), Some(_)) => Some((COBBLESTONE, 0)),
+            (Some(level), None) => Some((WATER, level)),
+            (None, Some(level)) => Some((LAVA, level)),
+            (None, None) => None,
+        }
+    }
+    None
+}
+
+/**CPU reference of update_ambience_fluids.comp followed by update_ambience_commit.comp. All blocks see
+the world from before this tick. Returns indices of blocks whose id changed, in the order of the world buffer.
+On the GPU they are enqueued into blocks_to_be_inserted_or_removed (in arbitrary order), so that their faces get updated*/
+pub fn update_fluids(world_size:&WorldSize, world:&mut [Block], tick:u32)->Vec<usize>{
+    let mut changes = vec![];
+    for y in 0..world_size.height(){
+        for z in 0..world_size.world_depth(){
+            for x in 0..world_size.world_width(){
+                if let Some(change) = next_fluid_state(world_size, world, x, y, z, tick){
+                    changes.push((world_size.block_pos_into_world_idx(x, y, z), change));
+                }
+            }
+        }
+    }
+    let mut edited = vec![];
+    for (idx, (id, level)) in changes{
+        let block = &mut world[idx];
+        if block.block_id() != id{
+            block.set_block_id(id);
+            edited.push(idx);
+        }
+        block.set_fluid_level(level);
+    }
+    edited
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::blocks::block_properties::STONE;
+
+    /**Single chunk of air standing on a floor of stone at y=0*/
+    struct TestWorld{
+        world_size:WorldSize,
+        blocks:Vec<Block>,
+    }
+
+    impl TestWorld{
+        fn new()->Self{
+            let world_size = WorldSize::new(1, 1);
+            let mut w = Self{world_size, blocks:vec![Block::new(AIR);world_size.world_volume()]};
+            for z in 0..world_size.world_depth(){
+                for x in 0..world_size.world_width(){
+                    w.set(x, 0, z, STONE, 0);
+                }
+            }
+            w
+        }
+        fn set(&mut self, x:usize, y:usize, z:usize, id:BlockId, level:u32){
+            let block = &mut self.blocks[self.world_size.block_pos_into_world_idx(x, y, z)];
+            block.set_block_id(id);
+            block.set_fluid_level(level);
+        }
+        fn get(&self, x:usize, y:usize, z:usize)->(BlockId,u32){
+            let block = self.blocks[self.world_size.block_pos_into_world_idx(x, y, z)];
+            (block.block_id(), block.fluid_level())
+        }
+        fn run(&mut self, ticks:std::ops::Range<u32>){
+            for tick in ticks{
+                update_fluids(&self.world_size, &mut self.blocks, tick);
+            }
+        }
+        fn count(&self, id:BlockId)->usize{
+            self.blocks.iter().filter(|b|b.block_id() == id).count()
+        }
+    }
+
+    #[test]
+    fn source_spreads_up_to_water_spread(){
+        let mut w = TestWorld::new();
+        w.set(4, 1, 4, WATER, FLUID_SOURCE_LEVEL);
+        w.run(0..64);
+        assert_eq!(w.get(4, 1, 4), (WATER, FLUID_SOURCE_LEVEL));
+        assert_eq!(w.get(5, 1, 4), (WATER, 1));
+        assert_eq!(w.get(4 + WATER_SPREAD as usize, 1, 4), (WATER, WATER_SPREAD));
+        assert_eq!(w.get(8, 1, 7), (WATER, WATER_SPREAD));
+        assert_eq!(w.get(5 + WATER_SPREAD as usize, 1, 4).0, AIR);
+        assert_eq!(w.get(8, 1, 8).0, AIR);
+        assert_eq!(w.get(4, 2, 4).0, AIR);
+    }
+
+    #[test]
+    fn waterfall_restarts_spread_at_level_1(){
+        let mut w = TestWorld::new();
+        w.set(4, 4, 4, STONE, 0);
+        w.set(4, 5, 4, WATER, FLUID_SOURCE_LEVEL);
+        w.run(0..WATER_FLOW_PERIOD + 1);
+        assert_eq!(w.get(5, 5, 4), (WATER, 1));
+        assert_eq!(w.get(5, 4, 4), (WATER, 1));
+        // water doesn't flow sideways out of blocks hanging above air
+        assert_eq!(w.get(6, 5, 4).0, AIR);
+        // the fall takes one flow per block and the spread at the bottom starts right after it
+        w.run(WATER_FLOW_PERIOD + 1..WATER_FLOW_PERIOD * 5 + 1);
+        for y in 1..5{
+            assert_eq!(w.get(5, y, 4), (WATER, 1), "y={}", y);
+        }
+        assert_eq!(w.get(6, 1, 4), (WATER, 2));
+    }
+
+    #[test]
+    fn flowing_water_dries_up_without_source(){
+        let mut w = TestWorld::new();
+        w.set(4, 1, 4, WATER, FLUID_SOURCE_LEVEL);
+        w.run(0..64);
+        assert!(w.count(WATER) > 1);
+        w.set(4, 1, 4, AIR, 0);
+        w.run(64..128);
+        assert_eq!(w.count(WATER), 0);
+    }
+
+    #[test]
+    fn lava_touching_water_hardens(){
+        let mut w = TestWorld::new();
+        w.set(4, 1, 4, LAVA, FLUID_SOURCE_LEVEL);
+        w.set(5, 1, 4, WATER, 3);
+        w.set(8, 1, 8, LAVA, 2);
+        w.set(8, 2, 8, WATER, 1);
+        // lava hardens regardless of its flow period
+        assert_eq!(next_fluid_state(&w.world_size, &w.blocks, 4, 1, 4, 1), Some((OBSIDIAN, 0)));
+        assert_eq!(next_fluid_state(&w.world_size, &w.blocks, 8, 1, 8, 1), Some((COBBLESTONE, 0)));
+        w.run(1..2);
+        assert_eq!(w.get(4, 1, 4).0, OBSIDIAN);
+        assert_eq!(w.get(8, 1, 8).0, COBBLESTONE);
+    }
+
+    #[test]
+    fn air_reached_by_both_fluids_becomes_cobblestone(){
+        let mut w = TestWorld::new();
+        w.set(4, 1, 5, WATER, 1);
+        w.set(6, 1, 5, LAVA, 1);
+        assert_eq!(next_fluid_state(&w.world_size, &w.blocks, 5, 1, 5, 0), Some((COBBLESTONE, 0)));
+        // in ticks when lava doesn't flow, only water gets there
+        assert_eq!(next_fluid_state(&w.world_size, &w.blocks, 5, 1, 5, WATER_FLOW_PERIOD), Some((WATER, 2)));
+    }
+
+    #[test]
+    fn lava_flows_only_every_lava_flow_period(){
+        let mut w = TestWorld::new();
+        w.set(4, 1, 4, LAVA, FLUID_SOURCE_LEVEL);
+        for tick in 1..LAVA_FLOW_PERIOD{
+            assert!(update_fluids(&w.world_size, &mut w.blocks, tick).is_empty(), "tick {}", tick);
+        }
+        assert_eq!(update_fluids(&w.world_size, &mut w.blocks, LAVA_FLOW_PERIOD).len(), 4);
+        assert_eq!(w.get(5, 1, 4), (LAVA, 1));
+        w.run(LAVA_FLOW_PERIOD + 1..LAVA_FLOW_PERIOD * 8);
+        assert_eq!(w.get(4 + LAVA_SPREAD as usize, 1, 4), (LAVA, LAVA_SPREAD));
+        assert_eq!(w.get(5 + LAVA_SPREAD as usize, 1, 4).0, AIR);
+    }
+}
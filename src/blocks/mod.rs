@@ -5,10 +5,13 @@ mod face_orientation;
 pub mod world_size;
 mod raycast;
 mod block_meta;
+pub mod fluid;
+pub mod climate;
 
 pub use block::Block;
 pub use block::BlockId;
 pub use block_meta::BlockMeta;
 pub use face::Face;
 pub use face_orientation::FaceOrientation;
-pub use world_size::WorldSize;
\ No newline at end of file
+pub use world_size::WorldSize;
+pub use raycast::ray_cast;
\ No newline at end of file
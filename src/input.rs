@@ -28,10 +28,23 @@ pub struct Input {
     has_mouse_right_click: bool,
     has_mouse_left_down: bool,
     has_mouse_right_down: bool,
+    has_mouse_middle_click: bool,
+    has_mouse_middle_down: bool,
     q: bool,
     e: bool,
     pause: bool,
-    next: bool,
+    speed_up: bool,
+    slow_down: bool,
+    max_speed: bool,
+    sprint: bool,
+    toggle_fly: bool,
+    save: bool,
+    load: bool,
+    spawn_random: bool,
+    spawn_clone: bool,
+    spawn_from_file: bool,
+    attach_rope: bool,
+    detach_ropes: bool,
     r: bool,
     no0: bool,
     no1: bool,
@@ -74,6 +87,8 @@ impl Input {
             has_mouse_right_click: false,
             has_mouse_left_down: false,
             has_mouse_right_down: false,
+            has_mouse_middle_click: false,
+            has_mouse_middle_down: false,
             q: false,
             e: false,
             r: false,
@@ -89,7 +104,18 @@ impl Input {
             no8: false,
             no9: false,
             number: 0,
-            next: false,
+            speed_up: false,
+            slow_down: false,
+            max_speed: false,
+            sprint: false,
+            toggle_fly: false,
+            save: false,
+            load: false,
+            spawn_random: false,
+            spawn_clone: false,
+            spawn_from_file: false,
+            attach_rope: false,
+            detach_ropes: false,
             verbose: false,
         }
     }
@@ -101,9 +127,20 @@ impl Input {
         self.has_mouse_move = false;
         self.has_mouse_left_click = false;
         self.has_mouse_right_click = false;
+        self.has_mouse_middle_click = false;
         self.q = false;
         self.pause = false;
-        self.next = false;
+        self.speed_up = false;
+        self.slow_down = false;
+        self.max_speed = false;
+        self.toggle_fly = false;
+        self.save = false;
+        self.load = false;
+        self.spawn_random = false;
+        self.spawn_clone = false;
+        self.spawn_from_file = false;
+        self.attach_rope = false;
+        self.detach_ropes = false;
         self.number = -1;
         self.prev_mouse_x = self.mouse_x;
         self.prev_mouse_y = self.mouse_y;
@@ -187,19 +224,42 @@ impl Input {
                                 }
                                 winit::event::VirtualKeyCode::R => {
                                     self.r = true;
+                                    self.spawn_clone = true;
                                 }
                                 winit::event::VirtualKeyCode::E => {
                                     self.e = true;
+                                    self.spawn_random = true;
+                                }
+                                winit::event::VirtualKeyCode::T => {
+                                    self.spawn_from_file = true;
+                                }
+                                winit::event::VirtualKeyCode::F => {
+                                    self.attach_rope = true;
+                                }
+                                winit::event::VirtualKeyCode::G => {
+                                    self.detach_ropes = true;
                                 }
                                 winit::event::VirtualKeyCode::Q => {
                                     self.q = true;
                                 }
                                 winit::event::VirtualKeyCode::Right => {
-                                    self.next = true;
+                                    self.speed_up = true;
+                                }
+                                winit::event::VirtualKeyCode::Left => {
+                                    self.slow_down = true;
+                                }
+                                winit::event::VirtualKeyCode::Up => {
+                                    self.max_speed = true;
                                 }
                                 winit::event::VirtualKeyCode::P => {
                                     self.pause = true;
                                 }
+                                winit::event::VirtualKeyCode::F5 => {
+                                    self.save = true;
+                                }
+                                winit::event::VirtualKeyCode::F9 => {
+                                    self.load = true;
+                                }
                                 winit::event::VirtualKeyCode::D => {
                                     self.right = true;
                                 }
@@ -218,6 +278,12 @@ impl Input {
                                 winit::event::VirtualKeyCode::LShift => {
                                     self.down = true;
                                 }
+                                winit::event::VirtualKeyCode::LControl => {
+                                    self.sprint = true;
+                                }
+                                winit::event::VirtualKeyCode::V => {
+                                    self.toggle_fly = true;
+                                }
                                 winit::event::VirtualKeyCode::Escape => {
                                     self.escape = true;
                                 }
@@ -285,6 +351,9 @@ impl Input {
                                 winit::event::VirtualKeyCode::LShift => {
                                     self.down = false;
                                 }
+                                winit::event::VirtualKeyCode::LControl => {
+                                    self.sprint = false;
+                                }
                                 winit::event::VirtualKeyCode::Escape => {
                                     self.escape = false;
                                 }
@@ -327,6 +396,12 @@ impl Input {
                             }
                             self.has_mouse_right_down = true;
                         }
+                        MouseButton::Middle => {
+                            if !self.has_mouse_middle_down {
+                                self.has_mouse_middle_click = true;
+                            }
+                            self.has_mouse_middle_down = true;
+                        }
                         _ => {}
                     }
                     ElementState::Released => match button {
@@ -336,6 +411,9 @@ impl Input {
                         MouseButton::Right => {
                             self.has_mouse_right_down = false;
                         }
+                        MouseButton::Middle => {
+                            self.has_mouse_middle_down = false;
+                        }
                         _ => {}
                     }
                 }
@@ -367,6 +445,14 @@ impl Input {
     pub fn has_mouse_right_down(&self) -> bool {
         self.has_mouse_right_down
     }
+    /**Grab the bone that the player is looking at*/
+    pub fn has_mouse_middle_click(&self) -> bool {
+        self.has_mouse_middle_click
+    }
+    /**The grabbed bone is held for as long as the button is down*/
+    pub fn has_mouse_middle_down(&self) -> bool {
+        self.has_mouse_middle_down
+    }
     pub fn mouse_x(&self) -> f64 {
         self.mouse_x
     }
@@ -385,8 +471,57 @@ impl Input {
     pub fn pause(&self) -> bool {
         self.pause
     }
-    pub fn next(&self) -> bool {
-        self.next
+    /**Double the time scale of the simulation*/
+    pub fn speed_up(&self) -> bool {
+        self.speed_up
+    }
+    /**Halve the time scale of the simulation*/
+    pub fn slow_down(&self) -> bool {
+        self.slow_down
+    }
+    /**Toggle running as many simulation steps per frame as allowed, regardless of the time scale*/
+    pub fn max_speed(&self) -> bool {
+        self.max_speed
+    }
+    pub fn save(&self) -> bool {
+        self.save
+    }
+    pub fn load(&self) -> bool {
+        self.load
+    }
+    /**Spawn an entity with a random brain*/
+    pub fn spawn_random(&self) -> bool {
+        self.spawn_random
+    }
+    /**Spawn a copy of the entity that the player is looking at*/
+    pub fn spawn_clone(&self) -> bool {
+        self.spawn_clone
+    }
+    /**Spawn an entity with the next brain from the brain file*/
+    pub fn spawn_from_file(&self) -> bool {
+        self.spawn_from_file
+    }
+    /**Pick one end of a rope, or tie the rope if the other end is already picked*/
+    pub fn attach_rope(&self) -> bool {
+        self.attach_rope
+    }
+    pub fn detach_ropes(&self) -> bool {
+        self.detach_ropes
+    }
+    /**Space is held. Walking player jumps (or swims up)*/
+    pub fn is_up(&self) -> bool {
+        self.up
+    }
+    /**Left shift is held. Walking player crouches*/
+    pub fn is_down(&self) -> bool {
+        self.down
+    }
+    pub fn is_sprint(&self) -> bool {
+        self.sprint
+    }
+    /**Switch between walking and flying*/
+    pub fn toggle_fly(&self) -> bool {
+        self.toggle_fly
     }
     pub fn get_direction_unit_vector(&self) -> glm::TVec3<f32> {
         let x_axis = -(self.left as i32) + (self.right as i32);
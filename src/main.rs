@@ -8,6 +8,7 @@ extern crate memoffset;
 use ash::vk;
 use failure::err_msg;
 use winit::dpi::PhysicalSize;
+use std::time::Instant;
 use winit::event::{Event, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
 
@@ -22,11 +23,15 @@ use pipelines::player::Player;
 
 use crate::fps::FpsCounter;
 use crate::input::Input;
+use crate::physics_timer::PhysicsTimer;
 
 
 use crate::render::vulkan_context::VulkanContext;
 use crate::pipelines::physics::PhysicsResources;
 use crate::pipelines::ambience::AmbienceResources;
+use crate::neat::brain_file;
+use crate::neat::genealogy::Genealogy;
+use crate::metrics::MetricsLogger;
 
 
 mod render;
@@ -36,15 +41,50 @@ mod blocks;
 mod pipelines;
 mod physics_timer;
 mod neat;
+mod metrics;
 
 use winit::platform::windows::WindowExtWindows;
 
+const BRAINS_FILE:&'static str = "brains.bin";
+const BRAINS_JSON_FILE:&'static str = "brains.json";
+const GENEALOGY_CSV_FILE:&'static str = "genealogy.csv";
+const GENEALOGY_NEWICK_FILE:&'static str = "genealogy.nwk";
+const LINEAGES_CSV_FILE:&'static str = "lineages.csv";
+/**Lifecycle events are read back from GPU at least every this many simulation steps, so that the buffer never fills up.
+Metrics are sampled at the first drain at which they are due, so drains happen more often when metrics have a shorter period*/
+const LIFECYCLE_EVENTS_DRAIN_PERIOD:u32 = 64;
+/**Simulation steps per second of real time when time scale is 1x*/
+const SIMULATION_STEPS_PER_SECOND:u32 = 60;
+/**If the GPU can't keep up, the simulation slows down rather than running ever more steps per frame. This is also the number of steps per frame at max speed*/
+const MAX_SIMULATION_STEPS_PER_FRAME:u32 = 64;
+
 
 fn main() -> Result<(), failure::Error> {
     run()
 }
 
 
+fn drain_lifecycle_events(display:&mut Display<GameResources, PhysicsResources, AmbienceResources>, genealogy:&mut Genealogy, metrics:&mut Option<MetricsLogger>) -> Result<(), failure::Error> {
+    let (events, lost, mutables, sample) = display.drain_lifecycle_events(|tick| metrics.as_ref().map_or(false, |m| m.is_due(tick)))?;
+    genealogy.record_all(&events);
+    if lost > 0 {
+        println!("Lost {} lifecycle events. Genealogy will be incomplete", lost);
+        genealogy.add_lost_events(lost as u64);
+    }
+    let collision_grid_overflows = mutables.collision_grid_overflows;
+    if collision_grid_overflows > 0 {
+        println!("Bones did not fit into the collision grid {} times since the last drain and were ignored by collision detection", collision_grid_overflows);
+    }
+    if let Some(metrics) = metrics {
+        metrics.record_lifecycle_events(&events, lost);
+        metrics.record_collision_grid_overflows(collision_grid_overflows);
+        if let Some(sample) = sample {
+            metrics.log(sample)?;
+        }
+    }
+    Ok(())
+}
+
 fn run() -> Result<(), failure::Error> {
     let event_loop = EventLoop::new();
     let window = winit::window::WindowBuilder::new()
@@ -67,8 +107,15 @@ fn run() -> Result<(), failure::Error> {
     // let event_pump = sdl.event_pump().map_err(err_msg)?;
     let mut input = Input::new(display.window());
     let mut fps_counter = FpsCounter::new(60);
+    let mut physics_timer = PhysicsTimer::new(SIMULATION_STEPS_PER_SECOND, MAX_SIMULATION_STEPS_PER_FRAME);
+    let mut previous_frame = Instant::now();
     input.set_verbose(true);
     let mut run_simulation = false;
+    let mut genealogy = Genealogy::new();
+    let mut steps_since_drain = 0;
+    let mut next_spawned_brain = 0;
+    let mut metrics = MetricsLogger::from_env(&display.run_header())?;
+    let drain_period = metrics.as_ref().map_or(LIFECYCLE_EVENTS_DRAIN_PERIOD, |m| m.period().min(LIFECYCLE_EVENTS_DRAIN_PERIOD));
     player.resize(&display);
     display.rerecord_all_graphics_cmd_buffers()?;
     display.record_compute_cmd_buffer()?;
@@ -98,12 +145,80 @@ fn run() -> Result<(), failure::Error> {
                 if input.pause() {
                     run_simulation = !run_simulation;
                 }
+                if input.speed_up() || input.slow_down() {
+                    if input.speed_up() {
+                        physics_timer.speed_up();
+                    } else {
+                        physics_timer.slow_down();
+                    }
+                    println!("Time scale: {}x", physics_timer.time_scale());
+                }
+                if input.max_speed() {
+                    physics_timer.toggle_max_speed();
+                    println!("Max speed: {}", physics_timer.is_max_speed());
+                }
 
-                player.update(&mut display, &input, &fps_counter);
+                if input.save() {
+                    match display.download_brains() {
+                        Ok(brains) => match brain_file::save_binary(BRAINS_FILE, &brains).and_then(|()| brain_file::save_json(BRAINS_JSON_FILE, &brains)) {
+                            Ok(()) => println!("Saved {} brains to {}", brains.len(), BRAINS_FILE),
+                            Err(err) => println!("Could not save brains to {}: {}", BRAINS_FILE, err)
+                        }
+                        Err(err) => println!("Could not download brains: {}", err)
+                    }
+                    if let Err(err) = drain_lifecycle_events(&mut display, &mut genealogy, &mut metrics) {
+                        println!("Could not drain lifecycle events: {}", err)
+                    }
+                    steps_since_drain = 0;
+                    match genealogy.save_csv(GENEALOGY_CSV_FILE)
+                        .and_then(|()| genealogy.save_newick(GENEALOGY_NEWICK_FILE))
+                        .and_then(|()| genealogy.save_lineage_stats_csv(LINEAGES_CSV_FILE)) {
+                        Ok(()) => println!("Saved genealogy of {} entities ({} alive, {} lost events) to {}, {} and {}", genealogy.records().len(),
+                                           genealogy.alive(), genealogy.lost_events(), GENEALOGY_CSV_FILE, GENEALOGY_NEWICK_FILE, LINEAGES_CSV_FILE),
+                        Err(err) => println!("Could not save genealogy: {}", err)
+                    }
+                }
+                if input.load() {
+                    match brain_file::load_binary(BRAINS_FILE) {
+                        Ok(brains) => match display.inject_brains(&brains) {
+                            Ok(injected) => println!("Injected {} out of {} brains from {}", injected, brains.len(), BRAINS_FILE),
+                            Err(err) => println!("Could not inject brains from {}: {}", BRAINS_FILE, err)
+                        }
+                        Err(err) => println!("Could not load {}: {}", BRAINS_FILE, err)
+                    }
+                }
+                if input.spawn_from_file() {
+                    // every press spawns the next brain from the file
+                    match brain_file::load_binary(BRAINS_FILE) {
+                        Ok(brains) if !brains.is_empty() => {
+                            let brain = &brains[next_spawned_brain % brains.len()];
+                            next_spawned_brain += 1;
+                            if let Err(err) = display.set_spawn_template(brain) {
+                                println!("Could not spawn brain from {}: {}", BRAINS_FILE, err)
+                            }
+                        }
+                        Ok(_) => println!("There are no brains in {}", BRAINS_FILE),
+                        Err(err) => println!("Could not load {}: {}", BRAINS_FILE, err)
+                    }
+                }
+                if let Err(err) = player.update(&mut display, &input, &fps_counter) {
+                    println!("Could not update the player: {}", err)
+                }
                 // player.send_events(sx);
-                if run_simulation || input.next() {
+                let current_frame = Instant::now();
+                let steps = physics_timer.advance(current_frame - previous_frame, run_simulation);
+                previous_frame = current_frame;
+                for _ in 0..steps {
                     display.compute(&mut player).unwrap();
+                    steps_since_drain += 1;
+                    if steps_since_drain >= drain_period {
+                        if let Err(err) = drain_lifecycle_events(&mut display, &mut genealogy, &mut metrics) {
+                            println!("Could not drain lifecycle events: {}", err)
+                        }
+                        steps_since_drain = 0;
+                    }
                 }
+                player.set_bone_interpolation(physics_timer.interpolation());
 
                 // render
 
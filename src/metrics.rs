@@ -0,0 +1,326 @@
+use crate::pipelines::lifecycle_record::{LifecycleRecord, NO_ENTITY_ID};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/**State of the simulation at a single tick. Births and deaths are counted since the previous sample.
+Births only include children spawned by reproduction, not entities generated with the world or injected from brain files*/
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct MetricsSample{
+    pub tick:i32,
+    pub ann_entities:u32,
+    pub htm_entities:u32,
+    /**Energy statistics of ANN entities. All of them are 0 if there are no entities*/
+    pub mean_energy:f32,
+    pub min_energy:f32,
+    pub max_energy:f32,
+    pub births:u32,
+    pub deaths:u32,
+    pub bones:u32,
+    pub max_bones:u32,
+    /**Opaque and transparent faces of all chunks*/
+    pub faces:u32,
+    pub max_faces:u32,
+    /**Blocks that are waiting to be inserted or removed by update_ambience*/
+    pub pending_block_edits:i32,
+    /**Lifecycle records lost since the previous sample, because the buffer was full. Births and deaths are underestimated if this is not 0*/
+    pub lost_lifecycle_events:u32,
+    /**Bones ignored by collision detection because their cell of the collision grid was full, summed over all physics steps since the previous sample*/
+    pub collision_grid_overflows:u32,
+}
+
+const CSV_COLUMNS:&str = "tick,ann_entities,htm_entities,mean_energy,min_energy,max_energy,births,deaths,bones,max_bones,faces,max_faces,pending_block_edits,lost_lifecycle_events,collision_grid_overflows";
+
+impl MetricsSample{
+    /**Fills in energy statistics*/
+    pub fn set_energies(&mut self, energies:impl Iterator<Item=f32>){
+        let mut count = 0;
+        let mut sum = 0.;
+        let mut min = f32::INFINITY;
+        let mut max = f32::NEG_INFINITY;
+        for e in energies{
+            count += 1;
+            sum += e;
+            min = min.min(e);
+            max = max.max(e);
+        }
+        if count == 0{
+            self.mean_energy = 0.;
+            self.min_energy = 0.;
+            self.max_energy = 0.;
+        }else{
+            self.mean_energy = sum / count as f32;
+            self.min_energy = min;
+            self.max_energy = max;
+        }
+    }
+    /**Values in the order of CSV_COLUMNS. Floats are formatted by the given function*/
+    fn fields(&self, float:impl Fn(f32)->String)->[String;15]{
+        [self.tick.to_string(), self.ann_entities.to_string(), self.htm_entities.to_string(),
+            float(self.mean_energy), float(self.min_energy), float(self.max_energy),
+            self.births.to_string(), self.deaths.to_string(), self.bones.to_string(), self.max_bones.to_string(),
+            self.faces.to_string(), self.max_faces.to_string(), self.pending_block_edits.to_string(), self.lost_lifecycle_events.to_string(),
+            self.collision_grid_overflows.to_string()]
+    }
+    pub fn to_csv_row(&self)->String{
+        self.fields(|f|f.to_string()).join(",")
+    }
+    /**Non-finite floats are written as null, because JSON has no NaN or infinity*/
+    pub fn to_json(&self)->String{
+        let fields = self.fields(|f|if f.is_finite(){ f.to_string() }else{ String::from("null") });
+        let body:Vec<String> = CSV_COLUMNS.split(',').zip(fields.iter()).map(|(name,value)|format!("\"{}\":{}", name, value)).collect();
+        format!("{{{}}}", body.join(","))
+    }
+}
+
+/**Quotes the string and escapes it according to the JSON grammar*/
+fn json_string(s:&str)->String{
+    let mut out = String::with_capacity(s.len()+2);
+    out.push('"');
+    for c in s.chars(){
+        match c{
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+    out
+}
+
+/**Describes the run, so that logs of different runs can be told apart and reproduced. Values must be valid JSON
+(numbers, or strings quoted and escaped by entry_str)*/
+#[derive(Clone, Debug, Default)]
+pub struct RunHeader{
+    pub seed:u32,
+    pub config:Vec<(String,String)>,
+}
+
+impl RunHeader{
+    pub fn new(seed:u32)->Self{
+        Self{seed, config:vec![]}
+    }
+    pub fn entry(&mut self, name:&str, value:impl ToString)->&mut Self{
+        self.config.push((name.to_string(), value.to_string()));
+        self
+    }
+    pub fn entry_str(&mut self, name:&str, value:&str)->&mut Self{
+        self.config.push((name.to_string(), json_string(value)));
+        self
+    }
+    pub fn to_json(&self)->String{
+        let config:Vec<String> = self.config.iter().map(|(name,value)|format!("{}:{}", json_string(name), value)).collect();
+        format!("{{\"run\":{{\"seed\":{},\"config\":{{{}}}}}}}", self.seed, config.join(","))
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum MetricsFormat{
+    Csv,
+    Jsonl,
+}
+
+impl MetricsFormat{
+    /**Files ending with .jsonl or .json get one JSON object per line. Anything else is CSV*/
+    pub fn from_path(path:&Path)->Self{
+        match path.extension().and_then(|e|e.to_str()){
+            Some("jsonl") | Some("json") => MetricsFormat::Jsonl,
+            _ => MetricsFormat::Csv
+        }
+    }
+}
+
+/**Writes a sample every `period` ticks. It does not talk to the GPU on its own, so it can be driven by the windowed app
+as well as by a headless runner. The caller feeds it with lifecycle events (to count births and deaths)
+and with samples (see Foundations::sample_metrics) whenever is_due returns true.
+CSV files start with the run header in lines prefixed with #, JSONL files start with the run header object.*/
+pub struct MetricsLogger{
+    out:BufWriter<File>,
+    format:MetricsFormat,
+    period:u32,
+    next_sample_tick:i32,
+    births:u32,
+    deaths:u32,
+    lost_lifecycle_events:u32,
+    collision_grid_overflows:u32,
+}
+
+impl MetricsLogger{
+    pub fn new(path:impl AsRef<Path>, period:u32, header:&RunHeader)->Result<Self,failure::Error>{
+        assert!(period>0);
+        let format = MetricsFormat::from_path(path.as_ref());
+        let mut out = BufWriter::new(File::create(path)?);
+        match format{
+            MetricsFormat::Csv => {
+                writeln!(out, "# seed={}", header.seed)?;
+                for (name,value) in &header.config{
+                    writeln!(out, "# {}={}", name, value)?;
+                }
+                writeln!(out, "{}", CSV_COLUMNS)?;
+            }
+            MetricsFormat::Jsonl => writeln!(out, "{}", header.to_json())?
+        }
+        out.flush()?;
+        Ok(Self{out, format, period, next_sample_tick:0, births:0, deaths:0, lost_lifecycle_events:0, collision_grid_overflows:0})
+    }
+    /**Reads the path from EVOLUTIO_METRICS and the period from EVOLUTIO_METRICS_PERIOD (defaults to 100 ticks).
+    Returns None if EVOLUTIO_METRICS is not set*/
+    pub fn from_env(header:&RunHeader)->Result<Option<Self>,failure::Error>{
+        let path = match std::env::var("EVOLUTIO_METRICS"){
+            Ok(path) => path,
+            Err(_) => return Ok(None)
+        };
+        let period = match std::env::var("EVOLUTIO_METRICS_PERIOD"){
+            Ok(period) => period.parse().ok().filter(|&p|p>0).ok_or_else(||failure::err_msg(format!("EVOLUTIO_METRICS_PERIOD={} is not a positive number", period)))?,
+            Err(_) => 100
+        };
+        Self::new(path, period, header).map(Some)
+    }
+    pub fn period(&self)->u32{
+        self.period
+    }
+    pub fn is_due(&self, tick:i32)->bool{
+        tick >= self.next_sample_tick
+    }
+    pub fn record_lifecycle_events(&mut self, events:&[LifecycleRecord], lost:u32){
+        for e in events{
+            if !e.is_birth(){
+                self.deaths += 1
+            }else if e.parent_id() != NO_ENTITY_ID{
+                self.births += 1
+            }
+        }
+        self.lost_lifecycle_events += lost;
+    }
+    pub fn record_collision_grid_overflows(&mut self, overflows:u32){
+        self.collision_grid_overflows += overflows;
+    }
+    /**Completes the sample with births, deaths and overflows counted since the previous one and writes it out*/
+    pub fn log(&mut self, mut sample:MetricsSample)->Result<(),failure::Error>{
+        sample.births = std::mem::replace(&mut self.births, 0);
+        sample.deaths = std::mem::replace(&mut self.deaths, 0);
+        sample.lost_lifecycle_events = std::mem::replace(&mut self.lost_lifecycle_events, 0);
+        sample.collision_grid_overflows = std::mem::replace(&mut self.collision_grid_overflows, 0);
+        match self.format{
+            MetricsFormat::Csv => writeln!(self.out, "{}", sample.to_csv_row())?,
+            MetricsFormat::Jsonl => writeln!(self.out, "{}", sample.to_json())?
+        }
+        self.out.flush()?;
+        self.next_sample_tick = sample.tick + self.period as i32;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn sample(tick:i32)->MetricsSample{
+        let mut sample = MetricsSample{tick, ann_entities:2, htm_entities:1, bones:10, max_bones:64, faces:300, max_faces:1000,
+            pending_block_edits:-1, collision_grid_overflows:4, ..MetricsSample::default()};
+        sample.set_energies([0.5, 2.5].iter().cloned());
+        sample
+    }
+
+    /**Logger that writes into a fresh file in the temporary directory*/
+    fn logger(name:&str, period:u32)->(MetricsLogger,std::path::PathBuf){
+        let path = std::env::temp_dir().join(format!("evolutio_{}_{}", std::process::id(), name));
+        let mut header = RunHeader::new(7);
+        header.entry("world_width", 32).entry_str("body", "crawler");
+        (MetricsLogger::new(&path, period, &header).unwrap(), path)
+    }
+
+    fn read_and_remove(path:&Path)->Vec<String>{
+        let content = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        content.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn csv_row_follows_the_header(){
+        assert_eq!(CSV_COLUMNS.split(',').count(), sample(0).fields(|f|f.to_string()).len());
+        assert_eq!(sample(5).to_csv_row(), "5,2,1,1.5,0.5,2.5,0,0,10,64,300,1000,-1,0,4");
+        let empty = MetricsSample{tick:5, ..MetricsSample::default()};
+        let mut with_no_entities = empty;
+        with_no_entities.set_energies(std::iter::empty());
+        assert_eq!(with_no_entities, empty);
+    }
+
+    #[test]
+    fn json_has_a_field_per_column(){
+        assert_eq!(sample(5).to_json(), "{\"tick\":5,\"ann_entities\":2,\"htm_entities\":1,\"mean_energy\":1.5,\"min_energy\":0.5,\"max_energy\":2.5,\
+            \"births\":0,\"deaths\":0,\"bones\":10,\"max_bones\":64,\"faces\":300,\"max_faces\":1000,\"pending_block_edits\":-1,\
+            \"lost_lifecycle_events\":0,\"collision_grid_overflows\":4}");
+    }
+
+    #[test]
+    fn json_writes_non_finite_floats_as_null(){
+        let mut s = sample(5);
+        s.mean_energy = f32::NAN;
+        s.min_energy = f32::NEG_INFINITY;
+        s.max_energy = f32::INFINITY;
+        let json = s.to_json();
+        assert!(json.contains("\"mean_energy\":null,\"min_energy\":null,\"max_energy\":null,"), "{}", json);
+        assert!(!json.contains("NaN") && !json.contains("inf"));
+    }
+
+    #[test]
+    fn run_header_escapes_strings(){
+        let mut header = RunHeader::new(3);
+        header.entry("max_bones", 64).entry_str("path", "C:\\runs\\\"a\"\n\u{1}");
+        assert_eq!(header.to_json(), "{\"run\":{\"seed\":3,\"config\":{\"max_bones\":64,\"path\":\"C:\\\\runs\\\\\\\"a\\\"\\n\\u0001\"}}}");
+    }
+
+    #[test]
+    fn samples_are_due_every_period(){
+        let (mut logger, path) = logger("due.csv", 100);
+        assert!(logger.is_due(0));
+        logger.log(sample(3)).unwrap();
+        assert!(!logger.is_due(3));
+        assert!(!logger.is_due(102));
+        assert!(logger.is_due(103));
+        // a late sample shifts the schedule
+        logger.log(sample(150)).unwrap();
+        assert!(!logger.is_due(249));
+        assert!(logger.is_due(250));
+        read_and_remove(&path);
+    }
+
+    #[test]
+    fn births_deaths_and_overflows_accumulate_between_samples(){
+        let (mut logger, path) = logger("births.csv", 10);
+        logger.record_lifecycle_events(&[LifecycleRecord::birth(1, NO_ENTITY_ID, 0), LifecycleRecord::birth(2, 1, 1)], 0);
+        logger.record_collision_grid_overflows(3);
+        logger.record_lifecycle_events(&[LifecycleRecord::death(1, 2), LifecycleRecord::birth(3, 2, 3), LifecycleRecord::death(3, 4)], 2);
+        logger.record_collision_grid_overflows(1);
+        logger.log(sample(5)).unwrap();
+        logger.record_lifecycle_events(&[LifecycleRecord::death(2, 12)], 0);
+        logger.log(sample(15)).unwrap();
+        let lines = read_and_remove(&path);
+        assert_eq!(lines, vec![
+            "# seed=7".to_string(),
+            "# world_width=32".to_string(),
+            "# body=\"crawler\"".to_string(),
+            CSV_COLUMNS.to_string(),
+            // founders are not counted as births
+            MetricsSample{births:2, deaths:2, lost_lifecycle_events:2, collision_grid_overflows:4, ..sample(5)}.to_csv_row(),
+            MetricsSample{births:0, deaths:1, lost_lifecycle_events:0, collision_grid_overflows:0, ..sample(15)}.to_csv_row(),
+        ]);
+    }
+
+    #[test]
+    fn jsonl_starts_with_the_run_header(){
+        let (mut logger, path) = logger("log.jsonl", 10);
+        logger.record_collision_grid_overflows(sample(0).collision_grid_overflows);
+        logger.log(sample(0)).unwrap();
+        let lines = read_and_remove(&path);
+        assert_eq!(lines, vec![
+            "{\"run\":{\"seed\":7,\"config\":{\"world_width\":32,\"body\":\"crawler\"}}}".to_string(),
+            sample(0).to_json(),
+        ]);
+    }
+}
@@ -1,59 +1,1385 @@
-const BLOCK_SENSORY_FEATURES_LEN:usize = 6;
-const ANN_LIDAR_COUNT:usize = 32;
-const BLOCK_EXTENDED_SENSORY_FEATURES_LEN:usize = BLOCK_SENSORY_FEATURES_LEN+2;// + block_mass and is_air
-const ANN_TOUCHED_BLOCK_COUNT:usize = 8;//cube has 8 corners
-const ANN_INPUT_SIZE:usize = ANN_LIDAR_COUNT+BLOCK_EXTENDED_SENSORY_FEATURES_LEN*ANN_TOUCHED_BLOCK_COUNT;
-const ANN_HIDDEN_SIZE:usize = 32;
-const ANN_INPUT_CONNECTIONS_PER_HIDDEN_NEURON:usize = 16;
-const ANN_LATENT_SIZE:usize = 32;
-const ANN_HIDDEN_CONNECTIONS_PER_LATENT_NEURON:usize = 4;
-const ANN_LATENT_CONNECTIONS_PER_LATENT_NEURON:usize = 4;
-const ANN_OUTPUT_ATTACK_MUSCLES_SIZE:usize = 4;
-const ANN_OUTPUT_MOVEMENT_MUSCLES_SIZE:usize = 20;
-const ANN_OUTPUT_ROTATION_MUSCLES_SIZE:usize = 8;
-const ANN_OUTPUT_SIZE:usize = ANN_OUTPUT_MOVEMENT_MUSCLES_SIZE+ANN_OUTPUT_ATTACK_MUSCLES_SIZE+ANN_OUTPUT_ROTATION_MUSCLES_SIZE;
-const ANN_LATENT_CONNECTIONS_PER_OUTPUT_NEURON:usize = 4;
+use crate::pipelines::bone::Bone;
+use crate::pipelines::lifecycle_record::NO_ENTITY_ID;
+use crate::neat::body::Body;
+use crate::blocks::BlockId;
+pub use crate::blocks::block_properties::BLOCK_SENSORY_FEATURES_LEN;
+use crate::blocks::block_properties::SENSORY_FEATURE_NAMES;
+
+pub const BLOCK_EXTENDED_SENSORY_FEATURES_LEN:usize = BLOCK_SENSORY_FEATURES_LEN+2;// + block_mass and is_air
+pub const ANN_TOUCHED_BLOCK_COUNT:usize = 8;//cube has 8 corners
+/**Sensory inputs of touched blocks come first. Each of the 8 corners of the bone contributes
+BLOCK_EXTENDED_SENSORY_FEATURES_LEN values (the last two are block mass and is_air)*/
+pub const ANN_BLOCK_TOUCH_SENSE_OFFSET:usize = 0;
+/**Lidars come right after the touch senses. Each lidar contributes ANN_LIDAR_SENSE_LEN values*/
+pub const ANN_LIDAR_SENSE_OFFSET:usize = ANN_BLOCK_TOUCH_SENSE_OFFSET+BLOCK_EXTENDED_SENSORY_FEATURES_LEN*ANN_TOUCHED_BLOCK_COUNT;
+/**Ratio of the lidar length that the ray traversed before hitting a block*/
+pub const ANN_LIDAR_LENGTH_SENSE:usize = 0;
+/**Sensory features of the block hit by the ray. All of them are 0 if the ray didn't hit anything*/
+pub const ANN_LIDAR_BLOCK_FEATURES_SENSE:usize = 1;
+/**1 if the ray hit a bone of another entity before hitting any block*/
+pub const ANN_LIDAR_ENTITY_SENSE:usize = ANN_LIDAR_BLOCK_FEATURES_SENSE+BLOCK_SENSORY_FEATURES_LEN;
+/**Energy of the detected entity relative to the energy of the observer (see relative_energy). 0 if no entity was detected*/
+pub const ANN_LIDAR_ENTITY_ENERGY_SENSE:usize = ANN_LIDAR_ENTITY_SENSE+1;
+pub const ANN_LIDAR_SENSE_LEN:usize = ANN_LIDAR_ENTITY_ENERGY_SENSE+1;
+/**Every signal channel contributes ANN_SIGNAL_SENSE_LEN values: strength of the signal followed by its direction*/
+pub const ANN_SIGNAL_SENSE_LEN:usize = 4;
+/**Sum of strengths of all particles of the channel that the entity can sense, clamped to 1*/
+pub const ANN_SIGNAL_STRENGTH_SENSE:usize = 0;
+/**Average direction towards the particles of the channel, in the frame of reference of the entity (see AnnInput::set_signal)*/
+pub const ANN_SIGNAL_DIRECTION_SENSE:usize = 1;
+pub const ROTATION_MUSCLE_STRENGTH:f32 = 0.01;
+/**Maximum change of yaw and pitch that can happen in a single step*/
+pub const ANN_MAX_ROTATION_CHANGE:f32 = 0.02;
+pub const ROTATION_BOUNDS:f32 = 2.*std::f32::consts::PI;
+/**Connections are stored on GPU as src_neuron followed by weight*/
+pub const ANN_SPARSE_CONNECTION_WORDS:usize = 2;
+/**Lidars are stored on GPU as vec3 direction followed by a dummy float*/
+pub const ANN_LIDAR_WORDS:usize = 4;
+/**Learning rates of connections coming into hidden, latent and output neurons. They are genes, so they get mutated together with weights*/
+pub const ANN_PLASTICITY_GENES:usize = 3;
+/**Plastic entities store learning rate genes and energy from the previous step, followed by one eligibility trace per connection*/
+pub const ANN_PLASTICITY_HEADER_WORDS:usize = ANN_PLASTICITY_GENES+1;
+/**Eligibility traces decay by this factor every step, before the Hebbian term (pre*post) is added*/
+pub const ANN_ELIGIBILITY_TRACE_DECAY:f32 = 0.9;
+/**Lifetime learning can't push weights out of range [-ANN_PLASTIC_WEIGHT_LIMIT,ANN_PLASTIC_WEIGHT_LIMIT]*/
+pub const ANN_PLASTIC_WEIGHT_LIMIT:f32 = 4.;
+/**Number of blocks that a single entity can carry. Broken blocks go into the inventory and placed blocks are taken from it*/
+pub const ANN_INVENTORY_SIZE:usize = 4;
+
+/**Number of dimensions in AnnConfig*/
+pub const ANN_CONFIG_LEN:usize = 15;
+/**Names of dimensions in the order of AnnConfig::as_array*/
+pub const ANN_CONFIG_NAMES:[&str;ANN_CONFIG_LEN] = ["lidar_count", "hidden_size", "input_connections_per_hidden_neuron", "latent_size",
+    "hidden_connections_per_latent_neuron", "latent_connections_per_latent_neuron", "latent_connections_per_output_neuron",
+    "output_attack_muscles_size", "output_movement_muscles_size", "output_rotation_muscles_size", "body_limbs", "output_limb_muscles_size", "plasticity",
+    "output_block_muscles_size", "signal_channels"];
+/**Dimensions of ANN brains. They are passed to shaders as specialization constants 600-612 and 614-616,
+so every run can pick a different brain size without recompiling shaders*/
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct AnnConfig{
+    pub lidar_count:usize,
+    pub hidden_size:usize,
+    pub input_connections_per_hidden_neuron:usize,
+    pub latent_size:usize,
+    pub hidden_connections_per_latent_neuron:usize,
+    pub latent_connections_per_latent_neuron:usize,
+    pub latent_connections_per_output_neuron:usize,
+    pub output_attack_muscles_size:usize,
+    /**The first two muscles push upwards, the next two push downwards and the remaining ones are evenly distributed on the xz circle*/
+    pub output_movement_muscles_size:usize,
+    /**The first half of muscles changes yaw, the second half changes pitch*/
+    pub output_rotation_muscles_size:usize,
+    /**Number of bones of the body, besides the root bone. Every limb adds a joint angle sense and a contact sense.
+    Entities with limbs move only by contracting their muscles. See Body*/
+    pub body_limbs:usize,
+    /**Every output drives target length of one Muscle of the body*/
+    pub output_limb_muscles_size:usize,
+    /**1 enables reward-modulated Hebbian learning during lifetime, 0 keeps weights fixed for life. See AnnEntity::step*/
+    pub plasticity:usize,
+    /**The first half of muscles breaks the block in front of the entity, the second half places a block from the inventory there*/
+    pub output_block_muscles_size:usize,
+    /**Every channel adds one output muscle that emits signal particles and ANN_SIGNAL_SENSE_LEN inputs that sense them*/
+    pub signal_channels:usize,
+}
+
+impl AnnConfig{
+    pub const fn small()->Self{
+        Self{
+            lidar_count: 16,
+            hidden_size: 16,
+            input_connections_per_hidden_neuron: 8,
+            latent_size: 16,
+            hidden_connections_per_latent_neuron: 4,
+            latent_connections_per_latent_neuron: 2,
+            latent_connections_per_output_neuron: 4,
+            output_attack_muscles_size: 4,
+            output_movement_muscles_size: 12,
+            output_rotation_muscles_size: 4,
+            body_limbs: 0,
+            output_limb_muscles_size: 0,
+            plasticity: 0,
+            output_block_muscles_size: 2,
+            signal_channels: 2,
+        }
+    }
+    /**Default size of brains. This is what the shaders are compiled with*/
+    pub const fn medium()->Self{
+        Self{
+            lidar_count: 32,
+            hidden_size: 32,
+            input_connections_per_hidden_neuron: 16,
+            latent_size: 32,
+            hidden_connections_per_latent_neuron: 4,
+            latent_connections_per_latent_neuron: 4,
+            latent_connections_per_output_neuron: 4,
+            output_attack_muscles_size: 4,
+            output_movement_muscles_size: 20,
+            output_rotation_muscles_size: 8,
+            body_limbs: 0,
+            output_limb_muscles_size: 0,
+            plasticity: 0,
+            output_block_muscles_size: 2,
+            signal_channels: 4,
+        }
+    }
+    pub const fn large()->Self{
+        Self{
+            lidar_count: 64,
+            hidden_size: 128,
+            input_connections_per_hidden_neuron: 32,
+            latent_size: 64,
+            hidden_connections_per_latent_neuron: 16,
+            latent_connections_per_latent_neuron: 8,
+            latent_connections_per_output_neuron: 8,
+            output_attack_muscles_size: 4,
+            output_movement_muscles_size: 20,
+            output_rotation_muscles_size: 8,
+            body_limbs: 0,
+            output_limb_muscles_size: 0,
+            plasticity: 0,
+            output_block_muscles_size: 4,
+            signal_channels: 8,
+        }
+    }
+    pub fn from_name(name:&str)->Option<Self>{
+        match name{
+            "small" => Some(Self::small()),
+            "medium" => Some(Self::medium()),
+            "large" => Some(Self::large()),
+            _ => None
+        }
+    }
+    /**Reads the brain size (small, medium or large) from EVOLUTIO_BRAIN environment variable. Defaults to medium.
+    Lifetime learning is enabled by EVOLUTIO_PLASTICITY=1*/
+    pub fn from_env()->Self{
+        let config = match std::env::var("EVOLUTIO_BRAIN"){
+            Ok(name) => Self::from_name(&name).unwrap_or_else(||{
+                println!("Unknown brain size {}. Using medium", name);
+                Self::medium()
+            }),
+            Err(_) => Self::medium()
+        };
+        config.with_plasticity(std::env::var("EVOLUTIO_PLASTICITY").map(|v|v=="1").unwrap_or(false))
+    }
+    pub fn with_plasticity(mut self, plasticity:bool)->Self{
+        self.plasticity = plasticity as usize;
+        self
+    }
+    pub fn is_plastic(&self)->bool{
+        self.plasticity != 0
+    }
+    /**Adjusts the dimensions of limb senses and limb muscles to the given body*/
+    pub fn with_body(mut self, body:&Body)->Self{
+        self.body_limbs = body.limbs();
+        self.output_limb_muscles_size = body.muscles().len();
+        self
+    }
+    pub fn lidar_sense_offset(&self, lidar:usize)->usize{
+        ANN_LIDAR_SENSE_OFFSET+lidar*ANN_LIDAR_SENSE_LEN
+    }
+    /**Joint angles of limbs come right after the lidars*/
+    pub fn joint_angle_sense_offset(&self)->usize{
+        self.lidar_sense_offset(self.lidar_count)
+    }
+    /**Limb contacts come right after the joint angles*/
+    pub fn limb_contact_sense_offset(&self)->usize{
+        self.joint_angle_sense_offset()+self.body_limbs
+    }
+    /**Signal senses come right after the limb contacts*/
+    pub fn signal_sense_offset(&self, channel:usize)->usize{
+        self.limb_contact_sense_offset()+self.body_limbs+channel*ANN_SIGNAL_SENSE_LEN
+    }
+    pub fn input_size(&self)->usize{
+        self.signal_sense_offset(self.signal_channels)
+    }
+    pub fn output_size(&self)->usize{
+        self.output_attack_muscles_size+self.output_movement_muscles_size+self.output_rotation_muscles_size+self.output_limb_muscles_size+self.output_block_muscles_size+self.signal_channels
+    }
+    pub fn output_attack_muscles_offset(&self)->usize{
+        0
+    }
+    pub fn output_movement_muscles_offset(&self)->usize{
+        self.output_attack_muscles_offset()+self.output_attack_muscles_size
+    }
+    pub fn output_rotation_muscles_offset(&self)->usize{
+        self.output_movement_muscles_offset()+self.output_movement_muscles_size
+    }
+    pub fn output_limb_muscles_offset(&self)->usize{
+        self.output_rotation_muscles_offset()+self.output_rotation_muscles_size
+    }
+    pub fn output_block_muscles_offset(&self)->usize{
+        self.output_limb_muscles_offset()+self.output_limb_muscles_size
+    }
+    /**Muscle of channel i emits signal particles of channel i*/
+    pub fn output_signal_muscles_offset(&self)->usize{
+        self.output_block_muscles_offset()+self.output_block_muscles_size
+    }
+    /**Size of the largest layer. Shaders use it for shared memory*/
+    pub fn max_size(&self)->usize{
+        self.input_size().max(self.hidden_size).max(self.latent_size).max(self.output_size())
+    }
+    pub fn hidden_neuron_words(&self)->usize{
+        self.input_connections_per_hidden_neuron*ANN_SPARSE_CONNECTION_WORDS+1
+    }
+    pub fn latent_neuron_words(&self)->usize{
+        (self.hidden_connections_per_latent_neuron+self.latent_connections_per_latent_neuron)*ANN_SPARSE_CONNECTION_WORDS+1
+    }
+    pub fn output_neuron_words(&self)->usize{
+        self.latent_connections_per_output_neuron*ANN_SPARSE_CONNECTION_WORDS+1
+    }
+    pub fn latent_neurons_offset(&self)->usize{
+        self.hidden_size*self.hidden_neuron_words()
+    }
+    pub fn output_neurons_offset(&self)->usize{
+        self.latent_neurons_offset()+self.latent_size*self.latent_neuron_words()
+    }
+    pub fn latent_offset(&self)->usize{
+        self.output_neurons_offset()+self.output_size()*self.output_neuron_words()
+    }
+    pub fn lidars_offset(&self)->usize{
+        self.latent_offset()+self.latent_size
+    }
+    pub fn bone_idx_offset(&self)->usize{
+        self.lidars_offset()+self.lidar_count*ANN_LIDAR_WORDS
+    }
+    /**Energy is stored as f32 bits. Mirrors ANN_ENTITY_ENERGY_OFFSET from constants.comp*/
+    pub fn energy_offset(&self)->usize{
+        self.bone_idx_offset()+2// bone_idx, main
+    }
+    /**Cooldown of block edits comes right after birth_tick and is followed by the inventory*/
+    pub fn inventory_offset(&self)->usize{
+        self.bone_idx_offset()+9// bone_idx, main, energy, speed, attack_cooldown, id, parent_id, birth_tick, block_cooldown
+    }
+    pub fn limbs_offset(&self)->usize{
+        self.inventory_offset()+ANN_INVENTORY_SIZE
+    }
+    /**Number of sparse connections of all neurons*/
+    pub fn connection_count(&self)->usize{
+        self.hidden_size*self.input_connections_per_hidden_neuron
+            + self.latent_size*(self.hidden_connections_per_latent_neuron+self.latent_connections_per_latent_neuron)
+            + self.output_size()*self.latent_connections_per_output_neuron
+    }
+    /**Learning rate genes, previous energy and eligibility traces come right after the limbs*/
+    pub fn plasticity_offset(&self)->usize{
+        self.limbs_offset()+self.body_limbs
+    }
+    /**0 if plasticity is disabled*/
+    pub fn plasticity_words(&self)->usize{
+        self.plasticity*(ANN_PLASTICITY_HEADER_WORDS+self.connection_count())
+    }
+    /**Number of 4-byte words that a single entity occupies on GPU. Mirrors ANN_ENTITY_WORDS from constants.comp*/
+    pub fn entity_words(&self)->usize{
+        self.plasticity_offset()+self.plasticity_words()
+    }
+    pub fn entity_bytes(&self)->usize{
+        self.entity_words()*std::mem::size_of::<u32>()
+    }
+    pub fn validate(&self)->Result<(),failure::Error>{
+        if self.output_movement_muscles_size < 4{
+            return Err(failure::err_msg(format!("There must be at least 4 movement muscles but got {}", self.output_movement_muscles_size)))
+        }
+        if self.output_rotation_muscles_size%2 != 0{
+            return Err(failure::err_msg(format!("Number of rotation muscles must be even but got {}", self.output_rotation_muscles_size)))
+        }
+        if self.hidden_size==0 || self.latent_size==0{
+            return Err(failure::err_msg("Hidden and latent layers must not be empty"))
+        }
+        if self.plasticity > 1{
+            return Err(failure::err_msg(format!("Plasticity must be either 0 or 1 but got {}", self.plasticity)))
+        }
+        if self.signal_channels == 0{
+            return Err(failure::err_msg("There must be at least one signal channel, because shaders size arrays by it"))
+        }
+        if self.output_block_muscles_size%2 != 0{
+            return Err(failure::err_msg(format!("Number of block muscles must be even but got {}", self.output_block_muscles_size)))
+        }
+        Ok(())
+    }
+    /**Fails if the limbs and muscles of the body don't match dimensions of the brain*/
+    pub fn validate_body(&self, body:&Body)->Result<(),failure::Error>{
+        if self.body_limbs != body.limbs() || self.output_limb_muscles_size != body.muscles().len(){
+            return Err(failure::err_msg(format!("Brain expects {} limbs and {} muscles but the body has {} limbs and {} muscles",
+                                                self.body_limbs, self.output_limb_muscles_size, body.limbs(), body.muscles().len())))
+        }
+        Ok(())
+    }
+    /**The order of values is the same as the order of specialization constants*/
+    pub fn as_array(&self)->[usize;ANN_CONFIG_LEN]{
+        [self.lidar_count,
+            self.hidden_size,
+            self.input_connections_per_hidden_neuron,
+            self.latent_size,
+            self.hidden_connections_per_latent_neuron,
+            self.latent_connections_per_latent_neuron,
+            self.latent_connections_per_output_neuron,
+            self.output_attack_muscles_size,
+            self.output_movement_muscles_size,
+            self.output_rotation_muscles_size,
+            self.body_limbs,
+            self.output_limb_muscles_size,
+            self.plasticity,
+            self.output_block_muscles_size,
+            self.signal_channels]
+    }
+    pub fn from_array(a:[usize;ANN_CONFIG_LEN])->Self{
+        Self{
+            lidar_count: a[0],
+            hidden_size: a[1],
+            input_connections_per_hidden_neuron: a[2],
+            latent_size: a[3],
+            hidden_connections_per_latent_neuron: a[4],
+            latent_connections_per_latent_neuron: a[5],
+            latent_connections_per_output_neuron: a[6],
+            output_attack_muscles_size: a[7],
+            output_movement_muscles_size: a[8],
+            output_rotation_muscles_size: a[9],
+            body_limbs: a[10],
+            output_limb_muscles_size: a[11],
+            plasticity: a[12],
+            output_block_muscles_size: a[13],
+            signal_channels: a[14],
+        }
+    }
+}
+
+impl Default for AnnConfig{
+    fn default() -> Self {
+        Self::medium()
+    }
+}
+
+/**Maps energy of a detected entity into range [0,1]. Entities with the same energy as the observer get 0.5.
+Mirrors relative_energy in constants.comp*/
+pub fn relative_energy(own_energy:f32, other_energy:f32)->f32{
+    let own = own_energy.max(0.);
+    let other = other_energy.max(0.);
+    if own + other > 0. { other / (own + other) } else { 0.5 }
+}
+
+/**What a single lidar ray has found*/
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct AnnLidarHit{
+    pub ratio_of_traversed_length:f32,
+    /**Block that stopped the ray or None if the ray reached its full length*/
+    pub block:Option<BlockId>,
+    /**Energy of the entity whose bone was hit before any block*/
+    pub entity_energy:Option<f32>,
+}
+
+/**Input vector of the brain. It mirrors the way update_ann_entities.comp fills the input layer, so that the CPU reference
+of the forward pass can be fed exactly the same values as the GPU. The layout is:
+touch senses (see ANN_BLOCK_TOUCH_SENSE_OFFSET), lidar senses (see ANN_LIDAR_SENSE_OFFSET),
+joint angles (see AnnConfig::joint_angle_sense_offset), limb contacts (see AnnConfig::limb_contact_sense_offset)
+and signals (see AnnConfig::signal_sense_offset)*/
+#[derive(Clone, PartialEq, Debug)]
+pub struct AnnInput{
+    config:AnnConfig,
+    values:Vec<f32>,
+}
+
+impl AnnInput{
+    pub fn new(config:AnnConfig)->Self{
+        Self{values:vec![0.;config.input_size()], config}
+    }
+    /**Block touched by one of the 8 corners of the bone*/
+    pub fn set_touched_block(&mut self, corner:usize, block:BlockId, block_mass:f32){
+        assert!(corner < ANN_TOUCHED_BLOCK_COUNT);
+        let offset = ANN_BLOCK_TOUCH_SENSE_OFFSET+corner*BLOCK_EXTENDED_SENSORY_FEATURES_LEN;
+        for (v,&f) in self.values[offset..].iter_mut().zip(block.sensory_features().iter()){
+            *v = f as u32 as f32;
+        }
+        self.values[offset+BLOCK_EXTENDED_SENSORY_FEATURES_LEN-2] = block_mass;
+        self.values[offset+BLOCK_EXTENDED_SENSORY_FEATURES_LEN-1] = block.is_air() as u32 as f32;
+    }
+    pub fn set_lidar(&mut self, lidar:usize, hit:&AnnLidarHit, own_energy:f32){
+        assert!(lidar < self.config.lidar_count);
+        let offset = self.config.lidar_sense_offset(lidar);
+        let lidar_senses = &mut self.values[offset..offset+ANN_LIDAR_SENSE_LEN];
+        lidar_senses[ANN_LIDAR_LENGTH_SENSE] = hit.ratio_of_traversed_length;
+        let features = hit.block.unwrap_or(BlockId::air()).sensory_features();
+        for (v,&f) in lidar_senses[ANN_LIDAR_BLOCK_FEATURES_SENSE..ANN_LIDAR_ENTITY_SENSE].iter_mut().zip(features.iter()){
+            *v = f as u32 as f32;
+        }
+        lidar_senses[ANN_LIDAR_ENTITY_SENSE] = hit.entity_energy.is_some() as u32 as f32;
+        lidar_senses[ANN_LIDAR_ENTITY_ENERGY_SENSE] = hit.entity_energy.map(|e|relative_energy(own_energy, e)).unwrap_or(0.);
+    }
+    /**See Body::joint_angles*/
+    pub fn set_joint_angle(&mut self, limb:usize, angle:f32){
+        assert!(limb < self.config.body_limbs);
+        self.values[self.config.joint_angle_sense_offset()+limb] = angle;
+    }
+    pub fn set_limb_contact(&mut self, limb:usize, is_touching_ground:bool){
+        assert!(limb < self.config.body_limbs);
+        self.values[self.config.limb_contact_sense_offset()+limb] = is_touching_ground as u32 as f32;
+    }
+    /**Strength and direction of the signal sensed on the given channel (see sense_signals in ecology.rs).
+    The direction is given in world coordinates and gets rotated into the frame of reference of the bone,
+    the same way as update_ann_entities.comp does it*/
+    pub fn set_signal(&mut self, channel:usize, strength:f32, direction:&glm::Vec3, yaw_and_pitch:glm::Vec2){
+        assert!(channel < self.config.signal_channels);
+        let offset = self.config.signal_sense_offset(channel);
+        let local = glm::transpose(&rotation_mat_from_yaw_and_pitch(yaw_and_pitch)) * direction;
+        self.values[offset+ANN_SIGNAL_STRENGTH_SENSE] = strength;
+        self.values[offset+ANN_SIGNAL_DIRECTION_SENSE..offset+ANN_SIGNAL_DIRECTION_SENSE+3].copy_from_slice(local.as_slice());
+    }
+    pub fn as_slice(&self)->&[f32]{
+        &self.values
+    }
+    /**Human readable name of the i-th input, for example "lidar[3].is_water". Useful for documenting and inspecting brains*/
+    pub fn label(config:&AnnConfig, i:usize)->String{
+        let touch_end = ANN_LIDAR_SENSE_OFFSET;
+        let lidar_end = config.joint_angle_sense_offset();
+        if i < touch_end{
+            let (corner, f) = ((i-ANN_BLOCK_TOUCH_SENSE_OFFSET)/BLOCK_EXTENDED_SENSORY_FEATURES_LEN, (i-ANN_BLOCK_TOUCH_SENSE_OFFSET)%BLOCK_EXTENDED_SENSORY_FEATURES_LEN);
+            let name = match f{
+                f if f < BLOCK_SENSORY_FEATURES_LEN => SENSORY_FEATURE_NAMES[f],
+                f if f == BLOCK_EXTENDED_SENSORY_FEATURES_LEN-2 => "block_mass",
+                _ => "is_air",
+            };
+            format!("touch[{}].{}", corner, name)
+        }else if i < lidar_end{
+            let (lidar, f) = ((i-ANN_LIDAR_SENSE_OFFSET)/ANN_LIDAR_SENSE_LEN, (i-ANN_LIDAR_SENSE_OFFSET)%ANN_LIDAR_SENSE_LEN);
+            let name = match f{
+                ANN_LIDAR_LENGTH_SENSE => "length",
+                ANN_LIDAR_ENTITY_SENSE => "entity",
+                ANN_LIDAR_ENTITY_ENERGY_SENSE => "entity_energy",
+                f => SENSORY_FEATURE_NAMES[f-ANN_LIDAR_BLOCK_FEATURES_SENSE],
+            };
+            format!("lidar[{}].{}", lidar, name)
+        }else if i < config.limb_contact_sense_offset(){
+            format!("joint_angle[{}]", i-lidar_end)
+        }else if i < config.signal_sense_offset(0){
+            format!("limb_contact[{}]", i-config.limb_contact_sense_offset())
+        }else{
+            assert!(i < config.input_size());
+            let (channel, f) = ((i-config.signal_sense_offset(0))/ANN_SIGNAL_SENSE_LEN, (i-config.signal_sense_offset(0))%ANN_SIGNAL_SENSE_LEN);
+            let name = match f{
+                ANN_SIGNAL_STRENGTH_SENSE => "strength",
+                f => ["direction_x", "direction_y", "direction_z"][f-ANN_SIGNAL_DIRECTION_SENSE],
+            };
+            format!("signal[{}].{}", channel, name)
+        }
+    }
+}
+
+/**Mirrors impulse_of_rotation_muscle from constants.comp*/
+pub fn ann_impulses_of_rotation_muscles(muscles:usize) -> Vec<glm::Vec2>{
+    let yaw = glm::vec2(ROTATION_MUSCLE_STRENGTH,0.);
+    let pitch = glm::vec2(0.,ROTATION_MUSCLE_STRENGTH);
+    (0..muscles).map(|i|if i < muscles/2 {yaw} else {pitch}).collect()
+}
+/**Mirrors impulse_of_movement_muscle from constants.comp. The first two muscles push upwards,
+the next two push downwards and the remaining ones are evenly distributed on the xz circle*/
+pub fn ann_impulses_of_movement_muscles(muscles:usize) -> Vec<glm::Vec3>{
+    (0..muscles).map(|i|if i < 2{
+        glm::vec3(0.,1.,0.)
+    }else if i < 4{
+        glm::vec3(0.,-1.,0.)
+    }else{
+        let angle = 2.*std::f32::consts::PI*(i-4) as f32/(muscles-4) as f32;
+        glm::vec3(angle.sin(),0.,angle.cos())
+    }).collect()
+}
+
+/**Adds the rotation change, clamped to ANN_MAX_ROTATION_CHANGE, to the given yaw and pitch and wraps the result
+the same way as update_ann_entities.comp and update_htm_entities.comp do*/
+pub fn rotate_yaw_and_pitch(yaw_and_pitch:glm::Vec2, rotation_change:glm::Vec2)->glm::Vec2{
+    let mut rotated = yaw_and_pitch;
+    for i in 0..2{
+        let r = rotated[i] + rotation_change[i].clamp(-ANN_MAX_ROTATION_CHANGE, ANN_MAX_ROTATION_CHANGE);
+        rotated[i] = if r.abs() > ROTATION_BOUNDS { r - r.signum()*ROTATION_BOUNDS } else { r };
+    }
+    rotated
+}
+
+/**Mirrors rotation_mat_from_yaw_and_pitch from constants.comp*/
+pub fn rotation_mat_from_yaw_and_pitch(yaw_and_pitch:glm::Vec2)->glm::Mat3{
+    let (s_yaw, s_pitch) = (yaw_and_pitch.x.sin(), yaw_and_pitch.y.sin());
+    let (c_yaw, c_pitch) = (yaw_and_pitch.x.cos(), yaw_and_pitch.y.cos());
+    // glm::mat3 takes rows, while GLSL mat3 constructor takes columns
+    glm::mat3(c_yaw*c_pitch, c_yaw*s_pitch, s_yaw,
+              -s_pitch, c_pitch, 0.,
+              -s_yaw*c_pitch, -s_yaw*s_pitch, c_yaw)
+}
+/**Mirrors direction_from_yaw_and_pitch from constants.comp. When yaw and pitch is 0 radians, then direction is (1,0,0)*/
+pub fn direction_from_yaw_and_pitch(yaw_and_pitch:glm::Vec2)->glm::Vec3{
+    let (yaw, pitch) = (yaw_and_pitch.x, yaw_and_pitch.y);
+    glm::vec3(yaw.cos()*pitch.cos(), pitch.sin(), yaw.sin()*pitch.cos())
+}
+
+/**Reads consecutive words of GPU representation of AnnEntity*/
+struct WordReader<'a>{
+    words:&'a [u32],
+    pos:usize,
+}
+impl <'a> WordReader<'a>{
+    fn uint(&mut self)->u32{
+        self.pos += 1;
+        self.words[self.pos-1]
+    }
+    fn float(&mut self)->f32{
+        f32::from_bits(self.uint())
+    }
+    fn connections(&mut self, len:usize)->Vec<AnnSparseConnection>{
+        (0..len).map(|_|{
+            let src_neuron = self.uint();
+            AnnSparseConnection::new(src_neuron, self.float())
+        }).collect()
+    }
+}
+fn write_connections(out:&mut Vec<u32>, connections:&[AnnSparseConnection]){
+    for c in connections{
+        out.push(c.src_neuron);
+        out.push(c.weight.to_bits());
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Debug)]
-#[repr(C, packed)]
 pub struct AnnSparseConnection{
     src_neuron:u32,
     weight:f32
 }
+impl AnnSparseConnection{
+    pub const fn new(src_neuron:u32, weight:f32)->Self{
+        Self{src_neuron,weight}
+    }
+    pub const fn zero()->Self{
+        Self::new(0,0.)
+    }
+    pub fn src_neuron(&self)->u32{
+        self.src_neuron
+    }
+    pub fn weight(&self)->f32{
+        self.weight
+    }
+    pub fn set_src_neuron(&mut self, src_neuron:u32){
+        self.src_neuron = src_neuron
+    }
+    pub fn set_weight(&mut self, weight:f32){
+        self.weight = weight
+    }
+    fn propagate(&self, src:&[f32])->f32{
+        src[self.src_neuron as usize] * self.weight
+    }
+}
 #[derive(Copy, Clone, PartialEq, Debug)]
 #[repr(C, packed)]
 pub struct AnnLidar{
     direction:glm::Vec3,
     dummy:f32,
 }
-#[derive(Copy, Clone, PartialEq, Debug)]
-#[repr(C, packed)]
+impl AnnLidar{
+    pub fn new(direction:glm::Vec3)->Self{
+        Self{direction,dummy:0.}
+    }
+    pub fn direction(&self)->glm::Vec3{
+        self.direction
+    }
+}
+#[derive(Clone, PartialEq, Debug)]
 pub struct AnnSparseHiddenNeuron{
-    incoming: [AnnSparseConnection;ANN_INPUT_CONNECTIONS_PER_HIDDEN_NEURON],
+    incoming: Vec<AnnSparseConnection>,
     bias:f32,
 }
-#[derive(Copy, Clone, PartialEq, Debug)]
-#[repr(C, packed)]
+impl AnnSparseHiddenNeuron{
+    pub fn new(incoming: Vec<AnnSparseConnection>, bias:f32)->Self{
+        Self{incoming,bias}
+    }
+    pub fn zero(config:&AnnConfig)->Self{
+        Self::new(vec![AnnSparseConnection::zero();config.input_connections_per_hidden_neuron],0.)
+    }
+    pub fn incoming(&self)->&[AnnSparseConnection]{
+        &self.incoming
+    }
+    pub fn incoming_mut(&mut self)->&mut [AnnSparseConnection]{
+        &mut self.incoming
+    }
+    pub fn bias(&self)->f32{
+        self.bias
+    }
+    pub fn set_bias(&mut self, bias:f32){
+        self.bias = bias
+    }
+    /**ReLU activation*/
+    fn activate(&self, input:&[f32])->f32{
+        let sum = self.incoming.iter().fold(self.bias,|sum,c|sum+c.propagate(input));
+        sum.max(0.)
+    }
+}
+#[derive(Clone, PartialEq, Debug)]
 pub struct AnnSparseLatentNeuron{
-    incoming_from_hidden:[AnnSparseConnection;ANN_HIDDEN_CONNECTIONS_PER_LATENT_NEURON] ,
-    recurrent_from_latent:[AnnSparseConnection;ANN_LATENT_CONNECTIONS_PER_LATENT_NEURON] ,
+    incoming_from_hidden:Vec<AnnSparseConnection>,
+    recurrent_from_latent:Vec<AnnSparseConnection>,
     bias:f32,
 }
-#[derive(Copy, Clone, PartialEq, Debug)]
-#[repr(C, packed)]
+impl AnnSparseLatentNeuron{
+    pub fn new(incoming_from_hidden:Vec<AnnSparseConnection>,
+               recurrent_from_latent:Vec<AnnSparseConnection>,
+               bias:f32)->Self{
+        Self{incoming_from_hidden,recurrent_from_latent,bias}
+    }
+    pub fn zero(config:&AnnConfig)->Self{
+        Self::new(vec![AnnSparseConnection::zero();config.hidden_connections_per_latent_neuron],
+                  vec![AnnSparseConnection::zero();config.latent_connections_per_latent_neuron],
+                  0.)
+    }
+    pub fn incoming_from_hidden(&self)->&[AnnSparseConnection]{
+        &self.incoming_from_hidden
+    }
+    pub fn incoming_from_hidden_mut(&mut self)->&mut [AnnSparseConnection]{
+        &mut self.incoming_from_hidden
+    }
+    pub fn recurrent_from_latent(&self)->&[AnnSparseConnection]{
+        &self.recurrent_from_latent
+    }
+    pub fn recurrent_from_latent_mut(&mut self)->&mut [AnnSparseConnection]{
+        &mut self.recurrent_from_latent
+    }
+    pub fn bias(&self)->f32{
+        self.bias
+    }
+    pub fn set_bias(&mut self, bias:f32){
+        self.bias = bias
+    }
+    /**Clamps the result to [0,1]. The recurrent connections read the latent state from previous step*/
+    fn activate(&self, hidden:&[f32], previous_latent:&[f32])->f32{
+        let sum = self.incoming_from_hidden.iter().fold(self.bias,|sum,c|sum+c.propagate(hidden));
+        let sum = self.recurrent_from_latent.iter().fold(sum,|sum,c|sum+c.propagate(previous_latent));
+        sum.clamp(0., 1.)
+    }
+}
+#[derive(Clone, PartialEq, Debug)]
 pub struct AnnSparseOutputNeuron{
-    incoming:[AnnSparseConnection;ANN_LATENT_CONNECTIONS_PER_OUTPUT_NEURON],
+    incoming:Vec<AnnSparseConnection>,
     bias:f32,
 }
-#[derive(Copy, Clone, PartialEq, Debug)]
-#[repr(C, packed)]
+impl AnnSparseOutputNeuron{
+    pub fn new(incoming:Vec<AnnSparseConnection>, bias:f32)->Self{
+        Self{incoming,bias}
+    }
+    pub fn zero(config:&AnnConfig)->Self{
+        Self::new(vec![AnnSparseConnection::zero();config.latent_connections_per_output_neuron],0.)
+    }
+    pub fn incoming(&self)->&[AnnSparseConnection]{
+        &self.incoming
+    }
+    pub fn incoming_mut(&mut self)->&mut [AnnSparseConnection]{
+        &mut self.incoming
+    }
+    pub fn bias(&self)->f32{
+        self.bias
+    }
+    pub fn set_bias(&mut self, bias:f32){
+        self.bias = bias
+    }
+    /**Clamps the result to [0,1]*/
+    fn activate(&self, latent:&[f32])->f32{
+        let sum = self.incoming.iter().fold(self.bias,|sum,c|sum+c.propagate(latent));
+        sum.clamp(0., 1.)
+    }
+}
+/**CPU representation of an agent brain. Its dimensions come from AnnConfig. On GPU it is stored as
+AnnConfig::entity_words consecutive words, see to_words and from_words*/
+#[derive(Clone, PartialEq, Debug)]
 pub struct AnnEntity{
-    ann_hidden:[AnnSparseHiddenNeuron;ANN_HIDDEN_SIZE] ,
-    ann_latent:[AnnSparseLatentNeuron;ANN_LATENT_SIZE] ,
-    ann_output:[AnnSparseOutputNeuron;ANN_OUTPUT_SIZE] ,
-    latent:[f32;ANN_LATENT_SIZE] ,
-    lidars:[AnnLidar;ANN_LIDAR_COUNT] ,
+    config:AnnConfig,
+    ann_hidden:Vec<AnnSparseHiddenNeuron>,
+    ann_latent:Vec<AnnSparseLatentNeuron>,
+    ann_output:Vec<AnnSparseOutputNeuron>,
+    latent:Vec<f32>,
+    lidars:Vec<AnnLidar>,
     bone_idx:u32,
     main:u32,
     energy:f32,
     speed:f32,
+    /**Number of ticks left until the entity can attack again*/
+    attack_cooldown:u32,
+    /**Stable identity of the entity. Unlike its index in the buffer, the id is never reused*/
+    id:u32,
+    /**Id of the entity that spawned this one or NO_ENTITY_ID*/
+    parent_id:u32,
+    birth_tick:u32,
+    /**Number of ticks left until the entity can break or place a block again*/
+    block_cooldown:u32,
+    /**Carried blocks. Free slots hold air*/
+    inventory:[BlockId;ANN_INVENTORY_SIZE],
+    /**Bone indices of limbs in the order of Body::parts (without the root)*/
+    limbs:Vec<u32>,
+    /**Learning rates of connections coming into hidden, latent and output neurons. Only used if config.is_plastic()*/
+    learning_rates:[f32;ANN_PLASTICITY_GENES],
+    /**Energy seen by the previous step. Reward is the difference between current and previous energy*/
+    previous_energy:f32,
+    /**Eligibility trace of every connection, in the order of to_words. Empty if plasticity is disabled*/
+    traces:Vec<f32>,
+}
+
+/**Result of a single forward pass of AnnEntity. Equivalent of what update_ann_entities.comp
+computes before writing into the bone*/
+#[derive(Clone, PartialEq, Debug)]
+pub struct AnnEntityStep{
+    pub config:AnnConfig,
+    pub outputs:Vec<f32>,
+    /**Sum of rotation muscle impulses. It is not yet clamped*/
+    pub rotation_change:glm::Vec2,
+    /**Sum of movement muscle impulses. It is not yet multiplied by speed*/
+    pub directional_impulse:glm::Vec3,
+}
+
+fn average(muscles:&[f32])->f32{
+    if muscles.is_empty(){
+        0.
+    }else{
+        muscles.iter().sum::<f32>()/muscles.len() as f32
+    }
+}
+
+impl AnnEntityStep{
+    pub fn attack_muscles(&self)->&[f32]{
+        let offset = self.config.output_attack_muscles_offset();
+        &self.outputs[offset..offset+self.config.output_attack_muscles_size]
+    }
+    /**Average activation of attack muscles. Mirrors attack_strength in update_ann_entities.comp*/
+    pub fn attack_strength(&self)->f32{
+        average(self.attack_muscles())
+    }
+    pub fn movement_muscles(&self)->&[f32]{
+        let offset = self.config.output_movement_muscles_offset();
+        &self.outputs[offset..offset+self.config.output_movement_muscles_size]
+    }
+    pub fn limb_muscles(&self)->&[f32]{
+        let offset = self.config.output_limb_muscles_offset();
+        &self.outputs[offset..offset+self.config.output_limb_muscles_size]
+    }
+    pub fn rotation_muscles(&self)->&[f32]{
+        let offset = self.config.output_rotation_muscles_offset();
+        &self.outputs[offset..offset+self.config.output_rotation_muscles_size]
+    }
+    pub fn block_muscles(&self)->&[f32]{
+        let offset = self.config.output_block_muscles_offset();
+        &self.outputs[offset..offset+self.config.output_block_muscles_size]
+    }
+    pub fn signal_muscles(&self)->&[f32]{
+        let offset = self.config.output_signal_muscles_offset();
+        &self.outputs[offset..offset+self.config.signal_channels]
+    }
+    /**Average activation of the first half of block muscles. Mirrors break_strength in update_ann_entities.comp*/
+    pub fn break_strength(&self)->f32{
+        let muscles = self.block_muscles();
+        average(&muscles[..muscles.len()/2])
+    }
+    /**Average activation of the second half of block muscles. Mirrors place_strength in update_ann_entities.comp*/
+    pub fn place_strength(&self)->f32{
+        let muscles = self.block_muscles();
+        average(&muscles[muscles.len()/2..])
+    }
+    /**Adds the clamped rotation change to the given yaw and pitch and wraps the result
+    the same way as update_ann_entities.comp does*/
+    pub fn rotate(&self, yaw_and_pitch:glm::Vec2)->glm::Vec2{
+        rotate_yaw_and_pitch(yaw_and_pitch, self.rotation_change)
+    }
+}
+
+impl AnnEntity{
+    pub fn new(config:AnnConfig, bone_idx:u32, speed:f32, energy:f32)->Self{
+        Self{
+            ann_hidden: vec![AnnSparseHiddenNeuron::zero(&config);config.hidden_size],
+            ann_latent: vec![AnnSparseLatentNeuron::zero(&config);config.latent_size],
+            ann_output: vec![AnnSparseOutputNeuron::zero(&config);config.output_size()],
+            latent: vec![0.;config.latent_size],
+            lidars: vec![AnnLidar::new(glm::vec3(1.,0.,0.));config.lidar_count],
+            bone_idx,
+            main: 0,
+            energy,
+            speed,
+            attack_cooldown: 0,
+            id: NO_ENTITY_ID,
+            parent_id: NO_ENTITY_ID,
+            birth_tick: 0,
+            block_cooldown: 0,
+            inventory: [BlockId::air();ANN_INVENTORY_SIZE],
+            limbs: vec![u32::MAX;config.body_limbs],
+            learning_rates: [0.;ANN_PLASTICITY_GENES],
+            previous_energy: energy,
+            traces: vec![0.;config.plasticity*config.connection_count()],
+            config,
+        }
+    }
+    pub fn config(&self)->&AnnConfig{
+        &self.config
+    }
+    pub fn hidden(&self)->&[AnnSparseHiddenNeuron]{
+        &self.ann_hidden
+    }
+    pub fn hidden_mut(&mut self)->&mut [AnnSparseHiddenNeuron]{
+        &mut self.ann_hidden
+    }
+    pub fn latent_neurons(&self)->&[AnnSparseLatentNeuron]{
+        &self.ann_latent
+    }
+    pub fn latent_neurons_mut(&mut self)->&mut [AnnSparseLatentNeuron]{
+        &mut self.ann_latent
+    }
+    pub fn output_neurons(&self)->&[AnnSparseOutputNeuron]{
+        &self.ann_output
+    }
+    pub fn output_neurons_mut(&mut self)->&mut [AnnSparseOutputNeuron]{
+        &mut self.ann_output
+    }
+    /**Recurrent state of the latent layer*/
+    pub fn latent(&self)->&[f32]{
+        &self.latent
+    }
+    pub fn latent_mut(&mut self)->&mut [f32]{
+        &mut self.latent
+    }
+    pub fn lidars(&self)->&[AnnLidar]{
+        &self.lidars
+    }
+    pub fn lidars_mut(&mut self)->&mut [AnnLidar]{
+        &mut self.lidars
+    }
+    pub fn bone_idx(&self)->u32{
+        self.bone_idx
+    }
+    pub fn set_bone_idx(&mut self, bone_idx:u32){
+        self.bone_idx = bone_idx
+    }
+    pub fn energy(&self)->f32{
+        self.energy
+    }
+    pub fn set_energy(&mut self, energy:f32){
+        self.energy = energy
+    }
+    pub fn speed(&self)->f32{
+        self.speed
+    }
+    pub fn set_speed(&mut self, speed:f32){
+        self.speed = speed
+    }
+    pub fn attack_cooldown(&self)->u32{
+        self.attack_cooldown
+    }
+    pub fn set_attack_cooldown(&mut self, attack_cooldown:u32){
+        self.attack_cooldown = attack_cooldown
+    }
+    pub fn id(&self)->u32{
+        self.id
+    }
+    pub fn parent_id(&self)->u32{
+        self.parent_id
+    }
+    pub fn birth_tick(&self)->u32{
+        self.birth_tick
+    }
+    /**Gives the entity a new identity. Mirrors ann_set_lineage in utils.comp*/
+    pub fn set_lineage(&mut self, id:u32, parent_id:u32, birth_tick:u32){
+        self.id = id;
+        self.parent_id = parent_id;
+        self.birth_tick = birth_tick;
+    }
+    pub fn block_cooldown(&self)->u32{
+        self.block_cooldown
+    }
+    pub fn set_block_cooldown(&mut self, block_cooldown:u32){
+        self.block_cooldown = block_cooldown
+    }
+    pub fn inventory(&self)->&[BlockId;ANN_INVENTORY_SIZE]{
+        &self.inventory
+    }
+    /**Number of occupied inventory slots. Blocks are always kept at the beginning of the inventory*/
+    pub fn inventory_len(&self)->usize{
+        self.inventory.iter().take_while(|b|!b.is_air()).count()
+    }
+    /**Returns false if the inventory is full. Mirrors ann_push_inventory in utils.comp*/
+    pub fn push_inventory(&mut self, block:BlockId)->bool{
+        let len = self.inventory_len();
+        if len < ANN_INVENTORY_SIZE{
+            self.inventory[len] = block;
+            true
+        }else{
+            false
+        }
+    }
+    /**Takes the most recently collected block. Mirrors ann_pop_inventory in utils.comp*/
+    pub fn pop_inventory(&mut self)->Option<BlockId>{
+        let len = self.inventory_len();
+        if len > 0{
+            Some(std::mem::replace(&mut self.inventory[len-1], BlockId::air()))
+        }else{
+            None
+        }
+    }
+    /**Empties the inventory and resets the cooldown of block edits. Newborn entities don't inherit blocks of their parents*/
+    pub fn clear_inventory(&mut self){
+        self.inventory = [BlockId::air();ANN_INVENTORY_SIZE];
+        self.block_cooldown = 0;
+    }
+    pub fn limbs(&self)->&[u32]{
+        &self.limbs
+    }
+    pub fn limbs_mut(&mut self)->&mut [u32]{
+        &mut self.limbs
+    }
+    pub fn learning_rates(&self)->&[f32;ANN_PLASTICITY_GENES]{
+        &self.learning_rates
+    }
+    pub fn learning_rates_mut(&mut self)->&mut [f32;ANN_PLASTICITY_GENES]{
+        &mut self.learning_rates
+    }
+    pub fn previous_energy(&self)->f32{
+        self.previous_energy
+    }
+    pub fn set_previous_energy(&mut self, previous_energy:f32){
+        self.previous_energy = previous_energy
+    }
+    pub fn traces(&self)->&[f32]{
+        &self.traces
+    }
+    /**Forgets eligibility traces and treats the current energy as the baseline of the next reward.
+    Called whenever an entity is born. Mirrors ann_reset_plasticity in utils.comp*/
+    pub fn reset_plasticity(&mut self){
+        self.previous_energy = self.energy;
+        for t in &mut self.traces{
+            *t = 0.;
+        }
+    }
+    /**Index of a bone of the body. Part 0 is the root bone, the remaining parts are limbs*/
+    pub fn body_bone_idx(&self, part:usize)->u32{
+        if part==0{self.bone_idx}else{self.limbs[part-1]}
+    }
+    /**Appends GPU representation of this entity. Mirrors the layout described by ANN_ENTITY_* offsets in constants.comp*/
+    pub fn to_words(&self, out:&mut Vec<u32>){
+        for n in &self.ann_hidden{
+            write_connections(out, &n.incoming);
+            out.push(n.bias.to_bits());
+        }
+        for n in &self.ann_latent{
+            write_connections(out, &n.incoming_from_hidden);
+            write_connections(out, &n.recurrent_from_latent);
+            out.push(n.bias.to_bits());
+        }
+        for n in &self.ann_output{
+            write_connections(out, &n.incoming);
+            out.push(n.bias.to_bits());
+        }
+        out.extend(self.latent.iter().map(|l|l.to_bits()));
+        for l in &self.lidars{
+            let d = l.direction();
+            out.extend_from_slice(&[d.x.to_bits(),d.y.to_bits(),d.z.to_bits(),0]);
+        }
+        out.extend_from_slice(&[self.bone_idx, self.main, self.energy.to_bits(), self.speed.to_bits(), self.attack_cooldown,
+            self.id, self.parent_id, self.birth_tick, self.block_cooldown]);
+        out.extend(self.inventory.iter().map(|b|b.id()));
+        out.extend_from_slice(&self.limbs);
+        if self.config.is_plastic(){
+            out.extend(self.learning_rates.iter().map(|l|l.to_bits()));
+            out.push(self.previous_energy.to_bits());
+            out.extend(self.traces.iter().map(|t|t.to_bits()));
+        }
+    }
+    /**Inverse of to_words. The slice must hold at least config.entity_words() words*/
+    pub fn from_words(config:AnnConfig, words:&[u32])->Self{
+        assert!(words.len()>=config.entity_words(), "{} >= {}", words.len(), config.entity_words());
+        let mut r = WordReader{words,pos:0};
+        let ann_hidden = (0..config.hidden_size).map(|_|{
+            let incoming = r.connections(config.input_connections_per_hidden_neuron);
+            AnnSparseHiddenNeuron::new(incoming, r.float())
+        }).collect();
+        let ann_latent = (0..config.latent_size).map(|_|{
+            let incoming_from_hidden = r.connections(config.hidden_connections_per_latent_neuron);
+            let recurrent_from_latent = r.connections(config.latent_connections_per_latent_neuron);
+            AnnSparseLatentNeuron::new(incoming_from_hidden, recurrent_from_latent, r.float())
+        }).collect();
+        let ann_output = (0..config.output_size()).map(|_|{
+            let incoming = r.connections(config.latent_connections_per_output_neuron);
+            AnnSparseOutputNeuron::new(incoming, r.float())
+        }).collect();
+        let latent = (0..config.latent_size).map(|_|r.float()).collect();
+        let lidars = (0..config.lidar_count).map(|_|{
+            let direction = glm::vec3(r.float(),r.float(),r.float());
+            r.uint();
+            AnnLidar::new(direction)
+        }).collect();
+        let bone_idx = r.uint();
+        let main = r.uint();
+        let energy = r.float();
+        let speed = r.float();
+        let attack_cooldown = r.uint();
+        let id = r.uint();
+        let parent_id = r.uint();
+        let birth_tick = r.uint();
+        let block_cooldown = r.uint();
+        let mut inventory = [BlockId::air();ANN_INVENTORY_SIZE];
+        for b in &mut inventory{
+            *b = BlockId::new(r.uint());
+        }
+        let limbs = (0..config.body_limbs).map(|_|r.uint()).collect();
+        let mut learning_rates = [0.;ANN_PLASTICITY_GENES];
+        let mut previous_energy = energy;
+        let mut traces = Vec::new();
+        if config.is_plastic(){
+            for l in &mut learning_rates{
+                *l = r.float();
+            }
+            previous_energy = r.float();
+            traces = (0..config.connection_count()).map(|_|r.float()).collect();
+        }
+        Self{
+            config,
+            ann_hidden,
+            ann_latent,
+            ann_output,
+            latent,
+            lidars,
+            bone_idx,
+            main,
+            energy,
+            speed,
+            attack_cooldown,
+            id,
+            parent_id,
+            birth_tick,
+            block_cooldown,
+            inventory,
+            limbs,
+            learning_rates,
+            previous_energy,
+            traces,
+        }
+    }
+    /**Reward-modulated Hebbian update. Reward is the change of energy since the previous step and every weight moves by
+    learning_rate*reward*trace, where the learning rate is the gene of the layer that the connection leads to*/
+    fn modulate_weights(&mut self){
+        let reward = self.energy - self.previous_energy;
+        self.previous_energy = self.energy;
+        let [hidden_rate, latent_rate, output_rate] = self.learning_rates;
+        let mut traces = self.traces.iter();
+        let mut modulate = |connections:&mut [AnnSparseConnection], learning_rate:f32|for c in connections{
+            let w = c.weight + learning_rate*reward*traces.next().unwrap();
+            c.weight = w.clamp(-ANN_PLASTIC_WEIGHT_LIMIT, ANN_PLASTIC_WEIGHT_LIMIT);
+        };
+        for n in &mut self.ann_hidden{
+            modulate(&mut n.incoming, hidden_rate);
+        }
+        for n in &mut self.ann_latent{
+            modulate(&mut n.incoming_from_hidden, latent_rate);
+            modulate(&mut n.recurrent_from_latent, latent_rate);
+        }
+        for n in &mut self.ann_output{
+            modulate(&mut n.incoming, output_rate);
+        }
+    }
+    /**Decays eligibility traces and adds activation of the source neuron times activation of the target neuron*/
+    fn update_traces(&mut self, input:&[f32], hidden:&[f32], previous_latent:&[f32], outputs:&[f32]){
+        let mut traces = self.traces.iter_mut();
+        let mut hebbian = |connections:&[AnnSparseConnection], pre:&[f32], post:f32|for c in connections{
+            let t = traces.next().unwrap();
+            *t = ANN_ELIGIBILITY_TRACE_DECAY * *t + pre[c.src_neuron as usize]*post;
+        };
+        for (n,&post) in self.ann_hidden.iter().zip(hidden.iter()){
+            hebbian(&n.incoming, input, post);
+        }
+        for (n,&post) in self.ann_latent.iter().zip(self.latent.iter()){
+            hebbian(&n.incoming_from_hidden, hidden, post);
+            hebbian(&n.recurrent_from_latent, previous_latent, post);
+        }
+        for (n,&post) in self.ann_output.iter().zip(outputs.iter()){
+            hebbian(&n.incoming, &self.latent, post);
+        }
+    }
+    /**CPU reference implementation of the forward pass in update_ann_entities.comp. The input
+    is laid out as described by AnnInput and must hold config.input_size() values. The latent state of the entity gets updated.
+    If the brain is plastic, weights are first modulated by the reward collected since the previous step (see modulate_weights)
+    and eligibility traces are updated after the forward pass*/
+    pub fn step(&mut self, input:&[f32])->AnnEntityStep{
+        assert_eq!(input.len(), self.config.input_size());
+        if self.config.is_plastic(){
+            self.modulate_weights();
+        }
+        let hidden:Vec<f32> = self.ann_hidden.iter().map(|neuron|neuron.activate(input)).collect();
+        let latent:Vec<f32> = self.ann_latent.iter().map(|neuron|neuron.activate(&hidden,&self.latent)).collect();
+        let previous_latent = std::mem::replace(&mut self.latent, latent);
+        let outputs:Vec<f32> = self.ann_output.iter().map(|neuron|neuron.activate(&self.latent)).collect();
+        if self.config.is_plastic(){
+            self.update_traces(input, &hidden, &previous_latent, &outputs);
+        }
+        let rotation_change = ann_impulses_of_rotation_muscles(self.config.output_rotation_muscles_size).iter()
+            .zip(outputs[self.config.output_rotation_muscles_offset()..].iter())
+            .fold(glm::vec2(0.,0.),|sum,(impulse,&o)|sum+*impulse*o);
+        let directional_impulse = ann_impulses_of_movement_muscles(self.config.output_movement_muscles_size).iter()
+            .zip(outputs[self.config.output_movement_muscles_offset()..].iter())
+            .fold(glm::vec3(0.,0.,0.),|sum,(impulse,&o)|sum+*impulse*o);
+        AnnEntityStep{
+            config: self.config,
+            outputs,
+            rotation_change,
+            directional_impulse
+        }
+    }
+    /**Writes results of step into the bone, exactly as update_ann_entities.comp does. Movement impulse
+    is only applied if the entity is standing on the ground and has no limbs. Limbs are driven by Body::apply_joints*/
+    pub fn apply_step(&self, step:&AnnEntityStep, bone:&mut Bone, is_standing_on_the_ground:bool){
+        bone.set_yaw_and_pitch(step.rotate(bone.yaw_and_pitch()));
+        if is_standing_on_the_ground && self.config.body_limbs == 0{
+            bone.add_impulse(step.directional_impulse * self.speed());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::blocks::block_properties::{WATER, SENSORY_FEATURE_IS_WATER};
+
+    /**Entity whose only non-zero path goes from input 0 through hidden 0 and latent 0 to output 0*/
+    fn single_path_entity()->AnnEntity{
+        let mut e = AnnEntity::new(AnnConfig::small(), 0, 1., 1.);
+        e.hidden_mut()[0] = AnnSparseHiddenNeuron::new(vec![AnnSparseConnection::new(0, 2.)], 0.);
+        e.latent_neurons_mut()[0] = AnnSparseLatentNeuron::new(vec![AnnSparseConnection::new(0, 0.5)], vec![AnnSparseConnection::new(0, 0.25)], 0.);
+        e.output_neurons_mut()[0] = AnnSparseOutputNeuron::new(vec![AnnSparseConnection::new(0, 1.)], 0.);
+        e
+    }
+
+    #[test]
+    fn step_propagates_through_hidden_latent_and_output(){
+        let mut e = single_path_entity();
+        let mut input = vec![0.;e.config().input_size()];
+        input[0] = 0.25;
+        let step = e.step(&input);
+        assert_eq!(step.outputs.len(), e.config().output_size());
+        assert_eq!(step.outputs[0], 0.25);
+        assert!(step.outputs[1..].iter().all(|&o|o==0.));
+        assert_eq!(e.latent()[0], 0.25);
+    }
+
+    #[test]
+    fn latent_state_is_recurrent(){
+        let mut e = single_path_entity();
+        let mut input = vec![0.;e.config().input_size()];
+        input[0] = 0.25;
+        e.step(&input);
+        // 0.5*0.5 from hidden plus 0.25*0.25 from the previous latent state
+        let step = e.step(&input);
+        assert_eq!(e.latent()[0], 0.3125);
+        assert_eq!(step.outputs[0], 0.3125);
+        // with no input the latent state still feeds itself
+        let step = e.step(&vec![0.;e.config().input_size()]);
+        assert_eq!(e.latent()[0], 0.078125);
+        assert_eq!(step.outputs[0], 0.078125);
+    }
+
+    #[test]
+    fn activations_are_clamped(){
+        let mut e = single_path_entity();
+        let mut input = vec![0.;e.config().input_size()];
+        input[0] = -1.;
+        e.output_neurons_mut()[1] = AnnSparseOutputNeuron::new(vec![], 3.);
+        let step = e.step(&input);
+        // ReLU of the hidden neuron zeroes the negative input
+        assert_eq!(e.latent()[0], 0.);
+        assert_eq!(step.outputs[0], 0.);
+        assert_eq!(step.outputs[1], 1.);
+        input[0] = 10.;
+        let step = e.step(&input);
+        assert_eq!(e.latent()[0], 1.);
+        assert_eq!(step.outputs[0], 1.);
+    }
+
+    #[test]
+    fn words_round_trip(){
+        let config = AnnConfig{body_limbs:2, output_limb_muscles_size:2, plasticity:1, ..AnnConfig::small()};
+        let mut e = AnnEntity::new(config, 7, 0.5, 3.);
+        e.hidden_mut()[1] = AnnSparseHiddenNeuron::new(vec![AnnSparseConnection::new(5, -0.75);config.input_connections_per_hidden_neuron], 0.125);
+        e.latent_neurons_mut()[2].recurrent_from_latent_mut()[1] = AnnSparseConnection::new(3, 1.5);
+        e.output_neurons_mut()[4].set_bias(-2.);
+        e.latent_mut()[6] = 0.375;
+        e.lidars_mut()[3] = AnnLidar::new(glm::vec3(0., 1., 0.));
+        e.set_attack_cooldown(4);
+        e.set_lineage(11, 10, 1234);
+        e.set_block_cooldown(2);
+        e.push_inventory(BlockId::new(3));
+        e.limbs_mut().copy_from_slice(&[8, 9]);
+        e.learning_rates_mut()[1] = 0.01;
+        let mut words = Vec::new();
+        e.to_words(&mut words);
+        assert_eq!(words.len(), config.entity_words());
+        assert_eq!(f32::from_bits(words[config.energy_offset()]), e.energy());
+        assert_eq!(AnnEntity::from_words(config, &words), e);
+    }
+
+    /**Index of the input with the given label*/
+    fn input_idx(config:&AnnConfig, label:&str)->usize{
+        (0..config.input_size()).find(|&i|AnnInput::label(config, i)==label).unwrap_or_else(||panic!("no input {}", label))
+    }
+
+    #[test]
+    fn input_labels_follow_the_layout(){
+        let config = AnnConfig{body_limbs:2, output_limb_muscles_size:2, ..AnnConfig::small()};
+        assert_eq!(ANN_LIDAR_SENSE_OFFSET, ANN_TOUCHED_BLOCK_COUNT*BLOCK_EXTENDED_SENSORY_FEATURES_LEN);
+        assert_eq!(config.input_size(), ANN_LIDAR_SENSE_OFFSET+config.lidar_count*ANN_LIDAR_SENSE_LEN+2*config.body_limbs+config.signal_channels*ANN_SIGNAL_SENSE_LEN);
+        let expected = [
+            (0, "touch[0].is_water"),
+            (BLOCK_EXTENDED_SENSORY_FEATURES_LEN-2, "touch[0].block_mass"),
+            (BLOCK_EXTENDED_SENSORY_FEATURES_LEN-1, "touch[0].is_air"),
+            (BLOCK_EXTENDED_SENSORY_FEATURES_LEN, "touch[1].is_water"),
+            (ANN_LIDAR_SENSE_OFFSET, "lidar[0].length"),
+            (ANN_LIDAR_SENSE_OFFSET+ANN_LIDAR_BLOCK_FEATURES_SENSE+3, "lidar[0].is_edible"),
+            (config.lidar_sense_offset(2)+ANN_LIDAR_ENTITY_SENSE, "lidar[2].entity"),
+            (config.lidar_sense_offset(2)+ANN_LIDAR_ENTITY_ENERGY_SENSE, "lidar[2].entity_energy"),
+            (config.joint_angle_sense_offset()+1, "joint_angle[1]"),
+            (config.limb_contact_sense_offset(), "limb_contact[0]"),
+            (config.signal_sense_offset(1), "signal[1].strength"),
+            (config.signal_sense_offset(1)+3, "signal[1].direction_z"),
+            (config.input_size()-1, "signal[1].direction_z"),
+        ];
+        for &(i, label) in expected.iter(){
+            assert_eq!(AnnInput::label(&config, i), label);
+        }
+        let labels:std::collections::HashSet<String> = (0..config.input_size()).map(|i|AnnInput::label(&config, i)).collect();
+        assert_eq!(labels.len(), config.input_size());
+    }
+
+    #[test]
+    fn setters_write_the_labelled_inputs(){
+        let config = AnnConfig{body_limbs:2, output_limb_muscles_size:2, ..AnnConfig::small()};
+        let mut input = AnnInput::new(config);
+        input.set_touched_block(1, WATER, 0.5);
+        input.set_joint_angle(1, 0.3);
+        input.set_limb_contact(0, true);
+        input.set_signal(1, 0.7, &glm::vec3(0., 0., -1.), glm::vec2(0., 0.));
+        let v = input.as_slice();
+        let expected = [("touch[1].is_water", 1.), ("touch[1].block_mass", 0.5), ("joint_angle[1]", 0.3),
+            ("limb_contact[0]", 1.), ("signal[1].strength", 0.7), ("signal[1].direction_z", -1.)];
+        for &(label, value) in expected.iter(){
+            assert_eq!(v[input_idx(&config, label)], value, "{}", label);
+        }
+        assert_eq!(v.iter().filter(|&&x|x!=0.).count(), expected.len());
+    }
+
+    #[test]
+    fn lidar_reports_entities_blocks_and_nothing(){
+        let config = AnnConfig::small();
+        let mut input = AnnInput::new(config);
+        let senses = |input:&AnnInput|input.as_slice()[config.lidar_sense_offset(3)..config.lidar_sense_offset(4)].to_vec();
+        let mut expected = vec![0.;ANN_LIDAR_SENSE_LEN];
+        // an entity with three times the energy of the observer
+        input.set_lidar(3, &AnnLidarHit{ratio_of_traversed_length:0.25, block:None, entity_energy:Some(3.)}, 1.);
+        expected[ANN_LIDAR_LENGTH_SENSE] = 0.25;
+        expected[ANN_LIDAR_ENTITY_SENSE] = 1.;
+        expected[ANN_LIDAR_ENTITY_ENERGY_SENSE] = 0.75;
+        assert_eq!(senses(&input), expected);
+        // a block replaces everything the previous hit has written
+        input.set_lidar(3, &AnnLidarHit{ratio_of_traversed_length:0.5, block:Some(WATER), entity_energy:None}, 1.);
+        let mut expected = vec![0.;ANN_LIDAR_SENSE_LEN];
+        expected[ANN_LIDAR_LENGTH_SENSE] = 0.5;
+        expected[ANN_LIDAR_BLOCK_FEATURES_SENSE+SENSORY_FEATURE_IS_WATER] = 1.;
+        assert_eq!(senses(&input), expected);
+        // the ray reached its full length
+        input.set_lidar(3, &AnnLidarHit{ratio_of_traversed_length:1., block:None, entity_energy:None}, 1.);
+        let mut expected = vec![0.;ANN_LIDAR_SENSE_LEN];
+        expected[ANN_LIDAR_LENGTH_SENSE] = 1.;
+        assert_eq!(senses(&input), expected);
+        // no other lidar was touched
+        assert_eq!(input.as_slice().iter().filter(|&&x|x!=0.).count(), 1);
+    }
+
+    /**Plastic entity with the same single path as single_path_entity, but with all connections that the config asks for*/
+    fn plastic_entity(learning_rates:[f32;ANN_PLASTICITY_GENES])->AnnEntity{
+        let config = AnnConfig::small().with_plasticity(true);
+        let mut e = AnnEntity::new(config, 0, 1., 1.);
+        e.hidden_mut()[0].incoming_mut()[0] = AnnSparseConnection::new(0, 2.);
+        e.latent_neurons_mut()[0].incoming_from_hidden_mut()[0] = AnnSparseConnection::new(0, 0.5);
+        e.output_neurons_mut()[0].incoming_mut()[0] = AnnSparseConnection::new(0, 1.);
+        *e.learning_rates_mut() = learning_rates;
+        e.reset_plasticity();
+        e
+    }
+
+    /**Indices of traces of the three connections on the single path*/
+    fn path_traces(config:&AnnConfig)->[usize;3]{
+        let latent = config.hidden_size*config.input_connections_per_hidden_neuron;
+        let output = latent+config.latent_size*(config.hidden_connections_per_latent_neuron+config.latent_connections_per_latent_neuron);
+        [0, latent, output]
+    }
+
+    fn path_weights(e:&AnnEntity)->[f32;3]{
+        [e.hidden()[0].incoming()[0].weight(), e.latent_neurons()[0].incoming_from_hidden()[0].weight(), e.output_neurons()[0].incoming()[0].weight()]
+    }
+
+    fn input(e:&AnnEntity, value:f32)->Vec<f32>{
+        let mut input = vec![0.;e.config().input_size()];
+        input[0] = value;
+        input
+    }
+
+    #[test]
+    fn eligibility_traces_decay(){
+        let mut e = plastic_entity([0.;ANN_PLASTICITY_GENES]);
+        let [h, l, o] = path_traces(e.config());
+        assert_eq!(e.traces().len(), e.config().connection_count());
+        e.step(&input(&e, 0.25));
+        // pre*post of every connection on the path: 0.25*0.5, 0.5*0.25 and 0.25*0.25
+        assert_eq!([e.traces()[h], e.traces()[l], e.traces()[o]], [0.125, 0.125, 0.0625]);
+        // connections of silent neurons get no trace
+        assert_eq!(e.traces()[e.config().input_connections_per_hidden_neuron], 0.);
+        e.step(&input(&e, 0.));
+        assert_eq!([e.traces()[h], e.traces()[l], e.traces()[o]], [0.125*ANN_ELIGIBILITY_TRACE_DECAY, 0.125*ANN_ELIGIBILITY_TRACE_DECAY, 0.0625*ANN_ELIGIBILITY_TRACE_DECAY]);
+        assert_eq!(path_weights(&e), [2., 0.5, 1.]);
+    }
+
+    #[test]
+    fn reward_gates_the_sign_of_hebbian_updates(){
+        let rates = [0.1, 0.2, 0.4];
+        let mut e = plastic_entity(rates);
+        e.step(&input(&e, 0.25));
+        let traces = [0.125, 0.125, 0.0625];
+        for &reward in [0.5f32, -0.5].iter(){
+            let mut rewarded = e.clone();
+            rewarded.set_energy(e.energy() + reward);
+            rewarded.step(&input(&e, 0.25));
+            let weights = path_weights(&rewarded);
+            for (i, &initial) in [2f32, 0.5, 1.].iter().enumerate(){
+                let expected = initial + rates[i]*reward*traces[i];
+                assert!((weights[i] - expected).abs() < 1e-6, "{} != {}", weights[i], expected);
+                assert_eq!(weights[i] > initial, reward > 0.);
+            }
+            assert_eq!(rewarded.previous_energy(), rewarded.energy());
+        }
+        // without any reward nothing is learned
+        let mut unrewarded = e.clone();
+        unrewarded.step(&input(&e, 0.25));
+        assert_eq!(path_weights(&unrewarded), [2., 0.5, 1.]);
+    }
+
+    #[test]
+    fn plastic_weights_are_clamped(){
+        let mut e = plastic_entity([1000.;ANN_PLASTICITY_GENES]);
+        e.step(&input(&e, 0.25));
+        let mut up = e.clone();
+        up.set_energy(e.energy() + 1.);
+        up.step(&input(&e, 0.25));
+        assert_eq!(path_weights(&up), [ANN_PLASTIC_WEIGHT_LIMIT;3]);
+        let mut down = e.clone();
+        down.set_energy(e.energy() - 1.);
+        down.step(&input(&e, 0.25));
+        assert_eq!(path_weights(&down), [-ANN_PLASTIC_WEIGHT_LIMIT;3]);
+    }
+
+    #[test]
+    fn non_plastic_weights_never_change(){
+        let mut e = AnnEntity::new(AnnConfig::small(), 0, 1., 1.);
+        e.hidden_mut()[0].incoming_mut()[0] = AnnSparseConnection::new(0, 2.);
+        e.latent_neurons_mut()[0].incoming_from_hidden_mut()[0] = AnnSparseConnection::new(0, 0.5);
+        e.output_neurons_mut()[0].incoming_mut()[0] = AnnSparseConnection::new(0, 1.);
+        *e.learning_rates_mut() = [1.;ANN_PLASTICITY_GENES];
+        assert!(e.traces().is_empty());
+        let before = e.clone();
+        for i in 0..4{
+            e.set_energy(1. + i as f32);
+            e.step(&input(&e, 0.25));
+        }
+        assert_eq!(e.hidden(), before.hidden());
+        assert_eq!(e.latent_neurons(), before.latent_neurons());
+        assert_eq!(e.output_neurons(), before.output_neurons());
+    }
 }
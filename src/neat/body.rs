@@ -0,0 +1,324 @@
+use crate::pipelines::bone::Bone;
+use crate::pipelines::constraint::{Constraint, CONSTRAINT_TYPE_HINGE};
+use crate::pipelines::muscle::Muscle;
+use crate::neat::ann_entity::rotation_mat_from_yaw_and_pitch;
+use failure::err_msg;
+use std::path::Path;
+
+/**Stiffness of joints that hold limbs attached to their parents. Mirrored by stiffness of the Constraint*/
+pub const BODY_JOINT_STIFFNESS:f32 = 0.5;
+/**Stiffness of muscles that pull bones towards their target length*/
+pub const BODY_MUSCLE_STIFFNESS:f32 = 0.1;
+/**Fraction of relative velocity (along the constraint) that gets cancelled by every constraint. Mirrors BODY_CONSTRAINT_DAMPING in constants.comp*/
+pub const BODY_CONSTRAINT_DAMPING:f32 = 0.5;
+/**Parent of the root bone*/
+pub const NO_PARENT_BONE:u32 = u32::MAX;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Joint{
+    Distance,
+    /**The axis is expressed in the frame of the root bone*/
+    Hinge(glm::Vec3),
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct BodyPart{
+    /**Index of the parent part. Parent must come before its children. The root has no parent*/
+    pub parent:Option<usize>,
+    /**Rest offset of the center of this part from the center of its parent*/
+    pub position_relative_to_parent:glm::Vec3,
+    pub half_side_length:f32,
+    pub half_height:f32,
+    pub mass:f32,
+    /**How the part is attached to its parent. Ignored for the root*/
+    pub joint:Joint,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct BodyMuscle{
+    pub a:usize,
+    pub b:usize,
+    pub min_length:f32,
+    pub max_length:f32,
+}
+
+/**Morphology of an articulated creature. It's a tree of bones (parts) whose root is the bone of the entity.
+Every other part (limb) is attached to its parent by a joint, while muscles pull arbitrary pairs of parts
+towards target lengths chosen by the brain. On GPU the body is represented by template bones (see template_bones),
+constraints (first one joint per limb, then one constraint per muscle) and muscles. All entities of a run share the same body.
+
+The text format has one part or muscle per line. Lines starting with # are comments.
+```text
+bone <name> <parent name or -> <x> <y> <z> <half_side_length> <half_height> <mass> [distance | hinge <axis x> <axis y> <axis z>]
+muscle <bone name> <bone name> <min_length> <max_length>
+```
+The first bone is the root and must have no parent.*/
+#[derive(Clone, PartialEq, Debug)]
+pub struct Body{
+    parts:Vec<BodyPart>,
+    muscles:Vec<BodyMuscle>,
+}
+
+fn root_part()->BodyPart{
+    BodyPart{
+        parent: None,
+        position_relative_to_parent: glm::vec3(0.,0.,0.),
+        half_side_length: 0.48,
+        half_height: 0.48,
+        mass: 1.,
+        joint: Joint::Distance
+    }
+}
+
+impl Body{
+    pub fn new(parts:Vec<BodyPart>, muscles:Vec<BodyMuscle>)->Result<Self,failure::Error>{
+        let body = Self{parts,muscles};
+        body.validate()?;
+        Ok(body)
+    }
+    /**Single cube without any limbs. This is the body that all entities had before articulated bodies were introduced*/
+    pub fn cube()->Self{
+        Self{parts:vec![root_part()],muscles:vec![]}
+    }
+    /**Root with four legs attached by ball joints. Muscles span between neighbouring legs, so contracting
+    them swings the legs around the root*/
+    pub fn crawler()->Self{
+        let leg = |x:f32,z:f32|BodyPart{
+            parent: Some(0),
+            position_relative_to_parent: glm::vec3(x,-0.3,z),
+            half_side_length: 0.2,
+            half_height: 0.2,
+            mass: 0.2,
+            joint: Joint::Distance
+        };
+        let parts = vec![root_part(), leg(0.75,0.75), leg(0.75,-0.75), leg(-0.75,-0.75), leg(-0.75,0.75)];
+        let muscle = |a,b|BodyMuscle{a,b,min_length:0.9,max_length:1.8};
+        let muscles = vec![muscle(1,2), muscle(2,3), muscle(3,4), muscle(4,1)];
+        Self{parts,muscles}
+    }
+    /**Chain of segments joined by hinges with vertical axis. Every muscle spans over one segment,
+    so it bends the chain left or right*/
+    pub fn snake(segments:usize)->Self{
+        let mut parts = vec![root_part()];
+        for i in 0..segments{
+            parts.push(BodyPart{
+                parent: Some(i),
+                position_relative_to_parent: glm::vec3(if i==0{-0.8}else{-0.65},0.,0.),
+                half_side_length: 0.3,
+                half_height: 0.3,
+                mass: 0.3,
+                joint: Joint::Hinge(glm::vec3(0.,1.,0.))
+            })
+        }
+        let muscles = (2..parts.len()).map(|b|BodyMuscle{a:b-2,b,min_length:0.8,max_length:1.45}).collect();
+        Self{parts,muscles}
+    }
+    pub fn from_name(name:&str)->Option<Self>{
+        match name{
+            "cube" => Some(Self::cube()),
+            "crawler" => Some(Self::crawler()),
+            "snake" => Some(Self::snake(4)),
+            _ => None
+        }
+    }
+    /**Reads the body from EVOLUTIO_BODY environment variable. It may hold either the name of a preset body
+    (cube, crawler or snake) or a path to a body file. Defaults to cube*/
+    pub fn from_env()->Result<Self,failure::Error>{
+        match std::env::var("EVOLUTIO_BODY"){
+            Ok(name) => match Self::from_name(&name){
+                Some(body) => Ok(body),
+                None => Self::load(&name)
+            },
+            Err(_) => Ok(Self::cube())
+        }
+    }
+    pub fn load(path:impl AsRef<Path>)->Result<Self,failure::Error>{
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+    pub fn parse(text:&str)->Result<Self,failure::Error>{
+        let mut names:Vec<&str> = vec![];
+        let mut parts = vec![];
+        let mut muscles = vec![];
+        for (line_no,line) in text.lines().enumerate(){
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#'){
+                continue
+            }
+            let err = |msg:&str|err_msg(format!("Line {}: {}", line_no+1, msg));
+            let words:Vec<&str> = line.split_whitespace().collect();
+            let float = |i:usize|->Result<f32,failure::Error>{
+                words.get(i).ok_or_else(||err("missing number"))?.parse::<f32>().map_err(|e|err(&e.to_string()))
+            };
+            let part_idx = |name:&str|names.iter().position(|&n|n==name).ok_or_else(||err(&format!("unknown bone {}", name)));
+            match words[0]{
+                "bone" => {
+                    if words.len() < 9{
+                        return Err(err("expected: bone <name> <parent> <x> <y> <z> <half_side_length> <half_height> <mass> [joint]"))
+                    }
+                    let parent = if words[2]=="-" {None} else {Some(part_idx(words[2])?)};
+                    let joint = match words.get(9){
+                        None | Some(&"distance") => Joint::Distance,
+                        Some(&"hinge") => Joint::Hinge(glm::vec3(float(10)?,float(11)?,float(12)?)),
+                        Some(other) => return Err(err(&format!("unknown joint {}", other)))
+                    };
+                    parts.push(BodyPart{
+                        parent,
+                        position_relative_to_parent: glm::vec3(float(3)?,float(4)?,float(5)?),
+                        half_side_length: float(6)?,
+                        half_height: float(7)?,
+                        mass: float(8)?,
+                        joint
+                    });
+                    names.push(words[1]);
+                }
+                "muscle" => {
+                    if words.len() != 5{
+                        return Err(err("expected: muscle <bone> <bone> <min_length> <max_length>"))
+                    }
+                    muscles.push(BodyMuscle{a:part_idx(words[1])?, b:part_idx(words[2])?, min_length:float(3)?, max_length:float(4)?});
+                }
+                other => return Err(err(&format!("unknown keyword {}", other)))
+            }
+        }
+        Self::new(parts, muscles)
+    }
+    pub fn validate(&self)->Result<(),failure::Error>{
+        if self.parts.first().map(|p|p.parent.is_some()).unwrap_or(true){
+            return Err(err_msg("The first bone must be the root and can't have a parent"))
+        }
+        for (i,p) in self.parts.iter().enumerate().skip(1){
+            match p.parent{
+                Some(parent) if parent < i => {}
+                _ => return Err(err_msg(format!("Bone {} must have a parent that comes before it", i)))
+            }
+            if glm::length(&p.position_relative_to_parent) <= 0.{
+                return Err(err_msg(format!("Bone {} can't be placed at the center of its parent", i)))
+            }
+            if let Joint::Hinge(axis) = p.joint{
+                if glm::length(&axis) <= 0.{
+                    return Err(err_msg(format!("Hinge axis of bone {} can't be zero", i)))
+                }
+            }
+        }
+        for (i,m) in self.muscles.iter().enumerate(){
+            if m.a >= self.parts.len() || m.b >= self.parts.len() || m.a == m.b{
+                return Err(err_msg(format!("Muscle {} must join two different bones", i)))
+            }
+            if !(0. < m.min_length && m.min_length <= m.max_length){
+                return Err(err_msg(format!("Muscle {} must have 0 < min_length <= max_length", i)))
+            }
+        }
+        Ok(())
+    }
+    pub fn parts(&self)->&[BodyPart]{
+        &self.parts
+    }
+    pub fn muscles(&self)->&[BodyMuscle]{
+        &self.muscles
+    }
+    /**Number of parts besides the root*/
+    pub fn limbs(&self)->usize{
+        self.parts.len()-1
+    }
+    /**Rest position of every part relative to the root*/
+    pub fn rest_positions(&self)->Vec<glm::Vec3>{
+        let mut positions:Vec<glm::Vec3> = Vec::with_capacity(self.parts.len());
+        for p in &self.parts{
+            let parent = p.parent.map(|parent|positions[parent]).unwrap_or(glm::vec3(0.,0.,0.));
+            positions.push(parent + p.position_relative_to_parent);
+        }
+        positions
+    }
+    /**Bones of the body placed at rest pose around the origin. Their parent_bone_idx is the index of the parent part.
+    Shaders spawn limbs by copying these bones*/
+    pub fn template_bones(&self)->Vec<Bone>{
+        self.rest_positions().iter().zip(self.parts.iter()).map(|(&center,p)|Bone::limb(center,
+                                                                                      p.half_side_length,
+                                                                                      p.half_height,
+                                                                                      p.mass,
+                                                                                      p.parent.map(|p|p as u32).unwrap_or(NO_PARENT_BONE),
+                                                                                      p.position_relative_to_parent)).collect()
+    }
+    /**Joint of limb i is constraint i-1. The muscle constraints follow right after. Bone indices refer to parts of the body*/
+    pub fn constraints(&self)->Vec<Constraint>{
+        let joints = self.parts.iter().enumerate().skip(1).map(|(i,p)|{
+            let parent = p.parent.unwrap() as u32;
+            let dist = glm::length(&p.position_relative_to_parent);
+            match p.joint{
+                Joint::Distance => Constraint::distance(BODY_JOINT_STIFFNESS, i as u32, parent, dist),
+                Joint::Hinge(axis) => Constraint::hinge(BODY_JOINT_STIFFNESS, i as u32, parent, dist, axis),
+            }
+        });
+        let rest = self.rest_positions();
+        let muscles = self.muscles.iter().map(|m|Constraint::muscle(BODY_MUSCLE_STIFFNESS, m.a as u32, m.b as u32, glm::distance(&rest[m.a], &rest[m.b])));
+        joints.chain(muscles).collect()
+    }
+    pub fn gpu_muscles(&self)->Vec<Muscle>{
+        self.muscles.iter().enumerate().map(|(i,m)|Muscle::new((self.limbs()+i) as u32, m.min_length, m.max_length)).collect()
+    }
+    /**Creates bones of limbs at rest pose around the given root bone. Limb i gets bone index first_limb_bone_idx+i.
+    Mirrors spawn_limbs in update_ann_entities_lifecycle.comp*/
+    pub fn spawn_limbs(&self, root:&Bone, root_bone_idx:u32, first_limb_bone_idx:u32)->Vec<Bone>{
+        let template = self.template_bones();
+        let offset = root.new_center() - template[0].new_center();
+        template[1..].iter().map(|t|{
+            let mut bone = *t;
+            bone.place_at(t.new_center() + offset);
+            bone.set_entity_idx(root.entity_idx());
+            let parent = t.parent_bone_idx();
+            bone.set_parent_bone_idx(if parent==0 {root_bone_idx} else {first_limb_bone_idx + parent - 1});
+            bone
+        }).collect()
+    }
+    /**Sensed angle between the current and rest offset of every limb from its parent, normalized into range [0,1].
+    The rest offset is rotated together with the root. Mirrors the joint angle senses in update_ann_entities.comp*/
+    pub fn joint_angles(&self, bones:&[Bone], body_bone_idx:&[u32])->Vec<f32>{
+        let rotation = rotation_mat_from_yaw_and_pitch(bones[body_bone_idx[0] as usize].yaw_and_pitch());
+        self.parts.iter().enumerate().skip(1).map(|(i,p)|{
+            let this = &bones[body_bone_idx[i] as usize];
+            let parent = &bones[body_bone_idx[p.parent.unwrap()] as usize];
+            let current = this.new_center() - parent.new_center();
+            let rest = rotation * p.position_relative_to_parent;
+            let cos = glm::dot(&current, &rest) / (glm::length(&current) * glm::length(&rest)).max(0.0001);
+            cos.clamp(-1., 1.).acos() / std::f32::consts::PI
+        }).collect()
+    }
+    /**Adds the impulses of joints and muscles to the bones. Activations of muscles must lie in range [0,1].
+    Mirrors apply_body_constraints in update_ann_entities.comp*/
+    pub fn apply_joints(&self, bones:&mut [Bone], body_bone_idx:&[u32], muscle_activations:&[f32]){
+        let constraints = self.constraints();
+        let rotation = rotation_mat_from_yaw_and_pitch(bones[body_bone_idx[0] as usize].yaw_and_pitch());
+        let mut apply = |c:&Constraint, target_length:f32|{
+            let this_idx = body_bone_idx[c.this_bone_idx as usize] as usize;
+            let other_idx = body_bone_idx[c.other_bone_idx as usize] as usize;
+            let offset = bones[this_idx].new_center() - bones[other_idx].new_center();
+            let relative_velocity = bones[this_idx].velocity() - bones[other_idx].velocity();
+            let impulse = constraint_impulse(c, offset, relative_velocity, target_length, &rotation);
+            bones[this_idx].add_impulse(impulse);
+            bones[other_idx].add_impulse(-impulse);
+        };
+        for c in &constraints[..self.limbs()]{
+            apply(c, c.constant_param1);
+        }
+        for (m,&activation) in self.gpu_muscles().iter().zip(muscle_activations.iter()){
+            apply(&constraints[m.constraint_id() as usize], m.target_length(activation));
+        }
+    }
+}
+
+/**Impulse acting on the "this" bone of the constraint. The "other" bone receives the opposite impulse.
+Mirrors constraint_impulse in constants.comp*/
+pub fn constraint_impulse(c:&Constraint, offset:glm::Vec3, relative_velocity:glm::Vec3, target_length:f32, root_rotation:&glm::Mat3)->glm::Vec3{
+    let length = glm::length(&offset);
+    if length <= 0.0001{
+        return glm::vec3(0.,0.,0.)
+    }
+    let direction = offset / length;
+    let stiffness = c.stiffness;
+    let mut impulse = -direction * ((length - target_length) * stiffness + glm::dot(&relative_velocity, &direction) * BODY_CONSTRAINT_DAMPING);
+    if c.constraint_type == CONSTRAINT_TYPE_HINGE{
+        let axis = root_rotation * c.constant_param;
+        impulse -= axis * (glm::dot(&offset, &axis) * stiffness + glm::dot(&relative_velocity, &axis) * BODY_CONSTRAINT_DAMPING);
+    }
+    impulse
+}
@@ -0,0 +1,342 @@
+use crate::neat::ann_entity::*;
+use crate::pipelines::bone::{Bone, encode_ann_entity_idx};
+use crate::neat::ecology::SpawnContext;
+use crate::pipelines::lifecycle_record::NO_ENTITY_ID;
+use failure::err_msg;
+use std::fmt::Write;
+use std::path::Path;
+
+/**Identifies binary brain files*/
+pub const BRAIN_FILE_MAGIC:[u8;4] = *b"EVBR";
+/**Increase whenever the layout of AnnEntity, Bone or AnnInput changes in a release. Version 1 is the first released layout.
+Older files are not migrated, from_bytes rejects every version other than the current one*/
+pub const BRAIN_FILE_VERSION:u32 = 1;
+const BRAIN_FILE_CONFIG_LEN:usize = ANN_CONFIG_LEN;
+const BRAIN_FILE_HEADER_LEN:usize = 4 + 4 + 4*BRAIN_FILE_CONFIG_LEN + 4 + 4;
+
+/**An agent taken out of the world, together with its root bone. Limbs are not saved, because they are fully
+described by the Body of the run and are spawned again at rest pose whenever the brain is injected*/
+#[derive(Clone, Debug)]
+pub struct SavedBrain{
+    pub entity:AnnEntity,
+    pub bone:Bone,
+}
+
+impl SavedBrain{
+    pub fn new(entity:AnnEntity, bone:Bone)->Self{
+        Self{entity,bone}
+    }
+    /**Returns entity and bone with their indices updated so that they can be placed at the given slots*/
+    pub fn placed_at(&self, entity_idx:u32, bone_idx:u32)->(AnnEntity,Bone){
+        let mut entity = self.entity.clone();
+        let mut bone = self.bone;
+        entity.set_bone_idx(bone_idx);
+        bone.set_entity_idx(encode_ann_entity_idx(entity_idx));
+        (entity,bone)
+    }
+}
+
+/**Collects all entities (or only the selected ones) together with their bones*/
+pub fn collect_brains(entities:&[AnnEntity], bones:&[Bone], selected:Option<&[usize]>)->Vec<SavedBrain>{
+    let brain = |e:&AnnEntity|SavedBrain::new(e.clone(), bones[e.bone_idx() as usize]);
+    match selected{
+        Some(selected) => selected.iter().map(|&i|brain(&entities[i])).collect(),
+        None => entities.iter().map(brain).collect()
+    }
+}
+
+/**Appends saved brains at the end of entities and bones, as long as there are free slots left. Every brain gets
+a root bone followed by the limbs of the body. Ids from the file are meaningless in the current run, so every injected brain
+starts a new lineage with a fresh id taken from ctx.next_entity_id. Returns the number of injected brains*/
+pub fn inject_brains(entities:&mut Vec<AnnEntity>, bones:&mut Vec<Bone>, brains:&[SavedBrain], ctx:&mut SpawnContext)->usize{
+    let bones_per_brain = 1 + ctx.body.limbs();
+    let n = brains.len().min(ctx.max_entities.saturating_sub(entities.len())).min(ctx.max_bones.saturating_sub(bones.len()) / bones_per_brain);
+    for brain in &brains[..n]{
+        let root_bone_idx = bones.len() as u32;
+        let (mut entity,bone) = brain.placed_at(entities.len() as u32, root_bone_idx);
+        let limbs = ctx.body.spawn_limbs(&bone, root_bone_idx, root_bone_idx + 1);
+        for (i,l) in entity.limbs_mut().iter_mut().enumerate(){
+            *l = root_bone_idx + 1 + i as u32;
+        }
+        entity.set_lineage(ctx.next_entity_id, NO_ENTITY_ID, ctx.tick);
+        entity.reset_plasticity();
+        // blocks carried in another world must not appear out of thin air
+        entity.clear_inventory();
+        ctx.next_entity_id += 1;
+        entities.push(entity);
+        bones.push(bone);
+        bones.extend(limbs);
+    }
+    n
+}
+
+fn as_bytes<T:Copy>(t:&T)->&[u8]{
+    unsafe{std::slice::from_raw_parts(t as *const T as *const u8, std::mem::size_of::<T>())}
+}
+
+fn read_unaligned<T:Copy>(bytes:&[u8])->T{
+    assert!(bytes.len()>=std::mem::size_of::<T>());
+    unsafe{std::ptr::read_unaligned(bytes.as_ptr() as *const T)}
+}
+
+fn read_u32(bytes:&[u8], offset:usize)->u32{
+    u32::from_le_bytes([bytes[offset],bytes[offset+1],bytes[offset+2],bytes[offset+3]])
+}
+
+/**Compact binary form. The header holds magic, version, the ANN_CONFIG_LEN dimensions of AnnConfig (in the order of AnnConfig::as_array),
+size of Bone and number of brains. It is followed by GPU representation (see AnnEntity::to_words) of every AnnEntity and raw bytes of its Bone.
+All brains must share the same config.*/
+pub fn to_bytes(brains:&[SavedBrain])->Vec<u8>{
+    let config = brains.first().map(|b|*b.entity.config()).unwrap_or_default();
+    assert!(brains.iter().all(|b|b.entity.config()==&config), "All brains must have the same dimensions");
+    let record = config.entity_bytes()+std::mem::size_of::<Bone>();
+    let mut out = Vec::with_capacity(BRAIN_FILE_HEADER_LEN+record*brains.len());
+    out.extend_from_slice(&BRAIN_FILE_MAGIC);
+    out.extend_from_slice(&BRAIN_FILE_VERSION.to_le_bytes());
+    for dim in config.as_array().iter(){
+        out.extend_from_slice(&(*dim as u32).to_le_bytes());
+    }
+    out.extend_from_slice(&(std::mem::size_of::<Bone>() as u32).to_le_bytes());
+    out.extend_from_slice(&(brains.len() as u32).to_le_bytes());
+    let mut words = Vec::with_capacity(config.entity_words());
+    for brain in brains{
+        words.clear();
+        brain.entity.to_words(&mut words);
+        for w in &words{
+            out.extend_from_slice(&w.to_le_bytes());
+        }
+        out.extend_from_slice(as_bytes(&brain.bone));
+    }
+    out
+}
+
+pub fn from_bytes(bytes:&[u8])->Result<Vec<SavedBrain>,failure::Error>{
+    if bytes.len() < BRAIN_FILE_HEADER_LEN || bytes[0..4] != BRAIN_FILE_MAGIC{
+        return Err(err_msg("Not a brain file"))
+    }
+    let version = read_u32(bytes,4);
+    if version != BRAIN_FILE_VERSION{
+        return Err(err_msg(format!("Unsupported brain file version {} (expected {})", version, BRAIN_FILE_VERSION)))
+    }
+    let mut dims = [0usize;BRAIN_FILE_CONFIG_LEN];
+    for (i,dim) in dims.iter_mut().enumerate(){
+        *dim = read_u32(bytes,8+4*i) as usize;
+    }
+    let config = AnnConfig::from_array(dims);
+    config.validate()?;
+    let bone_offset = 8+4*BRAIN_FILE_CONFIG_LEN;
+    let bone_size = read_u32(bytes,bone_offset) as usize;
+    if bone_size != std::mem::size_of::<Bone>(){
+        return Err(err_msg(format!("Brain file layout mismatch: Bone={} but expected Bone={}", bone_size, std::mem::size_of::<Bone>())))
+    }
+    let count = read_u32(bytes,bone_offset+4) as usize;
+    let entity_size = config.entity_bytes();
+    let record = entity_size+bone_size;
+    if bytes.len() != BRAIN_FILE_HEADER_LEN + record*count{
+        return Err(err_msg(format!("Brain file should hold {} brains but its length is {}", count, bytes.len())))
+    }
+    Ok((0..count).map(|i|{
+        let offset = BRAIN_FILE_HEADER_LEN + i*record;
+        let words:Vec<u32> = (0..config.entity_words()).map(|w|read_u32(bytes,offset+4*w)).collect();
+        SavedBrain::new(AnnEntity::from_words(config, &words), read_unaligned(&bytes[offset+entity_size..]))
+    }).collect())
+}
+
+fn write_f32(out:&mut String, f:f32){
+    if f.is_finite(){
+        write!(out, "{}", f).unwrap()
+    }else{
+        out.push_str("null")
+    }
+}
+
+fn write_f32_array(out:&mut String, a:&[f32]){
+    out.push('[');
+    for (i,&f) in a.iter().enumerate(){
+        if i>0{out.push(',')}
+        write_f32(out,f);
+    }
+    out.push(']');
+}
+
+fn write_connections(out:&mut String, connections:&[AnnSparseConnection]){
+    out.push('[');
+    for (i,c) in connections.iter().enumerate(){
+        if i>0{out.push(',')}
+        write!(out, "[{},", c.src_neuron()).unwrap();
+        write_f32(out,c.weight());
+        out.push(']');
+    }
+    out.push(']');
+}
+
+fn write_brain(out:&mut String, brain:&SavedBrain){
+    let e = &brain.entity;
+    let b = &brain.bone;
+    out.push_str("{\"energy\":");
+    write_f32(out,e.energy());
+    out.push_str(",\"speed\":");
+    write_f32(out,e.speed());
+    write!(out, ",\"attack_cooldown\":{},\"id\":{},\"birth_tick\":{}", e.attack_cooldown(), e.id(), e.birth_tick()).unwrap();
+    let inventory:Vec<&str> = e.inventory()[..e.inventory_len()].iter().map(|b|b.name()).collect();
+    write!(out, ",\"block_cooldown\":{},\"inventory\":{:?}", e.block_cooldown(), inventory).unwrap();
+    if e.parent_id() == NO_ENTITY_ID{
+        out.push_str(",\"parent_id\":null");
+    }else{
+        write!(out, ",\"parent_id\":{}", e.parent_id()).unwrap();
+    }
+    out.push_str(",\"bone\":{\"center\":");
+    write_f32_array(out,b.new_center().as_slice());
+    out.push_str(",\"half_side_length\":");
+    write_f32(out,b.half_side_length());
+    out.push_str(",\"half_height\":");
+    write_f32(out,b.half_height());
+    out.push_str(",\"mass\":");
+    write_f32(out,b.mass());
+    out.push_str(",\"yaw_and_pitch\":");
+    write_f32_array(out,b.yaw_and_pitch().as_slice());
+    out.push_str("},\"lidars\":[");
+    for (i,l) in e.lidars().iter().enumerate(){
+        if i>0{out.push(',')}
+        write_f32_array(out,l.direction().as_slice());
+    }
+    out.push_str("],\"latent\":");
+    write_f32_array(out,e.latent());
+    out.push_str(",\"hidden\":[");
+    for (i,n) in e.hidden().iter().enumerate(){
+        if i>0{out.push(',')}
+        out.push_str("{\"bias\":");
+        write_f32(out,n.bias());
+        out.push_str(",\"incoming\":");
+        write_connections(out,n.incoming());
+        out.push('}');
+    }
+    out.push_str("],\"latent_neurons\":[");
+    for (i,n) in e.latent_neurons().iter().enumerate(){
+        if i>0{out.push(',')}
+        out.push_str("{\"bias\":");
+        write_f32(out,n.bias());
+        out.push_str(",\"incoming_from_hidden\":");
+        write_connections(out,n.incoming_from_hidden());
+        out.push_str(",\"recurrent_from_latent\":");
+        write_connections(out,n.recurrent_from_latent());
+        out.push('}');
+    }
+    out.push_str("],\"output\":[");
+    for (i,n) in e.output_neurons().iter().enumerate(){
+        if i>0{out.push(',')}
+        out.push_str("{\"bias\":");
+        write_f32(out,n.bias());
+        out.push_str(",\"incoming\":");
+        write_connections(out,n.incoming());
+        out.push('}');
+    }
+    out.push(']');
+    if e.config().is_plastic(){
+        out.push_str(",\"learning_rates\":");
+        write_f32_array(out,e.learning_rates());
+    }
+    out.push('}');
+}
+
+/**Human readable form meant for inspection. Brains can only be loaded back from the binary form.*/
+pub fn to_json(brains:&[SavedBrain])->String{
+    let mut out = String::new();
+    write!(out, "{{\"version\":{},\"config\":{:?},\"brains\":[", BRAIN_FILE_VERSION, brains.first().map(|b|*b.entity.config()).unwrap_or_default().as_array()).unwrap();
+    for (i,brain) in brains.iter().enumerate(){
+        if i>0{out.push_str(",\n")}
+        write_brain(&mut out,brain);
+    }
+    out.push_str("]}\n");
+    out
+}
+
+pub fn save_binary(path:impl AsRef<Path>, brains:&[SavedBrain])->Result<(),failure::Error>{
+    Ok(std::fs::write(path, to_bytes(brains))?)
+}
+
+pub fn save_json(path:impl AsRef<Path>, brains:&[SavedBrain])->Result<(),failure::Error>{
+    Ok(std::fs::write(path, to_json(brains))?)
+}
+
+pub fn load_binary(path:impl AsRef<Path>)->Result<Vec<SavedBrain>,failure::Error>{
+    from_bytes(&std::fs::read(path)?)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::blocks::block_properties::{SAND, BERRIES};
+
+    fn brains()->Vec<SavedBrain>{
+        let config = AnnConfig{plasticity:1, ..AnnConfig::small()};
+        (0..3).map(|i|{
+            let mut entity = AnnEntity::new(config, i, 0.5, 1.1 + i as f32);
+            entity.hidden_mut()[0].incoming_mut()[1] = AnnSparseConnection::new(7, -0.3);
+            entity.output_neurons_mut()[2].set_bias(0.1 * i as f32);
+            entity.latent_mut()[3] = 0.7;
+            entity.set_lineage(10 + i, if i == 0 {NO_ENTITY_ID} else {10}, 42);
+            entity.push_inventory(SAND);
+            entity.push_inventory(BERRIES);
+            entity.learning_rates_mut()[2] = 0.03;
+            let mut bone = Bone::new(glm::vec3(1. + i as f32, 2., 3.), 0.5, 1., 1.);
+            bone.set_entity_idx(encode_ann_entity_idx(i));
+            SavedBrain::new(entity, bone)
+        }).collect()
+    }
+
+    #[test]
+    fn binary_round_trip(){
+        let brains = brains();
+        let loaded = from_bytes(&to_bytes(&brains)).unwrap();
+        assert_eq!(loaded.len(), brains.len());
+        for (a,b) in brains.iter().zip(loaded.iter()){
+            assert_eq!(a.entity, b.entity);
+            assert_eq!(as_bytes(&a.bone), as_bytes(&b.bone));
+        }
+        assert!(from_bytes(&to_bytes(&[])).unwrap().is_empty());
+    }
+
+    #[test]
+    fn json_keeps_every_value(){
+        let brains = brains();
+        let json = to_json(&brains);
+        assert!(json.starts_with(&format!("{{\"version\":{},\"config\":{:?}", BRAIN_FILE_VERSION, brains[0].entity.config().as_array())));
+        assert_eq!(json.matches("{\"energy\":").count(), brains.len());
+        // floats are written in their shortest form that parses back to exactly the same value
+        let energy = &json[json.find("\"energy\":").unwrap()+9..];
+        let energy:f32 = energy[..energy.find(',').unwrap()].parse().unwrap();
+        assert_eq!(energy, brains[0].entity.energy());
+        assert!(json.contains("[7,-0.3]"));
+        assert!(json.contains("\"parent_id\":null") && json.contains("\"parent_id\":10"));
+        assert!(json.contains("\"inventory\":[\"sand\", \"berries\"]"));
+        assert!(json.contains("\"learning_rates\":[0,0,0.03]"));
+        assert_eq!(json.matches('[').count(), json.matches(']').count());
+        assert_eq!(json.matches('{').count(), json.matches('}').count());
+        let mut nan = brains[0].clone();
+        nan.entity.set_energy(f32::NAN);
+        assert!(to_json(&[nan]).contains("{\"energy\":null,"));
+    }
+
+    #[test]
+    fn rejects_bad_magic_and_version(){
+        let mut bytes = to_bytes(&brains());
+        bytes[0] = b'X';
+        assert!(from_bytes(&bytes).is_err());
+        let mut bytes = to_bytes(&brains());
+        bytes[4..8].copy_from_slice(&(BRAIN_FILE_VERSION + 1).to_le_bytes());
+        let err = from_bytes(&bytes).unwrap_err().to_string();
+        assert!(err.contains("Unsupported brain file version"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_truncated_input(){
+        let bytes = to_bytes(&brains());
+        for &len in &[0, 3, BRAIN_FILE_HEADER_LEN - 1, BRAIN_FILE_HEADER_LEN, bytes.len() - 1]{
+            assert!(from_bytes(&bytes[..len]).is_err(), "{} bytes", len);
+        }
+        let mut longer = bytes.clone();
+        longer.push(0);
+        assert!(from_bytes(&longer).is_err());
+    }
+}
@@ -0,0 +1,824 @@
+use crate::neat::ann_entity::*;
+use crate::neat::num::Num;
+use crate::pipelines::bone::{Bone, encode_ann_entity_idx, decode_entity_idx, decode_entity_type, ENTITY_TYPE_ANN};
+use crate::blocks::BlockId;
+use crate::neat::util::RandRange;
+use crate::neat::body::Body;
+use crate::pipelines::lifecycle_record::LifecycleRecord;
+use crate::pipelines::particle::Particle;
+use crate::blocks::ray_cast;
+
+/**Energy lost per unit of length of the directional impulse. Only paid when the impulse is actually
+applied, that is when the agent is standing on the ground*/
+pub const ANN_MOVEMENT_ENERGY_COST:f32 = 0.002;
+/**Energy lost every tick just for being alive*/
+pub const ANN_METABOLISM_ENERGY_COST:f32 = 0.0005;
+/**Energy gained every tick per each corner of the bone that touches an edible block*/
+pub const ANN_FOOD_ENERGY_GAIN:f32 = 0.004;
+pub const ANN_MAX_ENERGY:f32 = 8.;
+pub const ANN_INITIAL_ENERGY:f32 = 2.;
+/**Agents with at least this much energy will spawn a child*/
+pub const ANN_REPRODUCTION_ENERGY_THRESHOLD:f32 = 4.;
+/**Fraction of parent's energy that is passed on to the child*/
+pub const ANN_CHILD_ENERGY_FRACTION:f32 = 0.5;
+/**Every weight and bias of the child is shifted by a random value in range (-ANN_WEIGHT_MUTATION_STRENGTH,ANN_WEIGHT_MUTATION_STRENGTH)*/
+pub const ANN_WEIGHT_MUTATION_STRENGTH:f32 = 0.1;
+/**Probability that src_neuron of a connection gets rewired to a random neuron*/
+pub const ANN_REWIRE_PROBABILITY:f32 = 0.02;
+/**Learning rate genes of plastic brains are shifted by a random value in range (-ANN_LEARNING_RATE_MUTATION_STRENGTH,ANN_LEARNING_RATE_MUTATION_STRENGTH)
+and can't become negative*/
+pub const ANN_LEARNING_RATE_MUTATION_STRENGTH:f32 = 0.01;
+/**Learning rate genes of entities generated together with the world are drawn from range [0,ANN_MAX_INITIAL_LEARNING_RATE)*/
+pub const ANN_MAX_INITIAL_LEARNING_RATE:f32 = 0.05;
+/**An entity attacks when the average activation of its attack muscles exceeds this value*/
+pub const ANN_ATTACK_THRESHOLD:f32 = 0.5;
+/**Energy paid by the attacker for every attack, regardless of whether it hit anything*/
+pub const ANN_ATTACK_ENERGY_COST:f32 = 0.05;
+/**Energy taken from the victim (and given to the attacker) by a single successful attack*/
+pub const ANN_ATTACK_ENERGY_DRAIN:f32 = 0.5;
+/**Number of ticks that must pass between two attacks of the same entity*/
+pub const ANN_ATTACK_COOLDOWN:u32 = 32;
+/**Length of the attack ray, measured from the center of attacker's bone along its facing direction*/
+pub const ANN_ATTACK_RANGE:f32 = 1.5;
+/**An entity breaks (or places) a block when the average activation of its break (or place) muscles exceeds this value*/
+pub const ANN_BLOCK_EDIT_THRESHOLD:f32 = 0.5;
+/**Number of ticks that must pass between two block edits of the same entity*/
+pub const ANN_BLOCK_EDIT_COOLDOWN:u32 = 64;
+/**Energy paid for every block that was broken or placed*/
+pub const ANN_BLOCK_EDIT_ENERGY_COST:f32 = 0.02;
+/**Length of the ray that selects the edited block, measured from the center of the bone along its facing direction*/
+pub const ANN_BLOCK_REACH:f32 = 2.;
+/**Energy gained by breaking an edible block. Edible blocks are eaten instead of being put into the inventory*/
+pub const ANN_HARVEST_ENERGY_GAIN:f32 = 0.5;
+/**An entity emits a signal particle when activation of its strongest signal muscle exceeds this value*/
+pub const ANN_SIGNAL_THRESHOLD:f32 = 0.5;
+/**Energy paid for every emitted signal particle*/
+pub const ANN_SIGNAL_ENERGY_COST:f32 = 0.005;
+/**Particles further away from the center of the bone can't be sensed. Closer particles are sensed more strongly*/
+pub const ANN_SIGNAL_SENSE_RADIUS:f32 = 8.;
+/**Speed of a freshly emitted particle. It flies in the direction the entity is facing*/
+pub const ANN_SIGNAL_EMISSION_SPEED:f32 = 0.1;
+
+/**Mirrors the energy accounting at the end of update_ann_entities.comp. Entities with limbs don't pay for
+the directional impulse, because it is never applied to them*/
+pub fn energy_change(step:&AnnEntityStep, is_standing_on_the_ground:bool, touched_blocks:&[BlockId;ANN_TOUCHED_BLOCK_COUNT]) -> f32{
+    let food = touched_blocks.iter().filter(|b|b.is_edible()).count() as f32;
+    let movement = if is_standing_on_the_ground && step.config.body_limbs == 0 { glm::length(&step.directional_impulse) * ANN_MOVEMENT_ENERGY_COST } else { 0. };
+    food * ANN_FOOD_ENERGY_GAIN - movement - ANN_METABOLISM_ENERGY_COST
+}
+
+/**Mirrors the attack logic of update_ann_entities.comp. Returns true if the entity attacks in this step.
+The cooldown is ticked down (or reset after an attack) and the cost of attack is paid.*/
+pub fn update_attack(entity:&mut AnnEntity, step:&AnnEntityStep) -> bool{
+    if entity.attack_cooldown() > 0{
+        entity.set_attack_cooldown(entity.attack_cooldown() - 1);
+        false
+    }else if step.attack_strength() > ANN_ATTACK_THRESHOLD{
+        entity.set_attack_cooldown(ANN_ATTACK_COOLDOWN);
+        apply_energy_change(entity, -ANN_ATTACK_ENERGY_COST);
+        true
+    }else{
+        false
+    }
+}
+
+/**What an entity does to the block in front of it*/
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BlockEdit{
+    /**Removes the block hit by the block edit ray*/
+    Break,
+    /**Puts the last block of the inventory into the air right in front of the block hit by the block edit ray*/
+    Place,
+}
+
+/**Mirrors the block edit logic of update_ann_entities.comp. Returns the edit that the entity attempts in this step,
+which is the stronger of the two actions, as long as it exceeds ANN_BLOCK_EDIT_THRESHOLD. The cooldown is ticked down,
+but nothing is paid yet, because the edit may still fail (see resolve_block_edit)*/
+pub fn update_block_edit(entity:&mut AnnEntity, step:&AnnEntityStep) -> Option<BlockEdit>{
+    if entity.block_cooldown() > 0{
+        entity.set_block_cooldown(entity.block_cooldown() - 1);
+        return None
+    }
+    let break_strength = step.break_strength();
+    let place_strength = step.place_strength();
+    if break_strength.max(place_strength) <= ANN_BLOCK_EDIT_THRESHOLD{
+        None
+    }else if break_strength >= place_strength{
+        Some(BlockEdit::Break)
+    }else{
+        Some(BlockEdit::Place)
+    }
+}
+
+/**The block edit ray starts at the center of the bone and points along the direction the bone is facing*/
+pub fn block_edit_ray(bone:&Bone) -> glm::Vec3{
+    direction_from_yaw_and_pitch(bone.yaw_and_pitch()) * ANN_BLOCK_REACH
+}
+
+/**Position of the first block along the block edit ray that is not air, followed by the position of the block right in front of it
+(where a block would be placed). None if there is only air within reach. Blocks beyond the borders of the world must be reported
+as not air, because they stop the ray too. Mirrors ray_cast in utils.comp*/
+pub fn block_edit_target(bone:&Bone, is_air:impl Fn(i32,i32,i32)->bool) -> Option<([i32;3],[i32;3])>{
+    let center = bone.new_center();
+    ray_cast(center.as_slice(), block_edit_ray(bone).as_slice(), |x, y, z, prev_x, prev_y, prev_z|{
+        if is_air(x as i32, y as i32, z as i32){
+            None
+        }else{
+            Some(([x as i32, y as i32, z as i32], [prev_x as i32, prev_y as i32, prev_z as i32]))
+        }
+    })
+}
+
+/**Mirrors try_enqueue_agent_block_edit from utils.comp. Agents share all but the last slot of the buffer of block edits,
+which is left for the player (see update_player_events.comp). The counter keeps growing past the buffer and every edit
+that doesn't fit is rejected*/
+pub fn try_enqueue_agent_block_edit(agent_block_edits:&mut u32, max_blocks_to_be_inserted_or_removed:u32) -> bool{
+    let idx = *agent_block_edits;
+    *agent_block_edits += 1;
+    idx + 1 < max_blocks_to_be_inserted_or_removed
+}
+
+/**Applies the edit to the inventory and energy of the entity. The target is the block hit by the block edit ray when breaking,
+or the block right in front of it when placing (the caller must make sure it isn't the block occupied by the entity itself).
+Breaking feeds the entity with edible blocks and puts other breakable blocks into the inventory, as long as there is space.
+Placing takes the last block of the inventory. Once all of that is possible, the edit is enqueued (see try_enqueue_agent_block_edit)
+and it fails if there is no space for it. Returns the block that should replace the target, or None if the edit failed.
+Cost and cooldown are only paid by successful edits*/
+pub fn resolve_block_edit(entity:&mut AnnEntity, edit:BlockEdit, target:BlockId, enqueue:impl FnOnce()->bool) -> Option<BlockId>{
+    let (new_block, energy_change) = match edit{
+        BlockEdit::Break => {
+            let fits = target.is_edible() || entity.inventory_len() < ANN_INVENTORY_SIZE;
+            if !target.is_breakable() || !fits || !enqueue(){
+                return None
+            }else if target.is_edible(){
+                (BlockId::air(), ANN_HARVEST_ENERGY_GAIN)
+            }else{
+                entity.push_inventory(target);
+                (BlockId::air(), 0.)
+            }
+        }
+        BlockEdit::Place => {
+            if !target.is_air() || entity.inventory_len() == 0 || !enqueue(){
+                return None
+            }
+            (entity.pop_inventory()?, 0.)
+        }
+    };
+    entity.set_block_cooldown(ANN_BLOCK_EDIT_COOLDOWN);
+    apply_energy_change(entity, energy_change - ANN_BLOCK_EDIT_ENERGY_COST);
+    Some(new_block)
+}
+
+/**Mirrors the signal emission of update_ann_entities.comp. At most one particle is emitted per step, on the channel
+of the most active signal muscle, and its strength is the activation of that muscle. The particle is appended only if
+there is space for it, in which case the entity pays for it. Returns true if a particle was emitted*/
+pub fn update_signal(entity:&mut AnnEntity, entity_idx:u32, step:&AnnEntityStep, bone:&Bone, particles:&mut Vec<Particle>, max_particles:usize) -> bool{
+    let mut strongest:Option<(usize,f32)> = None;
+    for (channel,&activation) in step.signal_muscles().iter().enumerate(){
+        if activation > strongest.map(|(_,a)|a).unwrap_or(ANN_SIGNAL_THRESHOLD){
+            strongest = Some((channel,activation));
+        }
+    }
+    match strongest{
+        Some((channel,strength)) if particles.len() < max_particles => {
+            let velocity = direction_from_yaw_and_pitch(bone.yaw_and_pitch()) * ANN_SIGNAL_EMISSION_SPEED;
+            particles.push(Particle::signal(bone.new_center(), velocity, encode_ann_entity_idx(entity_idx), channel as u32, strength));
+            apply_energy_change(entity, -ANN_SIGNAL_ENERGY_COST);
+            true
+        }
+        _ => false
+    }
+}
+
+/**Strength and direction (in world coordinates) of the signal of every channel, as sensed from the center of the bone.
+Every particle within ANN_SIGNAL_SENSE_RADIUS contributes its strength, which linearly falls off with distance.
+The direction is the weighted average of directions towards the particles. Particles emitted by the entity itself are ignored.
+Mirrors update_ann_entities.comp. Results are meant to be passed to AnnInput::set_signal*/
+pub fn sense_signals(channels:usize, entity_idx:u32, bone:&Bone, particles:&[Particle]) -> Vec<(f32,glm::Vec3)>{
+    let mut sensed = vec![(0.,glm::vec3(0.,0.,0.));channels];
+    let own = encode_ann_entity_idx(entity_idx);
+    for p in particles{
+        let channel = p.channel() as usize;
+        if !p.is_alive() || !p.is_signal() || channel >= channels || p.emitter_entity == own{
+            continue
+        }
+        let position = p.position;
+        let offset = position - bone.new_center();
+        let distance = glm::length(&offset);
+        if distance < ANN_SIGNAL_SENSE_RADIUS{
+            let w = p.strength() * (1. - distance / ANN_SIGNAL_SENSE_RADIUS);
+            let (sum, direction) = &mut sensed[channel];
+            *sum += w;
+            *direction += offset * (w / distance.max(0.0001));
+        }
+    }
+    sensed.into_iter().map(|(sum,direction)|(sum.min(1.), direction / sum.max(0.0001))).collect()
+}
+
+/**The attack ray starts at the center of the bone and points along the direction the bone is facing*/
+pub fn attack_ray(bone:&Bone) -> glm::Vec3{
+    direction_from_yaw_and_pitch(bone.yaw_and_pitch()) * ANN_ATTACK_RANGE
+}
+
+/**Moves at most ANN_ATTACK_ENERGY_DRAIN of energy from the victim to the attacker. Only ANN entities can be drained.
+Returns the amount of transferred energy*/
+pub fn resolve_attack(entities:&mut [AnnEntity], bones:&[Bone], attacker_idx:usize, hit_bone_idx:usize) -> f32{
+    let victim = bones[hit_bone_idx].entity_idx();
+    if decode_entity_type(victim) != ENTITY_TYPE_ANN{
+        return 0.
+    }
+    let victim_idx = decode_entity_idx(victim) as usize;
+    if victim_idx == attacker_idx || victim_idx >= entities.len(){
+        return 0.
+    }
+    let drained = entities[victim_idx].energy().clamp(0., ANN_ATTACK_ENERGY_DRAIN);
+    let victim = &mut entities[victim_idx];
+    victim.set_energy(victim.energy() - drained);
+    apply_energy_change(&mut entities[attacker_idx], drained);
+    drained
+}
+
+pub fn apply_energy_change(entity:&mut AnnEntity, change:f32){
+    entity.set_energy((entity.energy() + change).min(ANN_MAX_ENERGY))
+}
+
+pub fn is_dead(entity:&AnnEntity) -> bool{
+    entity.energy() <= 0.
+}
+
+pub fn can_reproduce(entity:&AnnEntity) -> bool{
+    entity.energy() >= ANN_REPRODUCTION_ENERGY_THRESHOLD
+}
+
+/**Indices refer to the state of entities right after the event. Ids are the stable ids of entities (see AnnEntity::id)*/
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum LifecycleEvent{
+    Death{entity_idx:usize, bone_idx:usize, entity_id:u32},
+    Birth{parent_idx:usize, child_idx:usize, bone_idx:usize, parent_id:u32, child_id:u32},
+}
+
+impl LifecycleEvent{
+    /**The record that update_ann_entities_lifecycle.comp appends for this event*/
+    pub fn to_record(&self, tick:u32) -> LifecycleRecord{
+        match *self{
+            LifecycleEvent::Death{entity_id, ..} => LifecycleRecord::death(entity_id, tick),
+            LifecycleEvent::Birth{parent_id, child_id, ..} => LifecycleRecord::birth(child_id, parent_id, tick),
+        }
+    }
+}
+
+/**Removes bone by moving the last bone into its slot. If the moved bone belongs to an ANN entity,
+its bone_idx (or the index of its limb) is updated accordingly, together with parent_bone_idx of the bones
+attached to it. Bones of HTM entities are only fixed up on GPU*/
+pub fn remove_bone(entities:&mut [AnnEntity], bones:&mut Vec<Bone>, bone_idx:usize){
+    let last = bones.len() - 1;
+    bones.swap_remove(bone_idx);
+    if bone_idx < last{
+        let moved_entity = bones[bone_idx].entity_idx();
+        if decode_entity_type(moved_entity) == ENTITY_TYPE_ANN{
+            let e = decode_entity_idx(moved_entity) as usize;
+            if e < entities.len(){
+                let entity = &mut entities[e];
+                if entity.bone_idx() as usize == last{
+                    entity.set_bone_idx(bone_idx as u32);
+                }
+                for l in entity.limbs_mut().iter_mut(){
+                    if *l as usize == last{
+                        *l = bone_idx as u32;
+                    }
+                }
+                for part in 0..=entity.limbs().len(){
+                    let b = entity.body_bone_idx(part) as usize;
+                    if b < bones.len() && bones[b].parent_bone_idx() as usize == last{
+                        bones[b].set_parent_bone_idx(bone_idx as u32);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/**Removes the entity together with all bones of its body. The last entity is moved into the freed slot (the same way
+update_ann_entities_lifecycle.comp does it)*/
+pub fn remove_ann_entity(entities:&mut Vec<AnnEntity>, bones:&mut Vec<Bone>, entity_idx:usize){
+    for limb in (0..entities[entity_idx].limbs().len()).rev(){
+        let bone_idx = entities[entity_idx].limbs()[limb] as usize;
+        remove_bone(entities, bones, bone_idx);
+        entities[entity_idx].limbs_mut()[limb] = u32::MAX;
+    }
+    let bone_idx = entities[entity_idx].bone_idx() as usize;
+    remove_bone(entities, bones, bone_idx);
+    entities.swap_remove(entity_idx);
+    if entity_idx < entities.len(){
+        let moved = &entities[entity_idx];
+        for part in 0..=moved.limbs().len(){
+            bones[moved.body_bone_idx(part) as usize].set_entity_idx(encode_ann_entity_idx(entity_idx as u32));
+        }
+    }
+}
+
+fn mutate_connection(connection:&mut AnnSparseConnection, src_neuron_count:usize){
+    if f32::random() < ANN_REWIRE_PROBABILITY{
+        connection.set_src_neuron(src_neuron_count.random() as u32);
+    }
+    connection.set_weight(connection.weight() + (f32::random()*2.-1.)*ANN_WEIGHT_MUTATION_STRENGTH);
+}
+
+fn mutate_bias(bias:f32) -> f32{
+    bias + (f32::random()*2.-1.)*ANN_WEIGHT_MUTATION_STRENGTH
+}
+
+/**Shifts all weights and biases (and learning rate genes of plastic brains) by a small random value and rewires some of the connections*/
+pub fn mutate(entity:&mut AnnEntity){
+    let config = *entity.config();
+    if config.is_plastic(){
+        for l in entity.learning_rates_mut().iter_mut(){
+            *l = (*l + (f32::random()*2.-1.)*ANN_LEARNING_RATE_MUTATION_STRENGTH).max(0.);
+        }
+    }
+    for neuron in entity.hidden_mut().iter_mut(){
+        neuron.set_bias(mutate_bias(neuron.bias()));
+        for c in neuron.incoming_mut().iter_mut(){
+            mutate_connection(c, config.input_size());
+        }
+    }
+    for neuron in entity.latent_neurons_mut().iter_mut(){
+        neuron.set_bias(mutate_bias(neuron.bias()));
+        for c in neuron.incoming_from_hidden_mut().iter_mut(){
+            mutate_connection(c, config.hidden_size);
+        }
+        for c in neuron.recurrent_from_latent_mut().iter_mut(){
+            mutate_connection(c, config.latent_size);
+        }
+    }
+    for neuron in entity.output_neurons_mut().iter_mut(){
+        neuron.set_bias(mutate_bias(neuron.bias()));
+        for c in neuron.incoming_mut().iter_mut(){
+            mutate_connection(c, config.latent_size);
+        }
+    }
+}
+
+/**Position of a child is right next to its parent*/
+pub fn child_position(parent_bone:&Bone) -> glm::Vec3{
+    parent_bone.new_center() + glm::vec3(parent_bone.half_side_length()*2. + 0.02, 0., 0.)
+}
+
+/**Everything that spawning of children depends on, besides the entities and bones themselves*/
+pub struct SpawnContext<'a>{
+    pub body:&'a Body,
+    pub max_entities:usize,
+    pub max_bones:usize,
+    /**Id of the next spawned child. It is incremented with every birth*/
+    pub next_entity_id:u32,
+    pub tick:u32,
+}
+
+/**Spawns a copy of the parent with its own bones (the root followed by limbs of the body) and splits parent's energy between the two.
+Weights learned by a plastic parent are inherited, but eligibility traces are not.
+The child gets id ctx.next_entity_id (which is then incremented) and remembers the parent's id and the tick of its birth.
+Returns the index of the child or None if there is no more space for entities or bones. The child is not mutated yet.*/
+pub fn spawn_ann_child(entities:&mut Vec<AnnEntity>, bones:&mut Vec<Bone>, ctx:&mut SpawnContext, parent_idx:usize) -> Option<usize>{
+    if entities.len() >= ctx.max_entities || bones.len() + 1 + ctx.body.limbs() > ctx.max_bones{
+        return None
+    }
+    let child_idx = entities.len();
+    let bone_idx = bones.len();
+    let parent = &mut entities[parent_idx];
+    let mut bone = bones[parent.bone_idx() as usize];
+    bone.place_at(child_position(&bone));
+    bone.set_entity_idx(encode_ann_entity_idx(child_idx as u32));
+    let child_energy = parent.energy() * ANN_CHILD_ENERGY_FRACTION;
+    parent.set_energy(parent.energy() - child_energy);
+    // energy given to the child is not a punishment
+    parent.set_previous_energy(parent.previous_energy() - child_energy);
+    let mut child = parent.clone();
+    child.set_energy(child_energy);
+    child.reset_plasticity();
+    child.set_bone_idx(bone_idx as u32);
+    child.set_attack_cooldown(0);
+    child.clear_inventory();
+    child.set_lineage(ctx.next_entity_id, parent.id(), ctx.tick);
+    ctx.next_entity_id += 1;
+    for l in child.latent_mut(){
+        *l = 0.;
+    }
+    for (i,l) in child.limbs_mut().iter_mut().enumerate(){
+        *l = (bone_idx + 1 + i) as u32;
+    }
+    let limbs = ctx.body.spawn_limbs(&bone, bone_idx as u32, bone_idx as u32 + 1);
+    bones.push(bone);
+    bones.extend(limbs);
+    entities.push(child);
+    Some(child_idx)
+}
+
+/**CPU reference of update_ann_entities_lifecycle.comp. First all entities that ran out of energy are removed.
+Then every surviving entity above reproduction threshold spawns one mutated child (as long as there is enough space).*/
+pub fn update_population(entities:&mut Vec<AnnEntity>, bones:&mut Vec<Bone>, ctx:&mut SpawnContext) -> Vec<LifecycleEvent>{
+    let mut events = Vec::new();
+    let mut i = 0;
+    while i < entities.len(){
+        if is_dead(&entities[i]){
+            events.push(LifecycleEvent::Death{entity_idx:i, bone_idx:entities[i].bone_idx() as usize, entity_id:entities[i].id()});
+            remove_ann_entity(entities, bones, i);
+        }else{
+            i += 1;
+        }
+    }
+    let alive = entities.len();
+    for parent_idx in 0..alive{
+        if can_reproduce(&entities[parent_idx]){
+            if let Some(child_idx) = spawn_ann_child(entities, bones, ctx, parent_idx){
+                mutate(&mut entities[child_idx]);
+                let child = &entities[child_idx];
+                events.push(LifecycleEvent::Birth{parent_idx, child_idx, bone_idx:child.bone_idx() as usize, parent_id:child.parent_id(), child_id:child.id()});
+            }
+        }
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::blocks::block_properties::{AIR, WATER, STONE, BERRIES, BEDROCK};
+
+    /**Appends an entity with id equal to its index, together with the root bone and limbs of the body*/
+    fn add_entity(entities:&mut Vec<AnnEntity>, bones:&mut Vec<Bone>, body:&Body, energy:f32){
+        let entity_idx = entities.len();
+        let bone_idx = bones.len();
+        let mut root = Bone::new(glm::vec3(entity_idx as f32*4., 10., 0.), 0.5, 1., 1.);
+        root.set_entity_idx(encode_ann_entity_idx(entity_idx as u32));
+        let mut entity = AnnEntity::new(AnnConfig{body_limbs:body.limbs(), ..AnnConfig::small()}, bone_idx as u32, 1., energy);
+        entity.set_lineage(entity_idx as u32, crate::pipelines::lifecycle_record::NO_ENTITY_ID, 0);
+        for (i,l) in entity.limbs_mut().iter_mut().enumerate(){
+            *l = (bone_idx + 1 + i) as u32;
+        }
+        bones.push(root);
+        bones.extend(body.spawn_limbs(&root, bone_idx as u32, bone_idx as u32 + 1));
+        entities.push(entity);
+    }
+
+    /**Every bone belongs to exactly one entity, points back at it and is attached to the right parent bone*/
+    fn assert_consistent(entities:&[AnnEntity], bones:&[Bone], body:&Body){
+        assert_eq!(bones.len(), entities.len() * (1 + body.limbs()));
+        let mut owned = vec![false;bones.len()];
+        for (i,e) in entities.iter().enumerate(){
+            for (part,p) in body.parts().iter().enumerate(){
+                let b = e.body_bone_idx(part) as usize;
+                assert!(!owned[b], "bone {} is owned twice", b);
+                owned[b] = true;
+                assert_eq!(bones[b].entity_idx(), encode_ann_entity_idx(i as u32));
+                if let Some(parent) = p.parent{
+                    assert_eq!(bones[b].parent_bone_idx(), e.body_bone_idx(parent));
+                }
+            }
+        }
+    }
+
+    fn ctx(body:&Body) -> SpawnContext<'_>{
+        SpawnContext{body, max_entities:16, max_bones:128, next_entity_id:100, tick:7}
+    }
+
+    #[test]
+    fn starving_entities_die(){
+        let body = Body::cube();
+        let (mut entities, mut bones) = (Vec::new(), Vec::new());
+        for &energy in &[1., 0., 1., -0.5]{
+            add_entity(&mut entities, &mut bones, &body, energy);
+        }
+        let events = update_population(&mut entities, &mut bones, &mut ctx(&body));
+        assert_eq!(events, vec![
+            LifecycleEvent::Death{entity_idx:1, bone_idx:1, entity_id:1},
+            LifecycleEvent::Death{entity_idx:1, bone_idx:1, entity_id:3},
+        ]);
+        assert_eq!(entities.iter().map(|e|e.id()).collect::<Vec<_>>(), vec![0, 2]);
+        assert_consistent(&entities, &bones, &body);
+    }
+
+    #[test]
+    fn reproduction_splits_energy(){
+        let body = Body::crawler();
+        let (mut entities, mut bones) = (Vec::new(), Vec::new());
+        add_entity(&mut entities, &mut bones, &body, ANN_REPRODUCTION_ENERGY_THRESHOLD);
+        add_entity(&mut entities, &mut bones, &body, ANN_REPRODUCTION_ENERGY_THRESHOLD - 0.1);
+        let mut ctx = ctx(&body);
+        let events = update_population(&mut entities, &mut bones, &mut ctx);
+        assert_eq!(events, vec![LifecycleEvent::Birth{parent_idx:0, child_idx:2, bone_idx:10, parent_id:0, child_id:100}]);
+        assert_eq!(ctx.next_entity_id, 101);
+        let child_energy = ANN_REPRODUCTION_ENERGY_THRESHOLD * ANN_CHILD_ENERGY_FRACTION;
+        assert_eq!(entities[2].energy(), child_energy);
+        assert_eq!(entities[0].energy(), ANN_REPRODUCTION_ENERGY_THRESHOLD - child_energy);
+        assert_eq!(entities[1].energy(), ANN_REPRODUCTION_ENERGY_THRESHOLD - 0.1);
+        assert_eq!((entities[2].parent_id(), entities[2].birth_tick()), (0, 7));
+        assert_consistent(&entities, &bones, &body);
+    }
+
+    #[test]
+    fn spawning_respects_entity_and_bone_limits(){
+        let body = Body::crawler();
+        let (mut entities, mut bones) = (Vec::new(), Vec::new());
+        add_entity(&mut entities, &mut bones, &body, ANN_MAX_ENERGY);
+        let mut ctx = SpawnContext{max_entities:1, ..ctx(&body)};
+        assert_eq!(spawn_ann_child(&mut entities, &mut bones, &mut ctx, 0), None);
+        ctx.max_entities = 2;
+        ctx.max_bones = bones.len() + body.limbs();
+        assert_eq!(spawn_ann_child(&mut entities, &mut bones, &mut ctx, 0), None);
+        assert_eq!((entities.len(), bones.len(), entities[0].energy(), ctx.next_entity_id), (1, 5, ANN_MAX_ENERGY, 100));
+        ctx.max_bones += 1;
+        assert_eq!(spawn_ann_child(&mut entities, &mut bones, &mut ctx, 0), Some(1));
+        assert_eq!(bones.len(), ctx.max_bones);
+        assert_consistent(&entities, &bones, &body);
+    }
+
+    #[test]
+    fn removal_fixes_up_moved_bones(){
+        let body = Body::crawler();
+        let (mut entities, mut bones) = (Vec::new(), Vec::new());
+        for _ in 0..4{
+            add_entity(&mut entities, &mut bones, &body, 1.);
+        }
+        let centers = |entities:&[AnnEntity], bones:&[Bone], id:u32|{
+            let e = entities.iter().find(|e|e.id()==id).unwrap();
+            (0..=body.limbs()).map(|part|bones[e.body_bone_idx(part) as usize].new_center()).collect::<Vec<_>>()
+        };
+        let last_before = centers(&entities, &bones, 3);
+        remove_ann_entity(&mut entities, &mut bones, 0);
+        assert_eq!(entities.iter().map(|e|e.id()).collect::<Vec<_>>(), vec![3, 1, 2]);
+        assert_consistent(&entities, &bones, &body);
+        assert_eq!(centers(&entities, &bones, 3), last_before);
+        remove_ann_entity(&mut entities, &mut bones, 1);
+        assert_eq!(entities.iter().map(|e|e.id()).collect::<Vec<_>>(), vec![3, 2]);
+        assert_consistent(&entities, &bones, &body);
+        assert_eq!(centers(&entities, &bones, 3), last_before);
+        remove_ann_entity(&mut entities, &mut bones, 1);
+        remove_ann_entity(&mut entities, &mut bones, 0);
+        assert!(entities.is_empty() && bones.is_empty());
+    }
+
+    /**Step of an entity with AnnConfig::small() whose outputs are all zero, except for the given ones*/
+    fn step_with(offset:usize, values:&[f32]) -> AnnEntityStep{
+        let config = AnnConfig::small();
+        let mut outputs = vec![0.;config.output_size()];
+        outputs[offset..offset+values.len()].copy_from_slice(values);
+        AnnEntityStep{config, outputs, rotation_change:glm::vec2(0., 0.), directional_impulse:glm::vec3(0., 0., 0.)}
+    }
+
+    fn attacking_step() -> AnnEntityStep{
+        let config = AnnConfig::small();
+        step_with(config.output_attack_muscles_offset(), &vec![1.;config.output_attack_muscles_size])
+    }
+
+    fn assert_close(a:f32, b:f32){
+        assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn attack_moves_energy_from_victim_to_attacker(){
+        let body = Body::cube();
+        let (mut entities, mut bones) = (Vec::new(), Vec::new());
+        add_entity(&mut entities, &mut bones, &body, 2.);
+        add_entity(&mut entities, &mut bones, &body, 3.);
+        let weak = step_with(AnnConfig::small().output_attack_muscles_offset(), &[ANN_ATTACK_THRESHOLD;4]);
+        assert!(!update_attack(&mut entities[0], &weak));
+        assert_eq!(entities[0].energy(), 2.);
+        assert!(update_attack(&mut entities[0], &attacking_step()));
+        assert_eq!(entities[0].attack_cooldown(), ANN_ATTACK_COOLDOWN);
+        assert_close(entities[0].energy(), 2. - ANN_ATTACK_ENERGY_COST);
+        let victim_bone = entities[1].bone_idx() as usize;
+        assert_eq!(resolve_attack(&mut entities, &bones, 0, victim_bone), ANN_ATTACK_ENERGY_DRAIN);
+        // everything the victim loses goes to the attacker
+        assert_close(entities[0].energy(), 2. - ANN_ATTACK_ENERGY_COST + ANN_ATTACK_ENERGY_DRAIN);
+        assert_close(entities[1].energy(), 3. - ANN_ATTACK_ENERGY_DRAIN);
+        // the attack can't be repeated until the cooldown is over
+        for _ in 0..ANN_ATTACK_COOLDOWN{
+            assert!(!update_attack(&mut entities[0], &attacking_step()));
+        }
+        assert!(update_attack(&mut entities[0], &attacking_step()));
+    }
+
+    #[test]
+    fn attacker_can_not_exceed_max_energy(){
+        let body = Body::cube();
+        let (mut entities, mut bones) = (Vec::new(), Vec::new());
+        add_entity(&mut entities, &mut bones, &body, ANN_MAX_ENERGY - 0.1);
+        add_entity(&mut entities, &mut bones, &body, 3.);
+        let victim_bone = entities[1].bone_idx() as usize;
+        assert_eq!(resolve_attack(&mut entities, &bones, 0, victim_bone), ANN_ATTACK_ENERGY_DRAIN);
+        // energy above the maximum is lost
+        assert_eq!(entities[0].energy(), ANN_MAX_ENERGY);
+        assert_close(entities[1].energy(), 3. - ANN_ATTACK_ENERGY_DRAIN);
+    }
+
+    #[test]
+    fn attack_drains_at_most_what_victim_has(){
+        let body = Body::cube();
+        let (mut entities, mut bones) = (Vec::new(), Vec::new());
+        add_entity(&mut entities, &mut bones, &body, 1.);
+        add_entity(&mut entities, &mut bones, &body, 0.2);
+        let victim_bone = entities[1].bone_idx() as usize;
+        assert_eq!(resolve_attack(&mut entities, &bones, 0, victim_bone), 0.2);
+        assert_eq!(entities[1].energy(), 0.);
+        assert!(is_dead(&entities[1]));
+        assert_close(entities[0].energy(), 1.2);
+    }
+
+    #[test]
+    fn mutual_attacks_cancel_out(){
+        let body = Body::cube();
+        let (mut entities, mut bones) = (Vec::new(), Vec::new());
+        add_entity(&mut entities, &mut bones, &body, 1.);
+        add_entity(&mut entities, &mut bones, &body, 3.);
+        let bone_of = |entities:&[AnnEntity], i:usize| entities[i].bone_idx() as usize;
+        for e in entities.iter_mut(){
+            assert!(update_attack(e, &attacking_step()));
+        }
+        let (b0, b1) = (bone_of(&entities, 0), bone_of(&entities, 1));
+        let mut reversed = entities.clone();
+        resolve_attack(&mut entities, &bones, 0, b1);
+        resolve_attack(&mut entities, &bones, 1, b0);
+        resolve_attack(&mut reversed, &bones, 1, b0);
+        resolve_attack(&mut reversed, &bones, 0, b1);
+        // both pay for the attack and get back what they lost, regardless of the order of resolution
+        assert_close(entities[0].energy(), 1. - ANN_ATTACK_ENERGY_COST);
+        assert_close(entities[1].energy(), 3. - ANN_ATTACK_ENERGY_COST);
+        assert_close(reversed[0].energy(), entities[0].energy());
+        assert_close(reversed[1].energy(), entities[1].energy());
+    }
+
+    #[test]
+    fn attack_on_dead_or_removed_target_transfers_nothing(){
+        let body = Body::cube();
+        let (mut entities, mut bones) = (Vec::new(), Vec::new());
+        add_entity(&mut entities, &mut bones, &body, 1.);
+        add_entity(&mut entities, &mut bones, &body, 0.);
+        add_entity(&mut entities, &mut bones, &body, 2.);
+        let dead_bone = entities[1].bone_idx() as usize;
+        assert_eq!(resolve_attack(&mut entities, &bones, 0, dead_bone), 0.);
+        assert_eq!((entities[0].energy(), entities[1].energy()), (1., 0.));
+        // the attacker can't drain itself
+        let own_bone = entities[0].bone_idx() as usize;
+        assert_eq!(resolve_attack(&mut entities, &bones, 0, own_bone), 0.);
+        // bone whose entity no longer exists
+        let removed_bone = entities[2].bone_idx() as usize;
+        let stale = bones.clone();
+        remove_ann_entity(&mut entities, &mut bones, 2);
+        assert_eq!(resolve_attack(&mut entities, &stale, 0, removed_bone), 0.);
+        // HTM entities can't be drained
+        let mut htm_bones = bones.clone();
+        htm_bones[dead_bone].set_entity_idx(crate::pipelines::bone::encode_htm_entity_idx(0));
+        assert_eq!(resolve_attack(&mut entities, &htm_bones, 0, dead_bone), 0.);
+        assert_eq!((entities[0].energy(), entities[1].energy()), (1., 0.));
+    }
+
+    fn entity_with_energy(energy:f32) -> AnnEntity{
+        AnnEntity::new(AnnConfig::small(), 0, 1., energy)
+    }
+
+    #[test]
+    fn block_edits_wait_for_cooldown_and_threshold(){
+        let config = AnnConfig::small();
+        let offset = config.output_block_muscles_offset();
+        let mut entity = entity_with_energy(1.);
+        assert_eq!(update_block_edit(&mut entity, &step_with(offset, &[ANN_BLOCK_EDIT_THRESHOLD;2])), None);
+        assert_eq!(update_block_edit(&mut entity, &step_with(offset, &[1., 0.])), Some(BlockEdit::Break));
+        assert_eq!(update_block_edit(&mut entity, &step_with(offset, &[0., 1.])), Some(BlockEdit::Place));
+        // nothing is paid before the edit is resolved
+        assert_eq!((entity.energy(), entity.block_cooldown()), (1., 0));
+        entity.set_block_cooldown(2);
+        assert_eq!(update_block_edit(&mut entity, &step_with(offset, &[1., 0.])), None);
+        assert_eq!(update_block_edit(&mut entity, &step_with(offset, &[1., 0.])), None);
+        assert_eq!(update_block_edit(&mut entity, &step_with(offset, &[1., 0.])), Some(BlockEdit::Break));
+    }
+
+    #[test]
+    fn block_edits_cost_energy(){
+        let mut entity = entity_with_energy(1.);
+        assert_eq!(resolve_block_edit(&mut entity, BlockEdit::Break, STONE, ||true), Some(BlockId::air()));
+        assert_close(entity.energy(), 1. - ANN_BLOCK_EDIT_ENERGY_COST);
+        assert_eq!(entity.block_cooldown(), ANN_BLOCK_EDIT_COOLDOWN);
+        assert_eq!(entity.inventory_len(), 1);
+        // edible blocks are eaten instead of stored
+        assert_eq!(resolve_block_edit(&mut entity, BlockEdit::Break, BERRIES, ||true), Some(BlockId::air()));
+        assert_close(entity.energy(), 1. - 2. * ANN_BLOCK_EDIT_ENERGY_COST + ANN_HARVEST_ENERGY_GAIN);
+        assert_eq!(entity.inventory_len(), 1);
+        assert_eq!(resolve_block_edit(&mut entity, BlockEdit::Place, AIR, ||true), Some(STONE));
+        assert_close(entity.energy(), 1. - 3. * ANN_BLOCK_EDIT_ENERGY_COST + ANN_HARVEST_ENERGY_GAIN);
+        assert_eq!(entity.inventory_len(), 0);
+    }
+
+    #[test]
+    fn block_edits_are_rejected_when_the_buffer_is_full(){
+        let max = 4;
+        let mut agent_block_edits = max - 2;
+        let mut entity = entity_with_energy(1.);
+        assert!(resolve_block_edit(&mut entity, BlockEdit::Break, STONE, ||try_enqueue_agent_block_edit(&mut agent_block_edits, max)).is_some());
+        // the last slot is left for the player
+        let before = entity.clone();
+        assert_eq!(resolve_block_edit(&mut entity, BlockEdit::Place, AIR, ||try_enqueue_agent_block_edit(&mut agent_block_edits, max)), None);
+        assert_eq!(resolve_block_edit(&mut entity, BlockEdit::Break, BERRIES, ||try_enqueue_agent_block_edit(&mut agent_block_edits, max)), None);
+        assert_eq!(agent_block_edits, max + 1);
+        assert_eq!(entity.energy(), before.energy());
+        assert_eq!(entity.inventory_len(), 1);
+        assert_eq!(entity.block_cooldown(), before.block_cooldown());
+    }
+
+    #[test]
+    fn air_and_indestructible_blocks_can_not_be_broken(){
+        let mut entity = entity_with_energy(1.);
+        for &target in &[AIR, WATER, BEDROCK]{
+            // rejected before the edit ever takes a slot of the buffer
+            assert_eq!(resolve_block_edit(&mut entity, BlockEdit::Break, target, ||panic!("enqueued {:?}", target)), None);
+        }
+        for _ in 0..ANN_INVENTORY_SIZE{
+            assert!(entity.push_inventory(STONE));
+        }
+        assert_eq!(resolve_block_edit(&mut entity, BlockEdit::Break, STONE, ||panic!("enqueued with full inventory")), None);
+        // blocks can only be placed into air
+        assert_eq!(resolve_block_edit(&mut entity, BlockEdit::Place, STONE, ||panic!("placed into stone")), None);
+        assert_eq!(resolve_block_edit(&mut entity, BlockEdit::Place, WATER, ||panic!("placed into water")), None);
+        while entity.pop_inventory().is_some(){}
+        assert_eq!(resolve_block_edit(&mut entity, BlockEdit::Place, AIR, ||panic!("placed from empty inventory")), None);
+        assert_eq!((entity.energy(), entity.block_cooldown()), (1., 0));
+    }
+
+    #[test]
+    fn block_edits_reach_only_nearby_blocks(){
+        let mut bone = Bone::new(glm::vec3(0.5, 0.5, 0.5), 0.5, 1., 1.);
+        bone.set_yaw_and_pitch(glm::vec2(0., 0.));
+        let stone_at = |stone_x:i32| move |x:i32, y:i32, z:i32| !(x == stone_x && y == 0 && z == 0);
+        assert_eq!(block_edit_target(&bone, stone_at(2)), Some(([2, 0, 0], [1, 0, 0])));
+        assert_eq!(block_edit_target(&bone, stone_at(3)), None);
+        assert_eq!(block_edit_target(&bone, stone_at(-1)), None);
+        // turning around brings the block behind into reach
+        bone.set_yaw_and_pitch(glm::vec2(std::f32::consts::PI, 0.));
+        assert_eq!(block_edit_target(&bone, stone_at(-1)), Some(([-1, 0, 0], [0, 0, 0])));
+        assert_close(glm::length(&block_edit_ray(&bone)), ANN_BLOCK_REACH);
+    }
+
+    fn signal(position:glm::Vec3, entity_idx:u32, channel:u32, strength:f32) -> Particle{
+        Particle::signal(position, glm::vec3(0., 0., 0.), encode_ann_entity_idx(entity_idx), channel, strength)
+    }
+
+    #[test]
+    fn signals_encode_channel_and_strength(){
+        let offset = AnnConfig::small().output_signal_muscles_offset();
+        let mut entity = entity_with_energy(1.);
+        let mut bone = Bone::new(glm::vec3(10., 10., 10.), 0.5, 1., 1.);
+        bone.set_yaw_and_pitch(glm::vec2(0., 0.));
+        let mut particles = Vec::new();
+        assert!(!update_signal(&mut entity, 3, &step_with(offset, &[ANN_SIGNAL_THRESHOLD;2]), &bone, &mut particles, 2));
+        // the most active channel wins
+        assert!(update_signal(&mut entity, 3, &step_with(offset, &[0.6, 0.8]), &bone, &mut particles, 2));
+        assert!(update_signal(&mut entity, 3, &step_with(offset, &[0.7, 0.2]), &bone, &mut particles, 2));
+        assert_eq!(particles.iter().map(|p|p.channel()).collect::<Vec<_>>(), vec![1, 0]);
+        assert_close(particles[0].strength(), 0.8);
+        assert_close(particles[1].strength(), 0.7);
+        let p = particles[0];
+        assert!(p.is_signal());
+        assert_eq!({p.emitter_entity}, encode_ann_entity_idx(3));
+        assert_close({p.velocity}.x, ANN_SIGNAL_EMISSION_SPEED);
+        assert_close(entity.energy(), 1. - 2. * ANN_SIGNAL_ENERGY_COST);
+        // nothing is emitted or paid once the buffer of particles is full
+        assert!(!update_signal(&mut entity, 3, &step_with(offset, &[1., 1.]), &bone, &mut particles, 2));
+        assert_eq!(particles.len(), 2);
+        assert_close(entity.energy(), 1. - 2. * ANN_SIGNAL_ENERGY_COST);
+    }
+
+    #[test]
+    fn sensed_signals_fall_off_with_distance(){
+        let bone = Bone::new(glm::vec3(10., 10., 10.), 0.5, 1., 1.);
+        let at = |dx:f32| glm::vec3(10. + dx, 10., 10.);
+        let strength_at = |dx:f32, strength:f32| sense_signals(2, 0, &bone, &[signal(at(dx), 1, 1, strength)])[1].0;
+        assert_close(strength_at(ANN_SIGNAL_SENSE_RADIUS / 2., 1.), 0.5);
+        assert_close(strength_at(ANN_SIGNAL_SENSE_RADIUS / 4., 0.5), 0.375);
+        assert_eq!(strength_at(ANN_SIGNAL_SENSE_RADIUS, 1.), 0.);
+        // every channel is sensed separately, its direction points towards the particles and its sum saturates at 1
+        let sensed = sense_signals(2, 0, &bone, &[signal(at(1.), 1, 0, 1.), signal(at(-1.), 2, 1, 0.5), signal(at(2.), 3, 0, 1.)]);
+        assert_eq!(sensed[0].0, 1.);
+        assert_close(sensed[0].1.x, 1.);
+        assert_close(sensed[1].0, 0.5 * (1. - 1. / ANN_SIGNAL_SENSE_RADIUS));
+        assert_close(sensed[1].1.x, -1.);
+        // channels beyond the ones sensed by the entity are ignored
+        assert_eq!(sense_signals(1, 0, &bone, &[signal(at(1.), 1, 1, 1.)])[0].0, 0.);
+    }
+
+    #[test]
+    fn only_signals_of_others_are_sensed(){
+        let bone = Bone::new(glm::vec3(10., 10., 10.), 0.5, 1., 1.);
+        let near = glm::vec3(11., 10., 10.);
+        let mut dead = signal(near, 1, 0, 1.);
+        dead.energy = 0.;
+        let ignored = [
+            signal(near, 0, 0, 1.),
+            Particle::debris(near, glm::vec3(0., 0., 0.)),
+            Particle::trail(near, &glm::vec3(1., 0., 0.)).unwrap(),
+            dead,
+        ];
+        assert!(ignored[1].channel() == 0 && ignored[2].channel() == 0 && !ignored[1].is_signal() && !ignored[2].is_signal());
+        assert_eq!(sense_signals(2, 0, &bone, &ignored)[0].0, 0.);
+        // the same signal is sensed by any other entity
+        assert!(sense_signals(2, 1, &bone, &ignored[..1])[0].0 > 0.);
+    }
+}
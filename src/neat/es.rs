@@ -0,0 +1,518 @@
+use crate::neat::ann_entity::*;
+use crate::neat::ecology::{energy_change, apply_energy_change, is_dead, ANN_INITIAL_ENERGY, ANN_METABOLISM_ENERGY_COST};
+use crate::blocks::BlockId;
+use failure::err_msg;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand_distr::{Distribution, StandardNormal};
+use std::path::Path;
+
+/**Identifies binary checkpoints of EvolutionStrategy*/
+pub const ES_CHECKPOINT_MAGIC:[u8;4] = *b"EVES";
+/**Increase whenever the layout of the checkpoint (or of AnnEntity) changes*/
+pub const ES_CHECKPOINT_VERSION:u32 = 2;
+
+/**Number of parameters optimised by EvolutionStrategy. Only weights and biases are trained,
+src_neuron of every connection (the topology) stays fixed*/
+pub fn parameter_count(config:&AnnConfig)->usize{
+    config.hidden_size*(config.input_connections_per_hidden_neuron+1)
+        + config.latent_size*(config.hidden_connections_per_latent_neuron+config.latent_connections_per_latent_neuron+1)
+        + config.output_size()*(config.latent_connections_per_output_neuron+1)
+}
+
+/**Flattens all weights and biases into a single vector. Neurons are visited in the same order as in AnnEntity::to_words
+and every neuron contributes its incoming weights followed by its bias*/
+pub fn get_parameters(entity:&AnnEntity)->Vec<f32>{
+    let mut params = Vec::with_capacity(parameter_count(entity.config()));
+    let weights = |params:&mut Vec<f32>, connections:&[AnnSparseConnection]|params.extend(connections.iter().map(|c|c.weight()));
+    for n in entity.hidden(){
+        weights(&mut params, n.incoming());
+        params.push(n.bias());
+    }
+    for n in entity.latent_neurons(){
+        weights(&mut params, n.incoming_from_hidden());
+        weights(&mut params, n.recurrent_from_latent());
+        params.push(n.bias());
+    }
+    for n in entity.output_neurons(){
+        weights(&mut params, n.incoming());
+        params.push(n.bias());
+    }
+    params
+}
+
+/**Inverse of get_parameters. The slice must hold exactly parameter_count values*/
+pub fn set_parameters(entity:&mut AnnEntity, params:&[f32]){
+    assert_eq!(params.len(), parameter_count(entity.config()));
+    let mut params = params.iter().cloned();
+    let weights = |connections:&mut [AnnSparseConnection], params:&mut dyn Iterator<Item=f32>|for c in connections{
+        c.set_weight(params.next().unwrap())
+    };
+    for n in entity.hidden_mut(){
+        weights(n.incoming_mut(), &mut params);
+        n.set_bias(params.next().unwrap());
+    }
+    for n in entity.latent_neurons_mut(){
+        weights(n.incoming_from_hidden_mut(), &mut params);
+        weights(n.recurrent_from_latent_mut(), &mut params);
+        n.set_bias(params.next().unwrap());
+    }
+    for n in entity.output_neurons_mut(){
+        weights(n.incoming_mut(), &mut params);
+        n.set_bias(params.next().unwrap());
+    }
+}
+
+/**Assigns fitness to candidate brains (higher is better). Candidates come in batches, so that an evaluator
+backed by the GPU simulation can inject all of them at once (see Foundations::inject_brains), run the world for a while
+and read the energies back. Any closure taking a single entity works as a CPU evaluator.*/
+pub trait EsEvaluator{
+    /**Must return exactly one fitness per candidate*/
+    fn evaluate(&mut self, candidates:&[AnnEntity])->Vec<f32>;
+}
+
+impl <F:FnMut(&AnnEntity)->f32> EsEvaluator for F{
+    fn evaluate(&mut self, candidates:&[AnnEntity])->Vec<f32>{
+        candidates.iter().map(|c|self(c)).collect()
+    }
+}
+
+/**Lets a single entity graze on a flat layer of blocks, using the CPU reference of the forward pass (AnnEntity::step)
+and the energy accounting of ecology.rs. There is no physics. The bone slides over the ground by directional_impulse*speed
+every tick and its top corners always touch air. Lidars can only hit the ground. Fitness is the energy left at the end
+plus the metabolism cost of every tick the entity stayed alive, so that surviving longer is rewarded even if the entity
+never finds food.*/
+#[derive(Clone, Debug)]
+pub struct CpuWorldEvaluator{
+    /**Blocks right below the surface, row by row (x changes fastest). Outside of the map there is only air*/
+    ground:Vec<BlockId>,
+    width:usize,
+    depth:usize,
+    ticks:u32,
+    half_side_length:f32,
+    half_height:f32,
+}
+
+impl CpuWorldEvaluator{
+    pub fn new(ground:Vec<BlockId>, width:usize, depth:usize, ticks:u32)->Self{
+        assert_eq!(ground.len(), width*depth);
+        Self{ground, width, depth, ticks, half_side_length:0.5, half_height:0.5}
+    }
+    pub fn with_bone_size(mut self, half_side_length:f32, half_height:f32)->Self{
+        self.half_side_length = half_side_length;
+        self.half_height = half_height;
+        self
+    }
+    pub fn ticks(&self)->u32{
+        self.ticks
+    }
+    /**Ground surface lies at y=0*/
+    pub fn ground_at(&self, x:f32, z:f32)->BlockId{
+        if x < 0. || z < 0. || x >= self.width as f32 || z >= self.depth as f32{
+            BlockId::air()
+        }else{
+            self.ground[x as usize + z as usize * self.width]
+        }
+    }
+    /**Mirrors the ray cast of update_ann_entities.comp against the flat ground*/
+    fn lidar_hit(&self, center:glm::Vec3, direction:glm::Vec3)->AnnLidarHit{
+        if direction.y < 0.{
+            let ratio = center.y / -direction.y;
+            if ratio <= 1.{
+                let hit = center + direction*ratio;
+                let block = self.ground_at(hit.x, hit.z);
+                if !block.is_air(){
+                    return AnnLidarHit{ratio_of_traversed_length:ratio, block:Some(block), entity_energy:None}
+                }
+            }
+        }
+        AnnLidarHit{ratio_of_traversed_length:1., block:None, entity_energy:None}
+    }
+    /**Runs a single episode. The entity starts in the middle of the map facing along x axis*/
+    pub fn fitness(&self, candidate:&AnnEntity)->f32{
+        let mut entity = candidate.clone();
+        entity.set_energy(ANN_INITIAL_ENERGY);
+        entity.latent_mut().iter_mut().for_each(|l|*l=0.);
+        let config = *entity.config();
+        let mut center = glm::vec3(self.width as f32/2., self.half_height, self.depth as f32/2.);
+        let mut yaw_and_pitch = glm::vec2(0.,0.);
+        let mut input = AnnInput::new(config);
+        for tick in 0..self.ticks{
+            let mut touched_blocks = [BlockId::air();ANN_TOUCHED_BLOCK_COUNT];
+            for (corner,touched) in touched_blocks.iter_mut().enumerate(){
+                if corner & 2 == 0{
+                    let reach = 1.1*self.half_side_length;
+                    let sign = |bit:usize|if corner & bit == 0 {-1.} else {1.};
+                    *touched = self.ground_at(center.x + sign(1)*reach, center.z + sign(4)*reach);
+                }
+                input.set_touched_block(corner, *touched, touched.mass());
+            }
+            let rotation = rotation_mat_from_yaw_and_pitch(yaw_and_pitch);
+            for (i,lidar) in entity.lidars().iter().enumerate(){
+                let hit = self.lidar_hit(center, rotation*lidar.direction());
+                input.set_lidar(i, &hit, entity.energy());
+            }
+            let step = entity.step(input.as_slice());
+            yaw_and_pitch = step.rotate(yaw_and_pitch);
+            let is_standing_on_the_ground = touched_blocks.iter().enumerate().any(|(corner,b)|corner & 2 == 0 && !b.is_air());
+            if is_standing_on_the_ground && config.body_limbs == 0{
+                let impulse = step.directional_impulse * entity.speed();
+                center.x += impulse.x;
+                center.z += impulse.z;
+            }
+            apply_energy_change(&mut entity, energy_change(&step, is_standing_on_the_ground, &touched_blocks));
+            if is_dead(&entity){
+                return tick as f32 * ANN_METABOLISM_ENERGY_COST
+            }
+        }
+        entity.energy() + self.ticks as f32 * ANN_METABOLISM_ENERGY_COST
+    }
+}
+
+impl EsEvaluator for CpuWorldEvaluator{
+    fn evaluate(&mut self, candidates:&[AnnEntity])->Vec<f32>{
+        candidates.iter().map(|c|self.fitness(c)).collect()
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct EsConfig{
+    /**Every generation evaluates 2*population_pairs candidates, because every perturbation is also tried with the opposite sign*/
+    pub population_pairs:usize,
+    /**Standard deviation of perturbations*/
+    pub sigma:f32,
+    pub learning_rate:f32,
+    /**Pulls parameters towards 0 by learning_rate*weight_decay*parameter every generation*/
+    pub weight_decay:f32,
+    /**Perturbations of generation g are drawn from a generator seeded with seed and g, so a run can be resumed
+    from a checkpoint and still produce exactly the same candidates*/
+    pub seed:u64,
+}
+
+impl Default for EsConfig{
+    fn default()->Self{
+        Self{
+            population_pairs: 32,
+            sigma: 0.05,
+            learning_rate: 0.01,
+            weight_decay: 0.005,
+            seed: 0,
+        }
+    }
+}
+
+const ADAM_BETA1:f32 = 0.9;
+const ADAM_BETA2:f32 = 0.999;
+const ADAM_EPSILON:f32 = 1e-8;
+
+/**Fitness of a single generation*/
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct EsGenerationStats{
+    pub generation:u32,
+    pub mean_fitness:f32,
+    pub min_fitness:f32,
+    pub max_fitness:f32,
+    /**Best fitness of all generations so far*/
+    pub best_fitness:f32,
+}
+
+/**Maps fitness to centered ranks in [-0.5,0.5], so that the update does not depend on the scale of fitness
+and a single outlier can't dominate the gradient*/
+pub fn centered_ranks(fitness:&[f32])->Vec<f32>{
+    let mut order:Vec<usize> = (0..fitness.len()).collect();
+    order.sort_by(|&a,&b|fitness[a].partial_cmp(&fitness[b]).unwrap_or(std::cmp::Ordering::Equal));
+    let mut ranks = vec![0.;fitness.len()];
+    let denominator = (fitness.len().max(2) - 1) as f32;
+    for (rank,&i) in order.iter().enumerate(){
+        ranks[i] = rank as f32 / denominator - 0.5;
+    }
+    ranks
+}
+
+/**OpenAI-style evolution strategy over weights and biases of a brain with fixed topology. The template provides
+the topology (src_neuron of connections), lidars and everything else that is not trained. Candidates come in antithetic pairs
+mean+sigma*noise and mean-sigma*noise, their fitness is shaped with centered_ranks and the mean is moved along the estimated gradient with Adam.*/
+#[derive(Clone, Debug)]
+pub struct EvolutionStrategy{
+    config:EsConfig,
+    template:AnnEntity,
+    mean:Vec<f32>,
+    adam_m:Vec<f32>,
+    adam_v:Vec<f32>,
+    generation:u32,
+    best_fitness:f32,
+    best:Vec<f32>,
+}
+
+impl EvolutionStrategy{
+    pub fn new(template:AnnEntity, config:EsConfig)->Self{
+        assert!(config.population_pairs > 0);
+        let mean = get_parameters(&template);
+        let n = mean.len();
+        Self{
+            config,
+            template,
+            best: mean.clone(),
+            mean,
+            adam_m: vec![0.;n],
+            adam_v: vec![0.;n],
+            generation: 0,
+            best_fitness: f32::NEG_INFINITY,
+        }
+    }
+    pub fn config(&self)->&EsConfig{
+        &self.config
+    }
+    pub fn generation(&self)->u32{
+        self.generation
+    }
+    pub fn mean(&self)->&[f32]{
+        &self.mean
+    }
+    pub fn best_fitness(&self)->f32{
+        self.best_fitness
+    }
+    fn entity_with(&self, params:&[f32])->AnnEntity{
+        let mut entity = self.template.clone();
+        set_parameters(&mut entity, params);
+        entity
+    }
+    /**Template with the current mean parameters*/
+    pub fn mean_entity(&self)->AnnEntity{
+        self.entity_with(&self.mean)
+    }
+    /**The best candidate evaluated so far (or the template if nothing was evaluated yet)*/
+    pub fn best_entity(&self)->AnnEntity{
+        self.entity_with(&self.best)
+    }
+    /**Perturbations of the current generation, population_pairs vectors of parameter_count values*/
+    fn noise(&self)->Vec<Vec<f32>>{
+        let seed = self.config.seed ^ (self.generation as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..self.config.population_pairs).map(|_|{
+            (0..self.mean.len()).map(|_|StandardNormal.sample(&mut rng)).collect()
+        }).collect()
+    }
+    /**Candidates of the current generation. Candidate 2i is mean+sigma*noise[i] and candidate 2i+1 is mean-sigma*noise[i]*/
+    pub fn ask(&self)->Vec<AnnEntity>{
+        let sigma = self.config.sigma;
+        let mut candidates = Vec::with_capacity(2*self.config.population_pairs);
+        let mut params = vec![0.;self.mean.len()];
+        for eps in self.noise(){
+            for &sign in &[1.,-1.]{
+                for ((p,&m),&e) in params.iter_mut().zip(self.mean.iter()).zip(eps.iter()){
+                    *p = m + sign*sigma*e;
+                }
+                candidates.push(self.entity_with(&params));
+            }
+        }
+        candidates
+    }
+    /**Updates the mean with fitness of candidates returned by ask (in the same order) and moves on to the next generation*/
+    pub fn tell(&mut self, fitness:&[f32])->EsGenerationStats{
+        assert_eq!(fitness.len(), 2*self.config.population_pairs);
+        let noise = self.noise();
+        let (best_idx, &max_fitness) = fitness.iter().enumerate().max_by(|a,b|a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal)).unwrap();
+        if max_fitness > self.best_fitness{
+            self.best_fitness = max_fitness;
+            let sign = if best_idx % 2 == 0 {1.} else {-1.};
+            for ((b,&m),&e) in self.best.iter_mut().zip(self.mean.iter()).zip(noise[best_idx/2].iter()){
+                *b = m + sign*self.config.sigma*e;
+            }
+        }
+        let ranks = centered_ranks(fitness);
+        let mut gradient = vec![0f32;self.mean.len()];
+        for (pair,eps) in noise.iter().enumerate(){
+            let weight = ranks[2*pair] - ranks[2*pair+1];
+            for (g,&e) in gradient.iter_mut().zip(eps.iter()){
+                *g += weight*e;
+            }
+        }
+        let scale = 1. / (fitness.len() as f32 * self.config.sigma);
+        let t = (self.generation + 1) as i32;
+        let (correction1, correction2) = (1. - ADAM_BETA1.powi(t), 1. - ADAM_BETA2.powi(t));
+        for i in 0..self.mean.len(){
+            // ascends fitness, while weight decay pulls towards 0
+            let g = gradient[i]*scale - self.config.weight_decay*self.mean[i];
+            self.adam_m[i] = ADAM_BETA1*self.adam_m[i] + (1.-ADAM_BETA1)*g;
+            self.adam_v[i] = ADAM_BETA2*self.adam_v[i] + (1.-ADAM_BETA2)*g*g;
+            let m = self.adam_m[i] / correction1;
+            let v = self.adam_v[i] / correction2;
+            self.mean[i] += self.config.learning_rate * m / (v.sqrt() + ADAM_EPSILON);
+        }
+        let stats = EsGenerationStats{
+            generation: self.generation,
+            mean_fitness: fitness.iter().sum::<f32>() / fitness.len() as f32,
+            min_fitness: fitness.iter().cloned().fold(f32::INFINITY, f32::min),
+            max_fitness,
+            best_fitness: self.best_fitness,
+        };
+        self.generation += 1;
+        stats
+    }
+    /**Evaluates a single generation*/
+    pub fn step(&mut self, evaluator:&mut impl EsEvaluator)->Result<EsGenerationStats,failure::Error>{
+        let candidates = self.ask();
+        let fitness = evaluator.evaluate(&candidates);
+        if fitness.len() != candidates.len(){
+            return Err(err_msg(format!("Evaluator returned {} fitness values for {} candidates", fitness.len(), candidates.len())))
+        }
+        Ok(self.tell(&fitness))
+    }
+
+    /**Binary checkpoint. The header holds magic, version, the ANN_CONFIG_LEN dimensions of AnnConfig, EsConfig, generation,
+    best fitness and number of parameters. It is followed by GPU representation of the template (see AnnEntity::to_words)
+    and then by mean, both Adam moments and best parameters. Perturbations don't need to be saved, because they are derived from the seed.*/
+    pub fn to_bytes(&self)->Vec<u8>{
+        let mut out = Vec::new();
+        out.extend_from_slice(&ES_CHECKPOINT_MAGIC);
+        out.extend_from_slice(&ES_CHECKPOINT_VERSION.to_le_bytes());
+        for dim in self.template.config().as_array().iter(){
+            out.extend_from_slice(&(*dim as u32).to_le_bytes());
+        }
+        out.extend_from_slice(&(self.config.population_pairs as u32).to_le_bytes());
+        out.extend_from_slice(&self.config.sigma.to_le_bytes());
+        out.extend_from_slice(&self.config.learning_rate.to_le_bytes());
+        out.extend_from_slice(&self.config.weight_decay.to_le_bytes());
+        out.extend_from_slice(&self.config.seed.to_le_bytes());
+        out.extend_from_slice(&self.generation.to_le_bytes());
+        out.extend_from_slice(&self.best_fitness.to_le_bytes());
+        out.extend_from_slice(&(self.mean.len() as u32).to_le_bytes());
+        let mut words = Vec::with_capacity(self.template.config().entity_words());
+        self.template.to_words(&mut words);
+        for w in &words{
+            out.extend_from_slice(&w.to_le_bytes());
+        }
+        for v in [&self.mean, &self.adam_m, &self.adam_v, &self.best].iter(){
+            for f in v.iter(){
+                out.extend_from_slice(&f.to_le_bytes());
+            }
+        }
+        out
+    }
+    pub fn from_bytes(bytes:&[u8])->Result<Self,failure::Error>{
+        let header_len = 4 + 4 + 4*ANN_CONFIG_LEN + 4*4 + 8 + 4*3;
+        if bytes.len() < header_len || bytes[0..4] != ES_CHECKPOINT_MAGIC{
+            return Err(err_msg("Not an evolution strategy checkpoint"))
+        }
+        let mut r = ByteReader{bytes, pos:4};
+        let version = r.u32();
+        if version != ES_CHECKPOINT_VERSION{
+            return Err(err_msg(format!("Unsupported checkpoint version {} (expected {})", version, ES_CHECKPOINT_VERSION)))
+        }
+        let mut dims = [0usize;ANN_CONFIG_LEN];
+        for dim in dims.iter_mut(){
+            *dim = r.u32() as usize;
+        }
+        let ann_config = AnnConfig::from_array(dims);
+        ann_config.validate()?;
+        let config = EsConfig{
+            population_pairs: r.u32() as usize,
+            sigma: r.f32(),
+            learning_rate: r.f32(),
+            weight_decay: r.f32(),
+            seed: r.u64(),
+        };
+        if config.population_pairs == 0{
+            return Err(err_msg("Checkpoint has no population"))
+        }
+        let generation = r.u32();
+        let best_fitness = r.f32();
+        let n = r.u32() as usize;
+        if n != parameter_count(&ann_config){
+            return Err(err_msg(format!("Checkpoint holds {} parameters but its brain has {}", n, parameter_count(&ann_config))))
+        }
+        if bytes.len() != header_len + ann_config.entity_bytes() + 4*4*n{
+            return Err(err_msg(format!("Checkpoint should hold {} parameters but its length is {}", n, bytes.len())))
+        }
+        let words:Vec<u32> = (0..ann_config.entity_words()).map(|_|r.u32()).collect();
+        let template = AnnEntity::from_words(ann_config, &words);
+        let mut vector = ||(0..n).map(|_|r.f32()).collect::<Vec<f32>>();
+        let mean = vector();
+        let adam_m = vector();
+        let adam_v = vector();
+        let best = vector();
+        Ok(Self{config, template, mean, adam_m, adam_v, generation, best_fitness, best})
+    }
+    pub fn save(&self, path:impl AsRef<Path>)->Result<(),failure::Error>{
+        Ok(std::fs::write(path, self.to_bytes())?)
+    }
+    pub fn load(path:impl AsRef<Path>)->Result<Self,failure::Error>{
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+}
+
+/**Reads consecutive little endian values of a checkpoint. Bounds are checked by the caller*/
+struct ByteReader<'a>{
+    bytes:&'a [u8],
+    pos:usize,
+}
+impl <'a> ByteReader<'a>{
+    fn take<const N:usize>(&mut self)->[u8;N]{
+        let mut a = [0u8;N];
+        a.copy_from_slice(&self.bytes[self.pos..self.pos+N]);
+        self.pos += N;
+        a
+    }
+    fn u32(&mut self)->u32{
+        u32::from_le_bytes(self.take())
+    }
+    fn u64(&mut self)->u64{
+        u64::from_le_bytes(self.take())
+    }
+    fn f32(&mut self)->f32{
+        f32::from_le_bytes(self.take())
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn es(seed:u64)->EvolutionStrategy{
+        EvolutionStrategy::new(AnnEntity::new(AnnConfig::small(), 0, 1., 1.), EsConfig{population_pairs:4, seed, ..EsConfig::default()})
+    }
+
+    /**Fitness is the higher the closer all parameters are to 1*/
+    fn fitness(candidates:&[AnnEntity])->Vec<f32>{
+        candidates.iter().map(|c|-get_parameters(c).iter().map(|p|(p-1.)*(p-1.)).sum::<f32>()).collect()
+    }
+
+    #[test]
+    fn same_seed_asks_for_same_candidates(){
+        let (mut a, mut b) = (es(7), es(7));
+        for _ in 0..3{
+            let candidates = a.ask();
+            assert_eq!(candidates, b.ask());
+            assert_ne!(candidates, es(8).ask());
+            let f = fitness(&candidates);
+            assert_eq!(a.tell(&f), b.tell(&f));
+        }
+        assert_eq!(a.mean(), b.mean());
+    }
+
+    #[test]
+    fn checkpoint_resumes_the_run(){
+        let mut original = es(3);
+        for _ in 0..2{
+            let f = fitness(&original.ask());
+            original.tell(&f);
+        }
+        let mut resumed = EvolutionStrategy::from_bytes(&original.to_bytes()).unwrap();
+        assert_eq!(resumed.generation(), 2);
+        assert_eq!(resumed.best_fitness(), original.best_fitness());
+        assert_eq!(resumed.ask(), original.ask());
+        let f = fitness(&original.ask());
+        assert_eq!(resumed.tell(&f), original.tell(&f));
+        assert_eq!(resumed.mean(), original.mean());
+        assert_eq!(resumed.best_entity(), original.best_entity());
+    }
+
+    #[test]
+    fn parameters_round_trip(){
+        let mut entity = AnnEntity::new(AnnConfig::small(), 0, 1., 1.);
+        let params:Vec<f32> = (0..parameter_count(entity.config())).map(|i|i as f32).collect();
+        set_parameters(&mut entity, &params);
+        assert_eq!(get_parameters(&entity), params);
+    }
+}
@@ -0,0 +1,337 @@
+use crate::pipelines::lifecycle_record::{LifecycleRecord, NO_ENTITY_ID};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+
+/**Everything that is known about a single ANN entity that ever lived*/
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct LineageRecord{
+    pub id:u32,
+    /**None for founders, that is entities generated together with the world or injected from a brain file*/
+    pub parent_id:Option<u32>,
+    pub birth_tick:u32,
+    /**None as long as the entity is alive*/
+    pub death_tick:Option<u32>,
+    /**Id of the founder of the lineage (the oldest known ancestor)*/
+    pub lineage:u32,
+    /**Number of ancestors between this entity and the founder of its lineage*/
+    pub generation:u32,
+}
+
+impl LineageRecord{
+    pub fn is_alive(&self)->bool{
+        self.death_tick.is_none()
+    }
+}
+
+/**Summary of all descendants of a single founder*/
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct LineageStats{
+    pub lineage:u32,
+    pub members:usize,
+    pub alive:usize,
+    /**Deepest generation reached by the lineage. Founder is generation 0*/
+    pub generations:u32,
+    pub first_birth_tick:u32,
+    pub last_birth_tick:u32,
+    /**Tick of the last death, if no member is alive anymore*/
+    pub extinct_at_tick:Option<u32>,
+    /**Average number of ticks between birth and death of members that already died*/
+    pub mean_lifespan:Option<f32>,
+}
+
+/**Family tree of all ANN entities, assembled from lifecycle records read back from the GPU
+(see Foundations::drain_lifecycle_events). Records must be fed in the order in which they were appended.*/
+#[derive(Clone, Default, Debug)]
+pub struct Genealogy{
+    records:Vec<LineageRecord>,
+    index:HashMap<u32,usize>,
+    lost_events:u64,
+    unknown_deaths:u64,
+}
+
+impl Genealogy{
+    pub fn new()->Self{
+        Self::default()
+    }
+    pub fn records(&self)->&[LineageRecord]{
+        &self.records
+    }
+    pub fn get(&self, id:u32)->Option<&LineageRecord>{
+        self.index.get(&id).map(|&i|&self.records[i])
+    }
+    /**Number of lifecycle records that did not fit into the GPU buffer. Entities born in a lost record
+    appear as founders of new lineages once their children are recorded*/
+    pub fn lost_events(&self)->u64{
+        self.lost_events
+    }
+    /**Number of deaths of entities whose birth was never recorded*/
+    pub fn unknown_deaths(&self)->u64{
+        self.unknown_deaths
+    }
+    pub fn add_lost_events(&mut self, lost:u64){
+        self.lost_events += lost
+    }
+    pub fn alive(&self)->usize{
+        self.records.iter().filter(|r|r.is_alive()).count()
+    }
+    pub fn record(&mut self, event:&LifecycleRecord){
+        if event.is_birth(){
+            let parent = Some(event.parent_id()).filter(|&p|p!=NO_ENTITY_ID);
+            let (lineage, generation) = match parent.and_then(|p|self.get(p)){
+                Some(p) => (p.lineage, p.generation + 1),
+                None => (event.entity_id(), 0)
+            };
+            self.index.insert(event.entity_id(), self.records.len());
+            self.records.push(LineageRecord{
+                id: event.entity_id(),
+                parent_id: parent,
+                birth_tick: event.tick(),
+                death_tick: None,
+                lineage,
+                generation,
+            });
+        }else{
+            match self.index.get(&event.entity_id()){
+                Some(&i) => self.records[i].death_tick = Some(event.tick()),
+                None => self.unknown_deaths += 1
+            }
+        }
+    }
+    pub fn record_all(&mut self, events:&[LifecycleRecord]){
+        for e in events{
+            self.record(e)
+        }
+    }
+    /**One row per entity: id,parent_id,birth_tick,death_tick,lineage,generation. Missing values are left empty*/
+    pub fn to_csv(&self)->String{
+        let mut out = String::from("id,parent_id,birth_tick,death_tick,lineage,generation\n");
+        let opt = |o:Option<u32>|o.map(|v|v.to_string()).unwrap_or_default();
+        for r in &self.records{
+            writeln!(out, "{},{},{},{},{},{}", r.id, opt(r.parent_id), r.birth_tick, opt(r.death_tick), r.lineage, r.generation).unwrap();
+        }
+        out
+    }
+    fn children(&self)->(Vec<usize>,Vec<Vec<usize>>){
+        let mut roots = Vec::new();
+        let mut children = vec![Vec::new();self.records.len()];
+        for (i,r) in self.records.iter().enumerate(){
+            match r.parent_id.and_then(|p|self.index.get(&p)){
+                Some(&p) => children[p].push(i),
+                None => roots.push(i)
+            }
+        }
+        (roots,children)
+    }
+    /**Writes the subtree without recursion, because lineages can easily be thousands of generations deep.
+    Every node is labelled by entity id and its branch length is the number of ticks between birth of the parent and birth of the entity.*/
+    fn write_newick_subtree(&self, out:&mut String, root:usize, children:&[Vec<usize>]){
+        let mut stack = vec![(root,0)];
+        while let Some((node,next_child)) = stack.pop(){
+            let kids = &children[node];
+            if next_child < kids.len(){
+                out.push(if next_child==0{'('}else{','});
+                stack.push((node,next_child+1));
+                stack.push((kids[next_child],0));
+            }else{
+                if !kids.is_empty(){
+                    out.push(')');
+                }
+                let r = &self.records[node];
+                let parent_birth = r.parent_id.and_then(|p|self.get(p)).map(|p|p.birth_tick).unwrap_or(0);
+                write!(out, "{}:{}", r.id, r.birth_tick.saturating_sub(parent_birth)).unwrap();
+            }
+        }
+    }
+    /**All lineages as a single Newick tree. If there is more than one founder, the lineages are joined under an unnamed root*/
+    pub fn to_newick(&self)->String{
+        let (roots,children) = self.children();
+        let mut out = String::new();
+        if roots.len() > 1{
+            out.push('(');
+        }
+        for (i,&root) in roots.iter().enumerate(){
+            if i>0{out.push(',')}
+            self.write_newick_subtree(&mut out, root, &children);
+        }
+        if roots.len() > 1{
+            out.push(')');
+        }
+        out.push_str(";\n");
+        out
+    }
+    /**Statistics of every lineage, in the order in which founders were born*/
+    pub fn lineage_stats(&self)->Vec<LineageStats>{
+        let mut stats:Vec<LineageStats> = Vec::new();
+        let mut lineage_idx = HashMap::new();
+        let mut lifespan_sums = Vec::new();
+        for r in &self.records{
+            let i = *lineage_idx.entry(r.lineage).or_insert_with(||{
+                stats.push(LineageStats{
+                    lineage: r.lineage,
+                    members: 0,
+                    alive: 0,
+                    generations: 0,
+                    first_birth_tick: r.birth_tick,
+                    last_birth_tick: r.birth_tick,
+                    extinct_at_tick: None,
+                    mean_lifespan: None,
+                });
+                lifespan_sums.push(0u64);
+                stats.len()-1
+            });
+            let s = &mut stats[i];
+            s.members += 1;
+            s.generations = s.generations.max(r.generation);
+            s.first_birth_tick = s.first_birth_tick.min(r.birth_tick);
+            s.last_birth_tick = s.last_birth_tick.max(r.birth_tick);
+            match r.death_tick{
+                None => s.alive += 1,
+                Some(death) => {
+                    s.extinct_at_tick = Some(s.extinct_at_tick.unwrap_or(0).max(death));
+                    lifespan_sums[i] += death.saturating_sub(r.birth_tick) as u64;
+                }
+            }
+        }
+        for (s,&sum) in stats.iter_mut().zip(lifespan_sums.iter()){
+            let dead = s.members - s.alive;
+            if dead > 0{
+                s.mean_lifespan = Some(sum as f32 / dead as f32);
+            }
+            if s.alive > 0{
+                s.extinct_at_tick = None;
+            }
+        }
+        stats
+    }
+    /**One row per lineage: lineage,members,alive,generations,first_birth_tick,last_birth_tick,extinct_at_tick,mean_lifespan*/
+    pub fn lineage_stats_csv(&self)->String{
+        let mut out = String::from("lineage,members,alive,generations,first_birth_tick,last_birth_tick,extinct_at_tick,mean_lifespan\n");
+        for s in self.lineage_stats(){
+            writeln!(out, "{},{},{},{},{},{},{},{}", s.lineage, s.members, s.alive, s.generations, s.first_birth_tick, s.last_birth_tick,
+                     s.extinct_at_tick.map(|t|t.to_string()).unwrap_or_default(),
+                     s.mean_lifespan.map(|l|l.to_string()).unwrap_or_default()).unwrap();
+        }
+        out
+    }
+    pub fn save_csv(&self, path:impl AsRef<Path>)->Result<(),failure::Error>{
+        Ok(std::fs::write(path, self.to_csv())?)
+    }
+    pub fn save_newick(&self, path:impl AsRef<Path>)->Result<(),failure::Error>{
+        Ok(std::fs::write(path, self.to_newick())?)
+    }
+    pub fn save_lineage_stats_csv(&self, path:impl AsRef<Path>)->Result<(),failure::Error>{
+        Ok(std::fs::write(path, self.lineage_stats_csv())?)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    /**Founders 1 and 2, children 3 and 4 of founder 1 and child 5 of founder 2. Entities 2 and 3 are dead*/
+    fn two_founders()->Genealogy{
+        let mut g = Genealogy::new();
+        g.record_all(&[
+            LifecycleRecord::birth(1, NO_ENTITY_ID, 0),
+            LifecycleRecord::birth(2, NO_ENTITY_ID, 5),
+            LifecycleRecord::birth(3, 1, 10),
+            LifecycleRecord::birth(4, 1, 12),
+            LifecycleRecord::birth(5, 2, 20),
+            LifecycleRecord::death(3, 30),
+            LifecycleRecord::death(2, 40),
+        ]);
+        g
+    }
+
+    #[test]
+    fn csv_has_one_row_per_entity(){
+        let g = two_founders();
+        assert_eq!(g.to_csv(), "id,parent_id,birth_tick,death_tick,lineage,generation\n\
+                                1,,0,,1,0\n\
+                                2,,5,40,2,0\n\
+                                3,1,10,30,1,1\n\
+                                4,1,12,,1,1\n\
+                                5,2,20,,2,1\n");
+        assert_eq!(g.alive(), 3);
+    }
+
+    #[test]
+    fn founders_are_joined_under_an_unnamed_root(){
+        let g = two_founders();
+        assert_eq!(g.to_newick(), "((3:10,4:12)1:0,(5:15)2:5);\n");
+    }
+
+    #[test]
+    fn lineage_stats_summarize_every_founder(){
+        let mut g = two_founders();
+        let stats = g.lineage_stats();
+        assert_eq!(stats, vec![
+            LineageStats{lineage:1, members:3, alive:2, generations:1, first_birth_tick:0, last_birth_tick:12, extinct_at_tick:None, mean_lifespan:Some(20.)},
+            LineageStats{lineage:2, members:2, alive:1, generations:1, first_birth_tick:5, last_birth_tick:20, extinct_at_tick:None, mean_lifespan:Some(35.)},
+        ]);
+        g.record(&LifecycleRecord::death(5, 50));
+        let stats = g.lineage_stats();
+        assert_eq!((stats[1].alive, stats[1].extinct_at_tick, stats[1].mean_lifespan), (0, Some(50), Some(32.5)));
+        assert_eq!(g.lineage_stats_csv(), "lineage,members,alive,generations,first_birth_tick,last_birth_tick,extinct_at_tick,mean_lifespan\n\
+                                           1,3,2,1,0,12,,20\n\
+                                           2,2,0,1,5,20,50,32.5\n");
+    }
+
+    #[test]
+    fn children_of_lost_records_found_new_lineages(){
+        let mut g = Genealogy::new();
+        // the birth of entity 99 did not fit into the buffer
+        g.add_lost_events(1);
+        g.record_all(&[
+            LifecycleRecord::birth(7, 99, 100),
+            LifecycleRecord::birth(8, 7, 110),
+            LifecycleRecord::death(99, 120),
+        ]);
+        assert_eq!(g.get(7), Some(&LineageRecord{id:7, parent_id:Some(99), birth_tick:100, death_tick:None, lineage:7, generation:0}));
+        assert_eq!((g.get(8).unwrap().lineage, g.get(8).unwrap().generation), (7, 1));
+        assert_eq!((g.lost_events(), g.unknown_deaths()), (1, 1));
+        // the unknown parent is kept in the CSV, while the tree starts at the orphan
+        assert!(g.to_csv().contains("\n7,99,100,,7,0\n"));
+        assert_eq!(g.to_newick(), "(8:10)7:100;\n");
+        assert_eq!(g.lineage_stats().len(), 1);
+    }
+
+    #[test]
+    fn deep_chains_do_not_overflow_the_stack(){
+        let depth = 100_000;
+        let mut g = Genealogy::new();
+        g.record(&LifecycleRecord::birth(0, NO_ENTITY_ID, 0));
+        for id in 1..depth{
+            g.record(&LifecycleRecord::birth(id, id - 1, id * 2));
+        }
+        let newick = g.to_newick();
+        let expected_start = format!("{}{}:2)", "(".repeat(depth as usize - 1), depth - 1);
+        assert!(newick.starts_with(&expected_start));
+        assert!(newick.ends_with(")1:2)0:0;\n"));
+        assert_eq!(newick.matches('(').count(), newick.matches(')').count());
+        assert_eq!(g.lineage_stats()[0].generations, depth - 1);
+    }
+
+    #[test]
+    fn newick_labels_and_branch_lengths_need_no_quoting(){
+        let mut g = Genealogy::new();
+        g.record_all(&[
+            LifecycleRecord::birth(u32::MAX - 1, NO_ENTITY_ID, 50),
+            // born before its parent, for example after the tick counter wrapped around
+            LifecycleRecord::birth(3, u32::MAX - 1, 10),
+        ]);
+        let newick = g.to_newick();
+        assert_eq!(newick, format!("(3:0){}:50;\n", u32::MAX - 1));
+        // labels and lengths are plain unsigned integers, so none of Newick's reserved characters can appear in them
+        let reserved = |c:char| "()[]':;, \t".contains(c);
+        for node in newick.trim_end_matches(";\n").split(|c|"(),".contains(c)).filter(|n|!n.is_empty()){
+            let (label, length) = {
+                let mut parts = node.split(':');
+                (parts.next().unwrap(), parts.next().unwrap())
+            };
+            assert!(!label.contains(reserved) && label.parse::<u32>().is_ok(), "{}", node);
+            assert!(!length.contains(reserved) && length.parse::<u32>().is_ok(), "{}", node);
+        }
+    }
+}
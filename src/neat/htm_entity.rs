@@ -1,12 +1,556 @@
-pub const ENTITY_MAX_LIDAR_COUNT: usize = 32;
+use crate::neat::ann_entity::{AnnLidar, rotate_yaw_and_pitch, ann_impulses_of_movement_muscles, ann_impulses_of_rotation_muscles};
+use crate::neat::util::RandRange;
+use crate::pipelines::bone::Bone;
+use crate::neat::num::Num;
+
+pub const ENTITY_LIDAR_COUNT: usize = 32;
+/**Mirrors BLOCKS_LEN from constants.comp. Every lidar encodes the id of the block it hit as one-hot vector of this length*/
+pub const HTM_BLOCKS_LEN: usize = 53;
+pub const HTM_INPUT_SIZE: usize = ENTITY_LIDAR_COUNT * HTM_BLOCKS_LEN;
+pub const HTM_MINICOLUMNS: usize = 64;
+pub const HTM_FEEDFORWARD_CONNECTIONS_PER_MINICOLUMN: usize = 32;
+/**Feedforward connections are grouped by minicolumn. Connection i belongs to minicolumn i/HTM_FEEDFORWARD_CONNECTIONS_PER_MINICOLUMN*/
+pub const HTM_TOTAL_FEEDFORWARD_CONNECTIONS: usize = HTM_MINICOLUMNS * HTM_FEEDFORWARD_CONNECTIONS_PER_MINICOLUMN;
+pub const HTM_MINICOLUMN_LAYERS: u32 = 6;
+/**Value of active_layer of a minicolumn that is active but none of its cells was predicted*/
+pub const HTM_MINICOLUMN_BURSTING: u32 = HTM_MINICOLUMN_LAYERS;
+/**Value of active_layer of a minicolumn that is not active*/
+pub const HTM_MINICOLUMN_INACTIVE: u32 = HTM_MINICOLUMN_LAYERS + 1;
+pub const HTM_PERMANENCE_THRESHOLD: f32 = 0.1;
+pub const HTM_LIMIT_OF_MINICOLUMNS_TO_ACTIVATE: usize = HTM_MINICOLUMNS / 8;
+pub const HTM_PERMAMENCE_DECREMENT: f32 = -0.04;
+pub const HTM_PERMAMENCE_INCREMENT: f32 = 0.08;
+pub const HTM_MAX_SEGMENTS: u32 = 4;
+pub const HTM_CONTEXT_CONNECTIONS_PER_MINICOLUMN: usize = 16;
+/**Number of connected synapses with active presynaptic minicolumn, that are needed to put a segment into predictive state*/
+pub const HTM_SEGMENT_ACTIVATION_THRESHOLD: u32 = 2;
+/**Permanence given to context connections that get rewired to a new presynaptic minicolumn*/
+pub const HTM_INITIAL_CONTEXT_PERMANENCE: f32 = 0.2;
+/**HTM entities use the same kind of muscles as ANN entities, but their number does not depend on AnnConfig*/
+pub const HTM_OUTPUT_MOVEMENT_MUSCLES_SIZE: usize = 20;
+pub const HTM_OUTPUT_ROTATION_MUSCLES_SIZE: usize = 8;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[repr(C, packed)]
+pub struct HtmFeedforwardConnection {
+    input_id: u32,
+    permanence: f32,
+}
+impl HtmFeedforwardConnection {
+    pub const fn new(input_id: u32, permanence: f32) -> Self {
+        Self { input_id, permanence }
+    }
+    pub const fn zero() -> Self {
+        Self::new(0, 0.)
+    }
+    pub fn input_id(&self) -> u32 {
+        self.input_id
+    }
+    pub fn permanence(&self) -> f32 {
+        self.permanence
+    }
+    pub fn is_connected(&self) -> bool {
+        self.permanence > HTM_PERMANENCE_THRESHOLD
+    }
+    fn learn(&mut self, is_input_active: bool) {
+        let change = if is_input_active { HTM_PERMAMENCE_INCREMENT } else { HTM_PERMAMENCE_DECREMENT };
+        self.permanence = (self.permanence + change).max(0.).min(1.);
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[repr(C, packed)]
+pub struct HtmContextConnection {
+    minicolumn_id: u32,
+    permanence: f32,
+    segment_id: u32,
+}
+impl HtmContextConnection {
+    pub const fn new(minicolumn_id: u32, permanence: f32, segment_id: u32) -> Self {
+        Self { minicolumn_id, permanence, segment_id }
+    }
+    pub const fn zero() -> Self {
+        Self::new(0, 0., 0)
+    }
+    /**Presynaptic minicolumn. The connection is active whenever that minicolumn was active in previous step*/
+    pub fn minicolumn_id(&self) -> u32 {
+        self.minicolumn_id
+    }
+    pub fn permanence(&self) -> f32 {
+        self.permanence
+    }
+    /**Encodes both the postsynaptic cell and its segment as layer*HTM_MAX_SEGMENTS+segment*/
+    pub fn segment_id(&self) -> u32 {
+        self.segment_id
+    }
+    pub fn layer(&self) -> u32 {
+        self.segment_id / HTM_MAX_SEGMENTS
+    }
+    pub fn is_connected(&self) -> bool {
+        self.permanence > HTM_PERMANENCE_THRESHOLD
+    }
+    fn learn(&mut self, was_presynaptic_active: bool) {
+        let change = if was_presynaptic_active { HTM_PERMAMENCE_INCREMENT } else { HTM_PERMAMENCE_DECREMENT };
+        self.permanence = (self.permanence + change).max(0.).min(1.);
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[repr(C, packed)]
+pub struct HtmMinicolumn {
+    active_layer: u32,
+    context_connections: [HtmContextConnection; HTM_CONTEXT_CONNECTIONS_PER_MINICOLUMN],
+}
+impl HtmMinicolumn {
+    pub const fn new(context_connections: [HtmContextConnection; HTM_CONTEXT_CONNECTIONS_PER_MINICOLUMN]) -> Self {
+        Self { active_layer: HTM_MINICOLUMN_INACTIVE, context_connections }
+    }
+    pub const fn zero() -> Self {
+        Self::new([HtmContextConnection::zero(); HTM_CONTEXT_CONNECTIONS_PER_MINICOLUMN])
+    }
+    /**There can be only one active cell in each minicolumn, except when the minicolumn is bursting.
+    Bursting is represented with HTM_MINICOLUMN_BURSTING and inactive minicolumn with HTM_MINICOLUMN_INACTIVE*/
+    pub fn active_layer(&self) -> u32 {
+        self.active_layer
+    }
+    pub fn is_active(&self) -> bool {
+        self.active_layer != HTM_MINICOLUMN_INACTIVE
+    }
+    pub fn is_bursting(&self) -> bool {
+        self.active_layer == HTM_MINICOLUMN_BURSTING
+    }
+    pub fn context_connections(&self) -> &[HtmContextConnection; HTM_CONTEXT_CONNECTIONS_PER_MINICOLUMN] {
+        &self.context_connections
+    }
+    pub fn context_connections_mut(&mut self) -> &mut [HtmContextConnection; HTM_CONTEXT_CONNECTIONS_PER_MINICOLUMN] {
+        &mut self.context_connections
+    }
+    /**Number of connected synapses of every segment whose presynaptic minicolumn is active*/
+    fn segment_activity(&self, previously_active: &[bool; HTM_MINICOLUMNS], connected_only: bool) -> [u32; (HTM_MINICOLUMN_LAYERS * HTM_MAX_SEGMENTS) as usize] {
+        let mut activity = [0; (HTM_MINICOLUMN_LAYERS * HTM_MAX_SEGMENTS) as usize];
+        for c in self.context_connections.iter() {
+            if previously_active[c.minicolumn_id() as usize] && (!connected_only || c.is_connected()) {
+                activity[c.segment_id() as usize] += 1;
+            }
+        }
+        activity
+    }
+    /**Returns the first layer that has a segment in predictive state*/
+    fn predicted_layer(&self, previously_active: &[bool; HTM_MINICOLUMNS]) -> Option<u32> {
+        self.segment_activity(previously_active, true).iter()
+            .position(|&a| a >= HTM_SEGMENT_ACTIVATION_THRESHOLD)
+            .map(|segment| segment as u32 / HTM_MAX_SEGMENTS)
+    }
+    /**Reinforces all connections of the segment, depending on whether their presynaptic minicolumns were active*/
+    fn learn_segment(&mut self, segment_id: u32, previously_active: &[bool; HTM_MINICOLUMNS]) {
+        for c in self.context_connections.iter_mut() {
+            if c.segment_id() == segment_id {
+                let was_active = previously_active[c.minicolumn_id() as usize];
+                c.learn(was_active)
+            }
+        }
+    }
+    /**Picks the segment that best matches previous activity (counting also disconnected synapses) and learns on it.
+    If no segment matches at all, then the weakest connection gets rewired to some previously active minicolumn.
+    Returns the layer that won.*/
+    fn learn_bursting(&mut self, previously_active: &[bool; HTM_MINICOLUMNS], rand_seed: u32) -> u32 {
+        let activity = self.segment_activity(previously_active, false);
+        let (best_segment, &best_activity) = activity.iter().enumerate().max_by_key(|&(i, &a)| (a, std::cmp::Reverse(i))).unwrap();
+        if best_activity > 0 {
+            self.learn_segment(best_segment as u32, previously_active);
+            best_segment as u32 / HTM_MAX_SEGMENTS
+        } else {
+            let active_count = previously_active.iter().filter(|&&a| a).count() as u32;
+            let layer = rand_seed % HTM_MINICOLUMN_LAYERS;
+            if active_count > 0 {
+                let nth = (rand_seed / HTM_MINICOLUMN_LAYERS) % active_count;
+                let presynaptic = previously_active.iter().enumerate().filter(|&(_, &a)| a).nth(nth as usize).unwrap().0 as u32;
+                let weakest = (0..HTM_CONTEXT_CONNECTIONS_PER_MINICOLUMN)
+                    .min_by(|&a, &b| self.context_connections[a].permanence().partial_cmp(&self.context_connections[b].permanence()).unwrap())
+                    .unwrap();
+                self.context_connections[weakest] = HtmContextConnection::new(presynaptic, HTM_INITIAL_CONTEXT_PERMANENCE, layer * HTM_MAX_SEGMENTS);
+            }
+            layer
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Debug)]
 #[repr(C, packed)]
 pub struct HtmEntity {
-    lidars:[u32;ENTITY_MAX_LIDAR_COUNT],
-    lidars_used:u32,
-    bone_idx:u32,
-    main:u32,
-    energy:f32,
-    speed:f32,
-    max_speed:f32,
+    lidars: [AnnLidar; ENTITY_LIDAR_COUNT],
+    htm_feedforward_connections: [HtmFeedforwardConnection; HTM_TOTAL_FEEDFORWARD_CONNECTIONS],
+    htm_minicolumns: [HtmMinicolumn; HTM_MINICOLUMNS],
+    bone_idx: u32,
+    main: u32,
+    energy: f32,
+    speed: f32,
+    max_speed: f32,
+    dummy: [u32; 3],// std430 rounds up the size of HtmEntity to a multiple of 16 bytes, because lidars are vec4
+}
+
+/**Result of a single step of HtmEntity. Equivalent of what update_htm_entities.comp
+computes before writing into the bone*/
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct HtmEntityStep {
+    pub active_minicolumns: [bool; HTM_MINICOLUMNS],
+    /**Sum of rotation muscle impulses of active minicolumns. It is not yet clamped*/
+    pub rotation_change: glm::Vec2,
+    /**Sum of movement muscle impulses of active minicolumns. It is not yet multiplied by speed*/
+    pub directional_impulse: glm::Vec3,
+}
+
+impl HtmEntity {
+    pub fn new(bone_idx: u32, speed: f32, energy: f32) -> Self {
+        Self {
+            lidars: [AnnLidar::new(glm::vec3(1., 0., 0.)); ENTITY_LIDAR_COUNT],
+            htm_feedforward_connections: [HtmFeedforwardConnection::zero(); HTM_TOTAL_FEEDFORWARD_CONNECTIONS],
+            htm_minicolumns: [HtmMinicolumn::zero(); HTM_MINICOLUMNS],
+            bone_idx,
+            main: 0,
+            energy,
+            speed,
+            max_speed: speed,
+            dummy: [0; 3],
+        }
+    }
+    /**Random lidar directions, random potential pool of every minicolumn with permanences scattered around the threshold
+    and random context connections. Mirrors generate_world_agents.comp*/
+    pub fn new_random(bone_idx: u32, speed: f32, energy: f32) -> Self {
+        let mut e = Self::new(bone_idx, speed, energy);
+        for l in e.lidars.iter_mut() {
+            *l = AnnLidar::new(f32::random_vec3() * 2. - glm::vec3(1., 1., 1.));
+        }
+        for c in e.htm_feedforward_connections.iter_mut() {
+            *c = HtmFeedforwardConnection::new(HTM_INPUT_SIZE.random() as u32, f32::random() * 2. * HTM_PERMANENCE_THRESHOLD);
+        }
+        for m in e.htm_minicolumns.iter_mut() {
+            for c in m.context_connections.iter_mut() {
+                *c = HtmContextConnection::new(HTM_MINICOLUMNS.random() as u32,
+                                               f32::random() * 2. * HTM_PERMANENCE_THRESHOLD,
+                                               ((HTM_MINICOLUMN_LAYERS * HTM_MAX_SEGMENTS) as usize).random() as u32);
+            }
+        }
+        e
+    }
+    pub fn lidars(&self) -> &[AnnLidar; ENTITY_LIDAR_COUNT] {
+        &self.lidars
+    }
+    pub fn lidars_mut(&mut self) -> &mut [AnnLidar; ENTITY_LIDAR_COUNT] {
+        &mut self.lidars
+    }
+    pub fn feedforward_connections(&self) -> &[HtmFeedforwardConnection; HTM_TOTAL_FEEDFORWARD_CONNECTIONS] {
+        &self.htm_feedforward_connections
+    }
+    pub fn feedforward_connections_mut(&mut self) -> &mut [HtmFeedforwardConnection; HTM_TOTAL_FEEDFORWARD_CONNECTIONS] {
+        &mut self.htm_feedforward_connections
+    }
+    /**Feedforward connections that make up the potential pool of given minicolumn*/
+    pub fn minicolumn_feedforward_connections(&self, minicolumn: usize) -> &[HtmFeedforwardConnection] {
+        let offset = minicolumn * HTM_FEEDFORWARD_CONNECTIONS_PER_MINICOLUMN;
+        &self.htm_feedforward_connections[offset..offset + HTM_FEEDFORWARD_CONNECTIONS_PER_MINICOLUMN]
+    }
+    pub fn minicolumns(&self) -> &[HtmMinicolumn; HTM_MINICOLUMNS] {
+        &self.htm_minicolumns
+    }
+    pub fn minicolumns_mut(&mut self) -> &mut [HtmMinicolumn; HTM_MINICOLUMNS] {
+        &mut self.htm_minicolumns
+    }
+    pub fn bone_idx(&self) -> u32 {
+        self.bone_idx
+    }
+    pub fn set_bone_idx(&mut self, bone_idx: u32) {
+        self.bone_idx = bone_idx
+    }
+    pub fn energy(&self) -> f32 {
+        self.energy
+    }
+    pub fn set_energy(&mut self, energy: f32) {
+        self.energy = energy
+    }
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed
+    }
+    pub fn max_speed(&self) -> f32 {
+        self.max_speed
+    }
+    /**Lidar j that hit block b activates input j*HTM_BLOCKS_LEN+b*/
+    pub fn is_input_active(hit_blocks: &[u32; ENTITY_LIDAR_COUNT], input_id: u32) -> bool {
+        let lidar = input_id as usize / HTM_BLOCKS_LEN;
+        hit_blocks[lidar] == input_id % HTM_BLOCKS_LEN as u32
+    }
+    /**Number of connected feedforward synapses of every minicolumn whose input is active*/
+    pub fn overlap(&self, hit_blocks: &[u32; ENTITY_LIDAR_COUNT]) -> [u32; HTM_MINICOLUMNS] {
+        let mut overlap = [0; HTM_MINICOLUMNS];
+        for (m, o) in overlap.iter_mut().enumerate() {
+            *o = self.minicolumn_feedforward_connections(m).iter()
+                .filter(|c| c.is_connected() && Self::is_input_active(hit_blocks, c.input_id()))
+                .count() as u32;
+        }
+        overlap
+    }
+    /**Activates at most HTM_LIMIT_OF_MINICOLUMNS_TO_ACTIVATE minicolumns with highest (and non-zero) overlap.
+    Ties are broken in favour of lower minicolumn index. If learning is enabled, then the potential pools of
+    active minicolumns are reinforced towards the current input.*/
+    pub fn spatial_pooler(&mut self, hit_blocks: &[u32; ENTITY_LIDAR_COUNT], learn: bool) -> [bool; HTM_MINICOLUMNS] {
+        let overlap = self.overlap(hit_blocks);
+        let mut order: Vec<usize> = (0..HTM_MINICOLUMNS).collect();
+        order.sort_by_key(|&m| std::cmp::Reverse(overlap[m]));// stable sort keeps ties ordered by index
+        let mut active = [false; HTM_MINICOLUMNS];
+        for &m in order.iter().take(HTM_LIMIT_OF_MINICOLUMNS_TO_ACTIVATE).filter(|&&m| overlap[m] > 0) {
+            active[m] = true;
+        }
+        if learn {
+            for m in (0..HTM_MINICOLUMNS).filter(|&m| active[m]) {
+                let offset = m * HTM_FEEDFORWARD_CONNECTIONS_PER_MINICOLUMN;
+                for c in self.htm_feedforward_connections[offset..offset + HTM_FEEDFORWARD_CONNECTIONS_PER_MINICOLUMN].iter_mut() {
+                    let is_input_active = Self::is_input_active(hit_blocks, c.input_id());
+                    c.learn(is_input_active)
+                }
+            }
+        }
+        active
+    }
+    /**Predictions are made from the minicolumns that were active in previous step. Every active minicolumn
+    activates its first predicted layer, or bursts if nothing was predicted. If learning is enabled, the segment
+    that made the correct prediction is reinforced, while bursting minicolumns learn on the best matching segment.*/
+    pub fn temporal_memory(&mut self, active: &[bool; HTM_MINICOLUMNS], learn: bool, rand_seed: u32) {
+        let mut previously_active = [false; HTM_MINICOLUMNS];
+        for (p, m) in previously_active.iter_mut().zip(self.htm_minicolumns.iter()) {
+            *p = m.is_active();
+        }
+        for (i, m) in self.htm_minicolumns.iter_mut().enumerate() {
+            m.active_layer = if !active[i] {
+                HTM_MINICOLUMN_INACTIVE
+            } else if let Some(layer) = m.predicted_layer(&previously_active) {
+                if learn {
+                    let segment = m.segment_activity(&previously_active, true).iter()
+                        .position(|&a| a >= HTM_SEGMENT_ACTIVATION_THRESHOLD).unwrap() as u32;
+                    m.learn_segment(segment, &previously_active);
+                }
+                layer
+            } else {
+                if learn {
+                    m.learn_bursting(&previously_active, rand_seed.wrapping_add(i as u32 * 0x3477));
+                }
+                HTM_MINICOLUMN_BURSTING
+            };
+        }
+    }
+    /**CPU reference implementation of update_htm_entities.comp. Takes the id of block hit by every lidar
+    (BLOCK_ID_AIR if nothing was hit). Minicolumn m drives movement muscle m%HTM_OUTPUT_MOVEMENT_MUSCLES_SIZE and
+    rotation muscle m%HTM_OUTPUT_ROTATION_MUSCLES_SIZE, with the same impulses as ANN entities use.*/
+    pub fn step(&mut self, hit_blocks: &[u32; ENTITY_LIDAR_COUNT], rand_seed: u32) -> HtmEntityStep {
+        let active_minicolumns = self.spatial_pooler(hit_blocks, true);
+        self.temporal_memory(&active_minicolumns, true, rand_seed);
+        let movement = ann_impulses_of_movement_muscles(HTM_OUTPUT_MOVEMENT_MUSCLES_SIZE);
+        let rotation = ann_impulses_of_rotation_muscles(HTM_OUTPUT_ROTATION_MUSCLES_SIZE);
+        let mut directional_impulse = glm::vec3(0., 0., 0.);
+        let mut rotation_change = glm::vec2(0., 0.);
+        for m in (0..HTM_MINICOLUMNS).filter(|&m| active_minicolumns[m]) {
+            directional_impulse += movement[m % HTM_OUTPUT_MOVEMENT_MUSCLES_SIZE];
+            rotation_change += rotation[m % HTM_OUTPUT_ROTATION_MUSCLES_SIZE];
+        }
+        let scale = 1. / HTM_LIMIT_OF_MINICOLUMNS_TO_ACTIVATE as f32;
+        HtmEntityStep {
+            active_minicolumns,
+            rotation_change: rotation_change * scale,
+            directional_impulse: directional_impulse * scale,
+        }
+    }
+    /**Writes results of step into the bone, exactly as update_htm_entities.comp does. Movement impulse
+    is only applied if the entity is standing on the ground*/
+    pub fn apply_step(&self, step: &HtmEntityStep, bone: &mut Bone, is_standing_on_the_ground: bool) {
+        bone.set_yaw_and_pitch(rotate_yaw_and_pitch(bone.yaw_and_pitch(), step.rotation_change));
+        if is_standing_on_the_ground {
+            bone.add_impulse(step.directional_impulse * self.speed());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /**Lidar 1 hit block 7 and all others hit block 0*/
+    fn hit_blocks() -> [u32; ENTITY_LIDAR_COUNT] {
+        let mut hit_blocks = [0; ENTITY_LIDAR_COUNT];
+        hit_blocks[1] = 7;
+        hit_blocks
+    }
+
+    /**Input that is active for hit_blocks()*/
+    fn active_input(lidar: usize) -> u32 {
+        (lidar * HTM_BLOCKS_LEN) as u32 + hit_blocks()[lidar]
+    }
+
+    /**Input that is not active for hit_blocks()*/
+    fn inactive_input(lidar: usize) -> u32 {
+        active_input(lidar) + 1
+    }
+
+    fn connect(e: &mut HtmEntity, minicolumn: usize, k: usize, input_id: u32, permanence: f32) {
+        e.feedforward_connections_mut()[minicolumn * HTM_FEEDFORWARD_CONNECTIONS_PER_MINICOLUMN + k] = HtmFeedforwardConnection::new(input_id, permanence);
+    }
+
+    fn permanence(e: &HtmEntity, minicolumn: usize, k: usize) -> f32 {
+        e.minicolumn_feedforward_connections(minicolumn)[k].permanence()
+    }
+
+    /**Minicolumn with no context connections that could ever become active*/
+    fn isolated_minicolumn() -> HtmMinicolumn {
+        HtmMinicolumn::new([HtmContextConnection::new(HTM_MINICOLUMNS as u32 - 1, 0., 0); HTM_CONTEXT_CONNECTIONS_PER_MINICOLUMN])
+    }
+
+    fn only(minicolumns: &[usize]) -> [bool; HTM_MINICOLUMNS] {
+        let mut active = [false; HTM_MINICOLUMNS];
+        for &m in minicolumns {
+            active[m] = true;
+        }
+        active
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn spatial_pooler_activates_minicolumns_with_highest_overlap() {
+        let mut e = HtmEntity::new(0, 1., 1.);
+        // minicolumns 3 and 9 have overlap 3, minicolumn 5 has overlap 2 and the rest of 0..10 has overlap 1
+        for m in 0..10 {
+            let overlap = match m { 3 | 9 => 3, 5 => 2, _ => 1 };
+            for k in 0..overlap {
+                connect(&mut e, m, k, active_input(k + 1), 0.5);
+            }
+        }
+        // disconnected synapses and synapses of inactive inputs don't count
+        connect(&mut e, 20, 0, active_input(2), HTM_PERMANENCE_THRESHOLD / 2.);
+        connect(&mut e, 20, 1, inactive_input(3), 0.5);
+        let overlap = e.overlap(&hit_blocks());
+        assert_eq!(&overlap[..11], &[1, 1, 1, 3, 1, 2, 1, 1, 1, 3, 0]);
+        assert_eq!(overlap[20], 0);
+        // only HTM_LIMIT_OF_MINICOLUMNS_TO_ACTIVATE win and ties go to lower indices
+        let active = e.spatial_pooler(&hit_blocks(), false);
+        assert_eq!(active, only(&[0, 1, 2, 3, 4, 5, 6, 9]));
+        assert_eq!(active.iter().filter(|&&a| a).count(), HTM_LIMIT_OF_MINICOLUMNS_TO_ACTIVATE);
+    }
+
+    #[test]
+    fn spatial_pooler_learns_only_on_active_minicolumns() {
+        let mut e = HtmEntity::new(0, 1., 1.);
+        connect(&mut e, 0, 0, active_input(0), 0.5);
+        connect(&mut e, 0, 1, inactive_input(1), 0.5);
+        connect(&mut e, 0, 2, active_input(2), 0.97);
+        connect(&mut e, 0, 3, inactive_input(3), 0.01);
+        connect(&mut e, 1, 0, inactive_input(0), 0.5);
+        let before = e;
+        let active = e.spatial_pooler(&hit_blocks(), false);
+        assert_eq!(active, only(&[0]));
+        assert_eq!(e, before);
+        e.spatial_pooler(&hit_blocks(), true);
+        assert_close(permanence(&e, 0, 0), 0.5 + HTM_PERMAMENCE_INCREMENT);
+        assert_close(permanence(&e, 0, 1), 0.5 + HTM_PERMAMENCE_DECREMENT);
+        assert_eq!(permanence(&e, 0, 2), 1.);
+        assert_eq!(permanence(&e, 0, 3), 0.);
+        assert_eq!(permanence(&e, 1, 0), 0.5);
+    }
+
+    #[test]
+    fn predicted_minicolumns_activate_one_layer_and_others_burst() {
+        let mut e = HtmEntity::new(0, 1., 1.);
+        for m in e.minicolumns_mut().iter_mut() {
+            *m = isolated_minicolumn();
+        }
+        // minicolumn 1 predicts layer 2 whenever minicolumn 0 was active
+        let segment = 2 * HTM_MAX_SEGMENTS + 1;
+        e.minicolumns_mut()[1].context_connections_mut()[3] = HtmContextConnection::new(0, 0.5, segment);
+        e.minicolumns_mut()[1].context_connections_mut()[7] = HtmContextConnection::new(0, 0.5, segment);
+        e.temporal_memory(&only(&[0]), false, 0);
+        assert!(e.minicolumns()[0].is_bursting());
+        e.temporal_memory(&only(&[1, 2]), true, 0);
+        assert!(!e.minicolumns()[0].is_active());
+        assert_eq!(e.minicolumns()[1].active_layer(), 2);
+        assert!(e.minicolumns()[2].is_bursting());
+        // the segment that predicted correctly is reinforced
+        assert_close(e.minicolumns()[1].context_connections()[3].permanence(), 0.5 + HTM_PERMAMENCE_INCREMENT);
+        assert_close(e.minicolumns()[1].context_connections()[7].permanence(), 0.5 + HTM_PERMAMENCE_INCREMENT);
+        // without minicolumn 0 in previous step there is no prediction
+        e.temporal_memory(&only(&[1]), false, 0);
+        assert!(e.minicolumns()[1].is_bursting());
+    }
+
+    #[test]
+    fn bursting_minicolumn_grows_the_best_matching_segment() {
+        let mut m = isolated_minicolumn();
+        let segment = 3 * HTM_MAX_SEGMENTS + 2;
+        m.context_connections_mut()[4] = HtmContextConnection::new(0, HTM_PERMANENCE_THRESHOLD / 2., segment);
+        m.context_connections_mut()[5] = HtmContextConnection::new(1, 0.5, segment);
+        let previously_active = only(&[0]);
+        assert_eq!(m.predicted_layer(&previously_active), None);
+        assert_eq!(m.learn_bursting(&previously_active, 0), 3);
+        let grown = m.context_connections()[4];
+        assert_close(grown.permanence(), HTM_PERMANENCE_THRESHOLD / 2. + HTM_PERMAMENCE_INCREMENT);
+        assert!(grown.is_connected());
+        assert_close(m.context_connections()[5].permanence(), 0.5 + HTM_PERMAMENCE_DECREMENT);
+    }
+
+    #[test]
+    fn bursting_minicolumn_rewires_its_weakest_connection() {
+        let mut m = HtmMinicolumn::new([HtmContextConnection::new(HTM_MINICOLUMNS as u32 - 1, 0.3, 0); HTM_CONTEXT_CONNECTIONS_PER_MINICOLUMN]);
+        m.context_connections_mut()[5] = HtmContextConnection::new(HTM_MINICOLUMNS as u32 - 1, 0.01, 0);
+        let previously_active = only(&[2, 4]);
+        // layer is rand_seed%HTM_MINICOLUMN_LAYERS and the presynaptic minicolumn is the second previously active one
+        let rand_seed = HTM_MINICOLUMN_LAYERS + 4;
+        assert_eq!(m.learn_bursting(&previously_active, rand_seed), 4);
+        assert_eq!(m.context_connections()[5], HtmContextConnection::new(4, HTM_INITIAL_CONTEXT_PERMANENCE, 4 * HTM_MAX_SEGMENTS));
+        assert!(m.context_connections().iter().enumerate().all(|(i, &c)| i == 5 || c == HtmContextConnection::new(HTM_MINICOLUMNS as u32 - 1, 0.3, 0)));
+        // there is nothing to wire to if no minicolumn was active
+        let before = m;
+        assert_eq!(m.learn_bursting(&only(&[]), 1), 1);
+        assert_eq!(m, before);
+    }
+
+    #[test]
+    fn step_sums_muscle_impulses_of_active_minicolumns() {
+        let mut e = HtmEntity::new(0, 2., 1.);
+        connect(&mut e, 5, 0, active_input(0), 0.5);
+        connect(&mut e, 30, 0, active_input(0), 0.5);
+        let step = e.step(&hit_blocks(), 0);
+        assert_eq!(step.active_minicolumns, only(&[5, 30]));
+        let movement = ann_impulses_of_movement_muscles(HTM_OUTPUT_MOVEMENT_MUSCLES_SIZE);
+        let rotation = ann_impulses_of_rotation_muscles(HTM_OUTPUT_ROTATION_MUSCLES_SIZE);
+        let scale = 1. / HTM_LIMIT_OF_MINICOLUMNS_TO_ACTIVATE as f32;
+        assert!(glm::length(&(step.directional_impulse - (movement[5] + movement[10]) * scale)) < 1e-6);
+        assert!(glm::length(&(step.rotation_change - (rotation[5] + rotation[6]) * scale)) < 1e-6);
+    }
+
+    #[test]
+    fn words_round_trip() {
+        let mut e = HtmEntity::new(7, 0.5, 3.);
+        e.lidars_mut()[3] = AnnLidar::new(glm::vec3(0., 1., 0.));
+        connect(&mut e, 63, 31, 1234, 0.75);
+        e.minicolumns_mut()[2].context_connections_mut()[15] = HtmContextConnection::new(9, 0.125, 23);
+        e.minicolumns_mut()[2].active_layer = HTM_MINICOLUMN_BURSTING;
+        // std430 layout of HtmEntity in constants.comp
+        let lidars_words = ENTITY_LIDAR_COUNT * 4;
+        let feedforward_words = HTM_TOTAL_FEEDFORWARD_CONNECTIONS * 2;
+        let minicolumn_words = 1 + HTM_CONTEXT_CONNECTIONS_PER_MINICOLUMN * 3;
+        let bone_idx_word = lidars_words + feedforward_words + HTM_MINICOLUMNS * minicolumn_words;
+        assert_eq!(std::mem::size_of::<HtmEntity>(), (bone_idx_word + 8) * 4);
+        assert_eq!(std::mem::size_of::<HtmEntity>() % 16, 0);
+        let mut words = vec![0u32; std::mem::size_of::<HtmEntity>() / 4];
+        unsafe { std::ptr::copy_nonoverlapping(&e as *const HtmEntity as *const u32, words.as_mut_ptr(), words.len()) };
+        assert_eq!(words[3 * 4 + 1], 1f32.to_bits());
+        assert_eq!(&words[lidars_words + feedforward_words - 2..lidars_words + feedforward_words], &[1234, 0.75f32.to_bits()]);
+        let minicolumn = lidars_words + feedforward_words + 2 * minicolumn_words;
+        assert_eq!(words[minicolumn], HTM_MINICOLUMN_BURSTING);
+        assert_eq!(&words[minicolumn + minicolumn_words - 3..minicolumn + minicolumn_words], &[9, 0.125f32.to_bits(), 23]);
+        assert_eq!(&words[bone_idx_word..bone_idx_word + 5], &[7, 0, 3f32.to_bits(), 0.5f32.to_bits(), 0.5f32.to_bits()]);
+        let read = unsafe { std::ptr::read_unaligned(words.as_ptr() as *const HtmEntity) };
+        assert_eq!(read, e);
+    }
 }
@@ -7,4 +7,9 @@ pub mod cppn;
 pub mod population;
 pub mod htm_entity;
 pub mod ann_entity;
+pub mod body;
 
+pub mod ecology;
+pub mod brain_file;
+pub mod genealogy;
+pub mod es;
@@ -1,38 +1,153 @@
+use std::time::Duration;
 
-use std::time::{Duration, Instant};
+/**Time scales that can be selected with the keyboard, from the slowest to the fastest*/
+pub const TIME_SCALES:[f32;7] = [0.25, 0.5, 1., 2., 4., 8., 16.];
+const DEFAULT_TIME_SCALE_IDX:usize = 2;
 
+/**Decides how many simulation steps should be run in each frame, so that the speed of the simulation
+doesn't depend on the frame rate. Real time elapsed between frames (multiplied by the time scale) is accumulated
+and every whole step of it is spent on one compute submission. At most max_steps_per_frame steps are run per frame
+and the rest of accumulated time is dropped. Otherwise a simulation that can't keep up would fall further behind
+with every frame. At max speed the time scale is ignored and every frame runs max_steps_per_frame steps*/
 pub struct PhysicsTimer {
-    previous: Instant,
-    delta: Duration,
-    steps_per_second: u64,
+    /**Real time that hasn't been simulated yet, measured in steps*/
+    accumulator: f32,
+    interpolation: f32,
+    steps_per_second: u32,
+    max_steps_per_frame: u32,
+    time_scale_idx: usize,
+    max_speed: bool,
 }
 
 impl PhysicsTimer {
-    pub fn new(steps_per_second:u64) -> Self {
+    pub fn new(steps_per_second:u32, max_steps_per_frame:u32) -> Self {
         Self {
-            previous: Instant::now(),
-            delta: Duration::default(),
-            steps_per_second
+            accumulator: 0.,
+            interpolation: 1.,
+            steps_per_second,
+            max_steps_per_frame,
+            time_scale_idx: DEFAULT_TIME_SCALE_IDX,
+            max_speed: false,
         }
     }
-    pub fn update(&mut self) {
-        let current = Instant::now();
-        let delta = current - self.previous;
-        let proportion = delta.as_millis() as u64 * self.steps_per_second;
-        let current = if proportion < 1000 {
-            let sleep_time = (1000 - proportion) / self.steps_per_second;
-            std::thread::sleep(Duration::from_millis(sleep_time));
-            Instant::now()
-        }else{
-            current
+    /**Call once per frame with the real time elapsed since the previous frame. Returns the number of simulation steps
+    to run in this frame. Time doesn't accumulate while the simulation is paused*/
+    pub fn advance(&mut self, elapsed:Duration, is_running:bool) -> u32 {
+        if !is_running || self.max_speed {
+            self.accumulator = 0.;
+            self.interpolation = 1.;
+            return if is_running { self.max_steps_per_frame } else { 0 };
+        }
+        self.accumulator += elapsed.as_secs_f32() * self.steps_per_second as f32 * self.time_scale();
+        let steps = self.accumulator as u32;
+        let steps = if steps > self.max_steps_per_frame {
+            self.accumulator = self.accumulator.fract();
+            self.max_steps_per_frame
+        } else {
+            self.accumulator -= steps as f32;
+            steps
         };
-        self.delta = current - self.previous;
-        self.previous = current;
+        self.interpolation = self.accumulator;
+        steps
+    }
+    /**Between 0 and 1. Bones are rendered at this fraction of the way from the state before the latest step
+    to the state after it, which hides the fact that some frames run more steps than others*/
+    pub fn interpolation(&self) -> f32 {
+        self.interpolation
+    }
+    pub fn time_scale(&self) -> f32 {
+        TIME_SCALES[self.time_scale_idx]
+    }
+    pub fn is_max_speed(&self) -> bool {
+        self.max_speed
+    }
+    pub fn speed_up(&mut self) {
+        self.time_scale_idx = (self.time_scale_idx + 1).min(TIME_SCALES.len() - 1);
+    }
+    pub fn slow_down(&mut self) {
+        self.time_scale_idx = self.time_scale_idx.saturating_sub(1);
     }
-    pub fn delta(&self) -> u64 {
-        self.delta.as_millis() as u64
+    pub fn toggle_max_speed(&mut self) {
+        self.max_speed = !self.max_speed;
     }
-    pub fn delta_f32(&self) -> f32 {
-        self.delta.as_millis() as f32
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    // Durations and rates are powers of two, so that the accumulated steps are exact
+    const STEPS_PER_SECOND:u32 = 64;
+    const MAX_STEPS_PER_FRAME:u32 = 10;
+
+    fn steps(n:u32) -> Duration{
+        Duration::from_secs_f64(n as f64 / STEPS_PER_SECOND as f64)
+    }
+
+    #[test]
+    fn steps_follow_elapsed_time(){
+        let mut timer = PhysicsTimer::new(STEPS_PER_SECOND, MAX_STEPS_PER_FRAME);
+        assert_eq!(timer.advance(steps(8), true), 8);
+        assert_eq!(timer.interpolation(), 0.);
+        assert_eq!(timer.advance(steps(1) / 2, true), 0);
+        assert_eq!(timer.interpolation(), 0.5);
+        assert_eq!(timer.advance(steps(1) / 2, true), 1);
+        assert_eq!(timer.interpolation(), 0.);
+        // the time scale multiplies the elapsed time
+        timer.speed_up();
+        assert_eq!(timer.advance(steps(3), true), 6);
+        timer.slow_down();
+        timer.slow_down();
+        assert_eq!(timer.advance(steps(3), true), 1);
+        assert_eq!(timer.interpolation(), 0.5);
+    }
+
+    #[test]
+    fn slow_frames_do_not_spiral(){
+        let mut timer = PhysicsTimer::new(STEPS_PER_SECOND, MAX_STEPS_PER_FRAME);
+        assert_eq!(timer.advance(Duration::from_secs(10) + steps(1) / 4, true), MAX_STEPS_PER_FRAME);
+        assert_eq!(timer.interpolation(), 0.25);
+        // the time that could not be simulated is dropped instead of being made up for later
+        assert_eq!(timer.advance(steps(1) / 2, true), 0);
+        assert_eq!(timer.interpolation(), 0.75);
+    }
+
+    #[test]
+    fn paused_or_max_speed_ignores_elapsed_time(){
+        let mut timer = PhysicsTimer::new(STEPS_PER_SECOND, MAX_STEPS_PER_FRAME);
+        assert_eq!(timer.advance(steps(1) / 2, true), 0);
+        assert_eq!(timer.advance(Duration::from_secs(1), false), 0);
+        assert_eq!(timer.interpolation(), 1.);
+        assert_eq!(timer.advance(steps(1) / 4, true), 0);
+        assert_eq!(timer.interpolation(), 0.25);
+        timer.toggle_max_speed();
+        assert!(timer.is_max_speed());
+        assert_eq!(timer.advance(Duration::from_secs(0), true), MAX_STEPS_PER_FRAME);
+        assert_eq!(timer.advance(Duration::from_secs(0), false), 0);
+        assert_eq!(timer.interpolation(), 1.);
+    }
+
+    #[test]
+    fn time_scale_stays_within_bounds(){
+        let mut timer = PhysicsTimer::new(STEPS_PER_SECOND, MAX_STEPS_PER_FRAME);
+        assert_eq!(timer.time_scale(), 1.);
+        for _ in 0..2 * TIME_SCALES.len(){
+            timer.slow_down();
+        }
+        assert_eq!(timer.time_scale(), TIME_SCALES[0]);
+        for _ in 0..2 * TIME_SCALES.len(){
+            timer.speed_up();
+        }
+        assert_eq!(timer.time_scale(), TIME_SCALES[TIME_SCALES.len() - 1]);
+    }
+
+    #[test]
+    fn interpolation_stays_below_one(){
+        let mut timer = PhysicsTimer::new(60, MAX_STEPS_PER_FRAME);
+        for frame in 0..1000u64{
+            timer.advance(Duration::from_micros(frame * 7919 % 40000), true);
+            let alpha = timer.interpolation();
+            assert!((0. ..1.).contains(&alpha), "{}", alpha);
+        }
     }
 }
@@ -16,32 +16,41 @@ use crate::pipelines::player_event::{PlayerEvent, EventType};
 
 pub struct AmbienceResources {
     update_player_events: ShaderModule<Compute>,
-    // update_ambience: ShaderModule<Compute>,
+    update_ambience: ShaderModule<Compute>,
     update_ambience_faces: ShaderModule<Compute>,
     update_ambience_flush_insertions: ShaderModule<Compute>,
     update_ambience_prepare_face_offsets: ShaderModule<Compute>,
     update_ambience_prepare_insertions: ShaderModule<Compute>,
     update_ambience_flush_world_copy: ShaderModule<Compute>,
+    update_ambience_fluids: ShaderModule<Compute>,
+    update_ambience_climate: ShaderModule<Compute>,
+    update_ambience_commit: ShaderModule<Compute>,
 }
 
 
 impl AmbienceResources {
     pub fn new(cmd_pool: &CommandPool, _foundations: &FoundationInitializer) -> Result<Self, failure::Error> {
         let update_player_events = ShaderModule::new(include_glsl!("assets/shaders/update_player_events.comp", kind: comp) as &[u32], cmd_pool.device())?;
-        // let update_ambience = ShaderModule::new(include_glsl!("assets/shaders/update_ambience.comp", kind: comp) as &[u32], cmd_pool.device())?;
+        let update_ambience = ShaderModule::new(include_glsl!("assets/shaders/update_ambience.comp", kind: comp) as &[u32], cmd_pool.device())?;
         let update_ambience_faces = ShaderModule::new(include_glsl!("assets/shaders/update_ambience_faces.comp", kind: comp) as &[u32], cmd_pool.device())?;
         let update_ambience_flush_insertions = ShaderModule::new(include_glsl!("assets/shaders/update_ambience_flush_insertions.comp", kind: comp) as &[u32], cmd_pool.device())?;
         let update_ambience_prepare_face_offsets = ShaderModule::new(include_glsl!("assets/shaders/update_ambience_prepare_face_offsets.comp", kind: comp, target: vulkan1_1) as &[u32], cmd_pool.device())?;
         let update_ambience_prepare_insertions = ShaderModule::new(include_glsl!("assets/shaders/update_ambience_prepare_insertions.comp", kind: comp, target: vulkan1_1) as &[u32], cmd_pool.device())?;
         let update_ambience_flush_world_copy = ShaderModule::new(include_glsl!("assets/shaders/update_ambience_flush_world_copy.comp", kind: comp) as &[u32], cmd_pool.device())?;
+        let update_ambience_fluids = ShaderModule::new(include_glsl!("assets/shaders/update_ambience_fluids.comp", kind: comp) as &[u32], cmd_pool.device())?;
+        let update_ambience_climate = ShaderModule::new(include_glsl!("assets/shaders/update_ambience_climate.comp", kind: comp) as &[u32], cmd_pool.device())?;
+        let update_ambience_commit = ShaderModule::new(include_glsl!("assets/shaders/update_ambience_commit.comp", kind: comp) as &[u32], cmd_pool.device())?;
         Ok(Self {
             update_player_events,
-            // update_ambience,
+            update_ambience,
             update_ambience_faces,
             update_ambience_flush_insertions,
             update_ambience_prepare_face_offsets,
             update_ambience_prepare_insertions,
-            update_ambience_flush_world_copy
+            update_ambience_flush_world_copy,
+            update_ambience_fluids,
+            update_ambience_climate,
+            update_ambience_commit
         })
     }
 }
@@ -52,12 +61,15 @@ impl ComputeResources for AmbienceResources {
     fn make_computable(self, cmd_pool: &CommandPool, foundations: &Foundations) -> Result<Ambience, Error> {
         let Self {
             update_player_events,
-            // update_ambience,
+            update_ambience,
             update_ambience_faces,
             update_ambience_flush_insertions,
             update_ambience_prepare_face_offsets,
             update_ambience_prepare_insertions,
-            update_ambience_flush_world_copy
+            update_ambience_flush_world_copy,
+            update_ambience_fluids,
+            update_ambience_climate,
+            update_ambience_commit
         } = self;
         let mut descriptors = ComputeDescriptorsBuilder::new();
         let uniform_binding = descriptors.uniform_buffer(foundations.player_event_uniform().buffer());
@@ -69,24 +81,35 @@ impl ComputeResources for AmbienceResources {
         descriptors.storage_buffer(foundations.world());
         descriptors.storage_buffer(foundations.faces());
         descriptors.storage_buffer(foundations.blocks_to_be_inserted_or_removed());
+        descriptors.storage_buffer(foundations.particles());
+        descriptors.storage_buffer(foundations.world_blocks_to_update());
         let descriptors = descriptors.build(cmd_pool.device())?;
 
         let sc = foundations.specialization_constants().build();
         let update_player_events = descriptors.build("main", update_player_events, &sc)?;
-        // let update_ambience = descriptors.build("main", update_ambience, &sc)?;
+        let update_ambience = descriptors.build("main", update_ambience, &sc)?;
         let update_ambience_faces = descriptors.build("main", update_ambience_faces,&sc)?;
         let update_ambience_flush_insertions = descriptors.build("main", update_ambience_flush_insertions,&sc)?;
         let update_ambience_prepare_face_offsets = descriptors.build("main", update_ambience_prepare_face_offsets,&sc)?;
         let update_ambience_prepare_insertions = descriptors.build("main", update_ambience_prepare_insertions,&sc)?;
         let update_ambience_flush_world_copy = descriptors.build("main", update_ambience_flush_world_copy,&sc)?;
+        let update_ambience_fluids = descriptors.build("main", update_ambience_fluids,&sc)?;
+        let update_ambience_climate = descriptors.build("main", update_ambience_climate,&sc)?;
+        let update_ambience_commit = descriptors.build("main", update_ambience_commit,&sc)?;
+        let subgroup_size = cmd_pool.device().get_max_subgroup_size();
+        let world_volume_groups = (foundations.world_size().world_volume() as u32 + subgroup_size - 1) / subgroup_size;
         Ok(Ambience {
             update_player_events,
-            // update_ambience,
+            update_ambience,
             update_ambience_faces,
             update_ambience_flush_insertions,
             update_ambience_prepare_face_offsets,
             update_ambience_prepare_insertions,
-            update_ambience_flush_world_copy
+            update_ambience_flush_world_copy,
+            update_ambience_fluids,
+            update_ambience_climate,
+            update_ambience_commit,
+            world_volume_groups
         })
     }
 }
@@ -94,12 +117,17 @@ impl ComputeResources for AmbienceResources {
 
 pub struct Ambience {
     update_player_events: ComputePipeline,
-    // update_ambience: ComputePipeline,
+    update_ambience: ComputePipeline,
     update_ambience_faces: ComputePipeline,
     update_ambience_flush_insertions: ComputePipeline,
     update_ambience_prepare_face_offsets: ComputePipeline,
     update_ambience_prepare_insertions: ComputePipeline,
     update_ambience_flush_world_copy: ComputePipeline,
+    update_ambience_fluids: ComputePipeline,
+    update_ambience_climate: ComputePipeline,
+    update_ambience_commit: ComputePipeline,
+    /**update_ambience_fluids.comp, update_ambience_climate.comp and update_ambience_commit.comp run one thread per block of the world*/
+    world_volume_groups: u32,
 }
 
 impl Computable for Ambience {
@@ -110,17 +138,37 @@ impl Computable for Ambience {
             .dispatch_1d(1)
             .buffer_barriers(vk::PipelineStageFlags::COMPUTE_SHADER, vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::DRAW_INDIRECT, &[
                 make_shader_dispatch_buffer_barrier(foundations.indirect().update_ambience()),
+                make_shader_dispatch_buffer_barrier(foundations.indirect().update_ambience_faces()),
                 make_shader_buffer_barrier(foundations.global_mutables()),
                 make_shader_buffer_barrier(foundations.blocks_to_be_inserted_or_removed())
             ])
-            // .bind_compute_pipeline(&self.update_ambience)
-            // .dispatch_indirect(foundations.indirect().update_ambience(),0)
-            // .buffer_barriers(vk::PipelineStageFlags::COMPUTE_SHADER, vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::DRAW_INDIRECT, &[
-            //     make_shader_buffer_barrier(foundations.world_blocks_to_update()),
-            //     make_shader_dispatch_buffer_barrier(foundations.indirect().update_ambience_faces()),
-            //     make_shader_buffer_barrier(foundations.global_mutables()),
-            //     make_shader_buffer_barrier(foundations.blocks_to_be_inserted_or_removed())
-            // ])
+            .bind_compute_pipeline(&self.update_ambience)
+            .dispatch_indirect(foundations.indirect().update_ambience(),0)
+            .buffer_barriers(vk::PipelineStageFlags::COMPUTE_SHADER, vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::DRAW_INDIRECT, &[
+                make_shader_buffer_barrier(foundations.world_blocks_to_update()),
+                make_shader_dispatch_buffer_barrier(foundations.indirect().update_ambience_faces()),
+                make_shader_buffer_barrier(foundations.world()),
+                make_shader_buffer_barrier(foundations.global_mutables()),
+                make_shader_buffer_barrier(foundations.blocks_to_be_inserted_or_removed())
+            ])
+            .bind_compute_pipeline(&self.update_ambience_fluids)
+            .dispatch_1d(self.world_volume_groups)
+            .buffer_barriers(vk::PipelineStageFlags::COMPUTE_SHADER, vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::DRAW_INDIRECT, &[
+                make_shader_dispatch_buffer_barrier(foundations.indirect().update_ambience_faces()),
+                make_shader_buffer_barrier(foundations.world()),
+                make_shader_buffer_barrier(foundations.global_mutables()),
+                make_shader_buffer_barrier(foundations.blocks_to_be_inserted_or_removed())
+            ])
+            .bind_compute_pipeline(&self.update_ambience_climate)
+            .dispatch_1d(self.world_volume_groups)
+            .buffer_barriers(vk::PipelineStageFlags::COMPUTE_SHADER, vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::DRAW_INDIRECT, &[
+                make_shader_dispatch_buffer_barrier(foundations.indirect().update_ambience_faces()),
+                make_shader_buffer_barrier(foundations.world()),
+                make_shader_buffer_barrier(foundations.global_mutables()),
+                make_shader_buffer_barrier(foundations.blocks_to_be_inserted_or_removed())
+            ])
+            .bind_compute_pipeline(&self.update_ambience_commit)
+            .dispatch_1d(self.world_volume_groups)
             .bind_compute_pipeline(&self.update_ambience_faces)
             .dispatch_indirect(foundations.indirect().update_ambience_faces(),0)
             .buffer_barriers(vk::PipelineStageFlags::COMPUTE_SHADER, vk::PipelineStageFlags::COMPUTE_SHADER, &[
@@ -4,6 +4,21 @@ use ash::vk;
 use crate::neat::num::Num;
 use crate::blocks::block_properties::DIRT;
 
+pub const ENTITY_TYPE_ANN:u32 = 0;
+pub const ENTITY_TYPE_HTM:u32 = 1;
+pub fn encode_ann_entity_idx(ann_entity_idx:u32) -> u32{
+    ann_entity_idx*2+ENTITY_TYPE_ANN
+}
+pub fn encode_htm_entity_idx(htm_entity_idx:u32) -> u32{
+    htm_entity_idx*2+ENTITY_TYPE_HTM
+}
+pub fn decode_entity_idx(encoded_entity_idx:u32) -> u32{
+    encoded_entity_idx/2
+}
+pub fn decode_entity_type(encoded_entity_idx:u32) -> u32{
+    encoded_entity_idx&1
+}
+
 #[repr(C, packed)]
 #[derive(Copy,Clone,Debug)]
 pub struct Bone{
@@ -41,6 +56,88 @@ impl Bone{
             parent_bone_idx: u32::MAX
         }
     }
+    /**Bone of an articulated body. Its position_relative_to_parent is the rest offset of its center
+    from the center of the parent bone. See Body in body.rs*/
+    pub fn limb(center:glm::Vec3,
+                half_side_length:f32,
+                half_height:f32,
+                mass:f32,
+                parent_bone_idx:u32,
+                position_relative_to_parent:glm::Vec3) -> Self{
+        Self{
+            new_center:center,
+            half_side_length,
+            half_height,
+            old_center:center,
+            entity_idx: 0,
+            position_relative_to_parent,
+            mass,
+            yaw_and_pitch:glm::vec2(0.,0.),
+            texture_for_block_id:DIRT.id(),
+            impulse: glm::vec3(0.,0.,0.),
+            parent_bone_idx
+        }
+    }
+    pub fn new_center(&self) -> glm::Vec3{
+        self.new_center
+    }
+    pub fn old_center(&self) -> glm::Vec3{
+        self.old_center
+    }
+    /**Moves the bone to the given position. Velocity and accumulated impulse are reset*/
+    pub fn place_at(&mut self, center:glm::Vec3){
+        self.old_center = center;
+        self.new_center = center;
+        self.impulse = glm::vec3(0.,0.,0.);
+    }
+    pub fn set_old_center(&mut self, old_center:glm::Vec3){
+        self.old_center = old_center
+    }
+    pub fn set_new_center(&mut self, new_center:glm::Vec3){
+        self.new_center = new_center
+    }
+    pub fn entity_idx(&self) -> u32{
+        self.entity_idx
+    }
+    pub fn set_entity_idx(&mut self, entity_idx:u32){
+        self.entity_idx = entity_idx
+    }
+    pub fn impulse(&self) -> glm::Vec3{
+        self.impulse
+    }
+    pub fn set_impulse(&mut self, impulse:glm::Vec3){
+        self.impulse = impulse
+    }
+    pub fn add_impulse(&mut self, impulse:glm::Vec3){
+        self.impulse = self.impulse + impulse
+    }
+    pub fn mass(&self) -> f32{
+        self.mass
+    }
+    pub fn half_side_length(&self) -> f32{
+        self.half_side_length
+    }
+    pub fn half_height(&self) -> f32{
+        self.half_height
+    }
+    pub fn parent_bone_idx(&self) -> u32{
+        self.parent_bone_idx
+    }
+    pub fn set_parent_bone_idx(&mut self, parent_bone_idx:u32){
+        self.parent_bone_idx = parent_bone_idx
+    }
+    pub fn position_relative_to_parent(&self) -> glm::Vec3{
+        self.position_relative_to_parent
+    }
+    pub fn velocity(&self) -> glm::Vec3{
+        self.new_center - self.old_center
+    }
+    pub fn yaw_and_pitch(&self) -> glm::Vec2{
+        self.yaw_and_pitch
+    }
+    pub fn set_yaw_and_pitch(&mut self, yaw_and_pitch:glm::Vec2){
+        self.yaw_and_pitch = yaw_and_pitch
+    }
 }
 
 impl VertexSource for Bone{
@@ -76,6 +173,12 @@ impl VertexSource for Bone{
                 format:  glm::Vec2::FORMAT,
                 offset: offset_of!(Self, yaw_and_pitch) as u32,
             },
+            vk::VertexInputAttributeDescription {
+                binding,
+                location: 5,
+                format:  glm::Vec3::FORMAT,
+                offset: offset_of!(Self, old_center) as u32,
+            },
         ]
     }
 }
\ No newline at end of file
@@ -0,0 +1,445 @@
+use crate::blocks::{Block, BlockId, WorldSize};
+use crate::blocks::world_size::{BROAD_PHASE_CELL_SIZE, BROAD_PHASE_CELL_CAPACITY, BROAD_PHASE_CHUNK_VOLUME_IN_CELLS, BROAD_PHASE_CHUNK_WIDTH_IN_CELLS, BROAD_PHASE_CHUNK_HEIGHT_IN_CELLS};
+use crate::pipelines::bone::Bone;
+
+/**Fraction of velocity kept by a bone that collided with a block. Same as BLOCK_COLLISION_FRICTION in constants.comp*/
+pub const BLOCK_COLLISION_FRICTION:f32 = 0.99;
+/**Same as BONE_COLLISION_FORCE_PER_AREA_UNIT in constants.comp*/
+pub const BONE_COLLISION_FORCE_PER_AREA_UNIT:f32 = 0.01;
+/**Same as PHYSICS_SIMULATION_DELTA_TIME_PER_STEP in constants.comp*/
+pub const PHYSICS_SIMULATION_DELTA_TIME_PER_STEP:f32 = 1.0;
+/**Same as GRAVITY in constants.comp*/
+pub const GRAVITY:f32 = 0.001;
+/**Velocity of bones is multiplied by this factor every step. Same as DAMPING_COEFFICIENT in constants.comp*/
+pub const DAMPING_COEFFICIENT:f32 = 0.99;
+/**Fraction of velocity cancelled by a block collision that comes back as impulse in the next step.
+Same as BLOCK_RIGIDITY in constants.comp*/
+pub const BLOCK_RIGIDITY:f32 = 0.9;
+/**Every step the velocity of the bone held by the player changes by this fraction of the distance to the hold target.
+Same as HOLD_STIFFNESS in constants.comp*/
+pub const HOLD_STIFFNESS:f32 = 0.05;
+/**Every step the held bone loses this fraction of its velocity, so that it doesn't swing around the hold target.
+Same as HOLD_DAMPING in constants.comp*/
+pub const HOLD_DAMPING:f32 = 0.4;
+
+/**An edge of the hitbox is identified by the dimensions of the normals of the two faces that it joins,
+followed by the dimension along which the edge itself lies. Same as dimensions_per_edge in update_bones.comp*/
+const DIMENSIONS_PER_EDGE:[[usize;3];3] = [[0, 1, 2], [1, 2, 0], [2, 0, 1]];
+
+/**Offsets of the neighbouring cells checked by narrow_phase_collision_detection.comp. Only half of the 26 neighbours
+are listed, because the other half is checked by the bones in those neighbouring cells*/
+const NEIGHBOUR_CELLS:[[i32;3];13] = [
+    [0, 0, 1], [1, 0, 0], [1, 0, 1], [1, 0, -1],
+    [0, 1, 1], [1, 1, 0], [1, 1, 1], [1, 1, -1],
+    [0, -1, 1], [1, -1, 0], [1, -1, 1], [1, -1, -1],
+    [0, 1, 0],
+];
+
+/**Same as CollisionCell in constants.comp. Len counts all the bones that were assigned to the cell,
+including those that did not fit into contents*/
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CollisionCell{
+    pub len:u32,
+    pub contents:[u32;BROAD_PHASE_CELL_CAPACITY],
+}
+
+impl CollisionCell{
+    /**Bones that actually made it into the cell*/
+    pub fn bones(&self) -> &[u32]{
+        &self.contents[..(self.len as usize).min(BROAD_PHASE_CELL_CAPACITY)]
+    }
+}
+
+/**CPU reference of the collision grid used by broad_phase_collision_detection.comp and narrow_phase_collision_detection.comp.
+Each cell holds at most BROAD_PHASE_CELL_CAPACITY bones. Any bones beyond that are ignored by the narrow phase
+and counted as overflows, which accumulate over steps until they are taken (same as GlobalMutables::collision_grid_overflows)*/
+pub struct CollisionGrid{
+    world_size:WorldSize,
+    cells:Vec<CollisionCell>,
+    overflows:u32,
+}
+
+impl CollisionGrid{
+    pub fn new(world_size:WorldSize) -> Self{
+        Self{world_size, cells:vec![CollisionCell::default(); world_size.total_chunks() * BROAD_PHASE_CHUNK_VOLUME_IN_CELLS], overflows:0}
+    }
+    pub fn cells(&self) -> &[CollisionCell]{
+        &self.cells
+    }
+    /**Number of bones that did not fit into their cell since the overflows were last taken*/
+    pub fn overflows(&self) -> u32{
+        self.overflows
+    }
+    /**Returns the overflows and resets them, like Foundations::drain_lifecycle_events does on the GPU*/
+    pub fn take_overflows(&mut self) -> u32{
+        std::mem::replace(&mut self.overflows, 0)
+    }
+    /**Same as broad_phase_position_to_cell_pos in constants.comp*/
+    pub fn position_to_cell_pos(position:&glm::Vec3) -> [u32;3]{
+        let cell_size = BROAD_PHASE_CELL_SIZE as f32;
+        [(position.x / cell_size) as u32, (position.y / cell_size) as u32, (position.z / cell_size) as u32]
+    }
+    /**Same as broad_phase_cell_pos_to_cell_idx in constants.comp (including wrap-around of uint arithmetic)*/
+    pub fn cell_pos_to_cell_idx(&self, cell_pos:[u32;3]) -> usize{
+        let row = (self.world_size.width() * BROAD_PHASE_CHUNK_WIDTH_IN_CELLS) as u32;
+        cell_pos[0].wrapping_add(cell_pos[1].wrapping_add(cell_pos[2].wrapping_mul(BROAD_PHASE_CHUNK_HEIGHT_IN_CELLS as u32)).wrapping_mul(row)) as usize
+    }
+    /**Cells outside of the grid read as empty, like robust buffer access does on the GPU*/
+    fn cell(&self, cell_idx:usize) -> CollisionCell{
+        self.cells.get(cell_idx).copied().unwrap_or_default()
+    }
+    /**Empties all cells. Equivalent to broad_phase_collision_detection_cleanup.comp, which empties only the cells of bones
+    at their old positions (because those are the only cells that were filled in the previous step)*/
+    pub fn clear(&mut self){
+        for cell in &mut self.cells{
+            cell.len = 0;
+        }
+    }
+    /**Mirrors broad_phase_collision_detection.comp. Bones are assigned in the order of their indices,
+    whereas on the GPU the order in which bones claim slots of a crowded cell is arbitrary*/
+    pub fn broad_phase(&mut self, bones:&[Bone]){
+        for (bone_idx, bone) in bones.iter().enumerate(){
+            let cell_idx = self.cell_pos_to_cell_idx(Self::position_to_cell_pos(&bone.new_center()));
+            let cell = &mut self.cells[cell_idx];
+            let newly_assigned_bone_index = cell.len as usize;
+            cell.len += 1;
+            if newly_assigned_bone_index < BROAD_PHASE_CELL_CAPACITY{
+                cell.contents[newly_assigned_bone_index] = bone_idx as u32;
+            }else{
+                self.overflows += 1;
+            }
+        }
+    }
+    /**Mirrors narrow_phase_collision_detection.comp. Every pair of overlapping bones pushes both bones apart
+    by adding opposite impulses to them. Bones that did not fit into their cell take no part in it*/
+    pub fn narrow_phase(&self, bones:&mut [Bone]){
+        let mut impulses = vec![glm::vec3(0., 0., 0.); bones.len()];
+        let world_borders = [self.world_size.world_width() as u32, self.world_size.height() as u32, self.world_size.world_depth() as u32];
+        for bone_idx in 0..bones.len(){
+            let bone = &bones[bone_idx];
+            let half_hitbox = glm::vec3(bone.half_side_length(), bone.half_height(), bone.half_side_length());
+            let hitbox_from = bone.new_center() - half_hitbox;
+            let hitbox_to = bone.new_center() + half_hitbox;
+            let mut potential_collisions = Vec::with_capacity(14 * BROAD_PHASE_CELL_CAPACITY);
+            let cell_pos = Self::position_to_cell_pos(&bone.new_center());
+            // Only the bones that were placed in the same cell before this one are checked, so that no pair is detected twice
+            let cell = self.cell(self.cell_pos_to_cell_idx(cell_pos));
+            for &neighbouring_bone_idx in cell.bones(){
+                if neighbouring_bone_idx as usize == bone_idx{
+                    break
+                }
+                potential_collisions.push(neighbouring_bone_idx as usize);
+            }
+            if potential_collisions.len() == cell.bones().len(){
+                // the bone did not fit into its cell. Other bones can't see it, so it must not push them either
+                continue
+            }
+            for offset in &NEIGHBOUR_CELLS{
+                let mut neighbour_pos = cell_pos;
+                for dim in 0..3{
+                    neighbour_pos[dim] = neighbour_pos[dim].wrapping_add(offset[dim] as u32);
+                }
+                // Just like the shader, the cell position is compared against the world borders measured in blocks
+                if (0..3).all(|dim| neighbour_pos[dim] < world_borders[dim]){
+                    let neighbour = self.cell(self.cell_pos_to_cell_idx(neighbour_pos));
+                    potential_collisions.extend(neighbour.bones().iter().map(|&idx| idx as usize));
+                }
+            }
+            let mut bone_impulse = glm::vec3(0., 0., 0.);
+            for neighbouring_bone_idx in potential_collisions{
+                let neighbour_bone = &bones[neighbouring_bone_idx];
+                let neighbour_half_hitbox = glm::vec3(neighbour_bone.half_side_length(), neighbour_bone.half_height(), neighbour_bone.half_side_length());
+                let neighbour_hitbox_from = neighbour_bone.new_center() - neighbour_half_hitbox;
+                let neighbour_hitbox_to = neighbour_bone.new_center() + neighbour_half_hitbox;
+                if glm::all(&glm::less_than(&neighbour_hitbox_from, &hitbox_to)) && glm::all(&glm::less_than(&hitbox_from, &neighbour_hitbox_to)){
+                    let penetration_distance_from = hitbox_from - neighbour_hitbox_to;
+                    let penetration_distance_to = hitbox_to - neighbour_hitbox_from;
+                    // points from this bone towards the neighbouring bone. Its length is the diagonal of the overlapping region
+                    let mut penetration_distance = penetration_distance_to;
+                    for dim in 0..3{
+                        if -penetration_distance_from[dim] < penetration_distance_to[dim]{
+                            penetration_distance[dim] = penetration_distance_from[dim];
+                        }
+                    }
+                    let impulse = penetration_distance * glm::dot(&penetration_distance, &penetration_distance) * BONE_COLLISION_FORCE_PER_AREA_UNIT * 0.5;
+                    bone_impulse -= impulse;
+                    impulses[neighbouring_bone_idx] += impulse;
+                }
+            }
+            impulses[bone_idx] += bone_impulse;
+        }
+        for (bone, impulse) in bones.iter_mut().zip(impulses){
+            bone.add_impulse(impulse)
+        }
+    }
+}
+
+/**CPU reference of update_bones.comp. It runs the same algorithm against a CPU copy of the world,
+so that bone physics can be debugged and reproduced without the GPU. Blocks are laid out
+just like in the world buffer (see WorldSize::block_pos_into_world_idx)*/
+pub struct BonePhysics{
+    world_size:WorldSize,
+    world:Vec<Block>,
+}
+
+impl BonePhysics{
+    pub fn new(world_size:WorldSize, world:Vec<Block>) -> Self{
+        assert_eq!(world.len(), world_size.world_volume());
+        Self{world_size, world}
+    }
+    /**World made entirely of air*/
+    pub fn empty(world_size:WorldSize) -> Self{
+        Self::new(world_size, vec![Block::new(BlockId::air()); world_size.world_volume()])
+    }
+    pub fn world_size(&self) -> &WorldSize{
+        &self.world_size
+    }
+    pub fn world(&self) -> &[Block]{
+        &self.world
+    }
+    pub fn block_id_at(&self, x:usize, y:usize, z:usize) -> BlockId{
+        self.world[self.world_size.block_pos_into_world_idx(x, y, z)].block_id()
+    }
+    pub fn set_block_id_at(&mut self, x:usize, y:usize, z:usize, block_id:BlockId){
+        let idx = self.world_size.block_pos_into_world_idx(x, y, z);
+        self.world[idx].set_block_id(block_id)
+    }
+    /**Same as WORLD_BORDERS in constants.comp*/
+    fn world_borders(&self) -> [u32;3]{
+        [self.world_size.world_width() as u32, self.world_size.height() as u32, self.world_size.world_depth() as u32]
+    }
+    /**Mirrors is_traversable(block_pos_into_world_idx(pos)) in the shaders. Coordinates are unsigned
+    and wrap around exactly like uvec3 arithmetic on the GPU. Reads past the end of the world buffer
+    yield 0 (air) like robust buffer access does*/
+    fn is_traversable(&self, pos:[u32;3]) -> bool{
+        let world_width = self.world_size.world_width() as u32;
+        let world_area = self.world_size.world_area() as u32;
+        let idx = pos[0].wrapping_add(pos[1].wrapping_mul(world_area)).wrapping_add(pos[2].wrapping_mul(world_width)) as usize;
+        self.world.get(idx).map(|b| b.block_id().is_traversable()).unwrap_or(true)
+    }
+    /**Performs one simulation step of all bones. Mirrors update_bones.comp (apart from the handling of
+    PLAYER_EVENT_THROW and of the bone held by the player, which are not a part of physics)*/
+    pub fn update(&self, bones:&mut [Bone]){
+        for bone in bones{
+            self.update_bone(bone)
+        }
+    }
+    /**Performs one whole simulation step: collision detection between bones followed by update_bones.comp.
+    Mirrors the order of pipelines in Physics::record_compute_cmd_buffer*/
+    pub fn step(&self, grid:&mut CollisionGrid, bones:&mut [Bone]){
+        grid.clear();
+        grid.broad_phase(bones);
+        grid.narrow_phase(bones);
+        self.update(bones);
+    }
+    /**Mirrors the body of update_bones.comp executed for a single bone*/
+    pub fn update_bone(&self, bone:&mut Bone){
+        let mut velocity = bone.velocity();
+        velocity += bone.impulse() * PHYSICS_SIMULATION_DELTA_TIME_PER_STEP / bone.mass();
+        velocity.y -= GRAVITY * PHYSICS_SIMULATION_DELTA_TIME_PER_STEP;
+        velocity *= DAMPING_COEFFICIENT;
+        let new_center = bone.new_center();
+        let half_hitbox = glm::vec3(bone.half_side_length(), bone.half_height(), bone.half_side_length());
+        let hitbox_from = new_center - half_hitbox;
+        let hitbox_to = new_center + half_hitbox;
+        let hitbox_to_ceil = glm::ceil(&hitbox_to);
+        let start:[i32;3] = [hitbox_from.x as i32, hitbox_from.y as i32, hitbox_from.z as i32];
+        let end:[i32;3] = [hitbox_to_ceil.x as i32, hitbox_to_ceil.y as i32, hitbox_to_ceil.z as i32];
+        let world_borders = self.world_borders();
+        let velocity_before_block_collisions = velocity;
+        let velocity_sign:[bool;3] = [velocity.x < 0., velocity.y < 0., velocity.z < 0.];
+        let will_hitbox_move_to_next_block = |velocity:&glm::Vec3| -> [bool;3]{
+            let mut will_move = [false;3];
+            for dim in 0..3{
+                will_move[dim] = if velocity_sign[dim] {
+                    hitbox_from[dim] + velocity[dim] < start[dim] as f32
+                } else {
+                    hitbox_to_ceil[dim] < hitbox_to[dim] + velocity[dim]
+                };
+            }
+            will_move
+        };
+        let will_move = will_hitbox_move_to_next_block(&velocity);
+        for dim in 0..3{
+            if !will_move[dim]{
+                continue
+            }
+            let neg = velocity_sign[dim];
+            let bounded_by_world_border = if neg { start[dim] <= 0 } else { end[dim] as u32 >= world_borders[dim] };
+            if bounded_by_world_border{
+                velocity[dim] = 0.;
+            } else {
+                // Only the blocks touching the face that is "in front" of the moving hitbox need to be checked
+                let mut from = [start[0] as u32, start[1] as u32, start[2] as u32];
+                let mut to = [end[0] as u32, end[1] as u32, end[2] as u32];
+                if neg {
+                    from[dim] = from[dim].wrapping_sub(1);
+                    to[dim] = start[dim] as u32;
+                } else {
+                    from[dim] = end[dim] as u32;
+                    to[dim] = to[dim].wrapping_add(1);
+                }
+                for y in from[1]..to[1]{
+                    for z in from[2]..to[2]{
+                        for x in from[0]..to[0]{
+                            if !self.is_traversable([x, y, z]){
+                                velocity[dim] = 0.;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        let will_move = will_hitbox_move_to_next_block(&velocity);
+        let mut common_corner_block = [0u32;3];
+        let mut opposite_corner_block = [0u32;3];
+        let mut velocity_direction = [0i32;3];
+        for dim in 0..3{
+            if velocity_sign[dim] {
+                common_corner_block[dim] = (start[dim] - 1) as u32;
+                opposite_corner_block[dim] = end[dim] as u32;
+                velocity_direction[dim] = 1;
+            } else {
+                common_corner_block[dim] = (end[dim] + 1) as u32;
+                opposite_corner_block[dim] = start[dim] as u32;
+                velocity_direction[dim] = -1;
+            }
+        }
+        // Just like the shader, only the first two edges are checked
+        for dimensions in &DIMENSIONS_PER_EDGE[..2]{
+            let [dim_x, dim_y, dim_z] = *dimensions;
+            if will_move[dim_x] && will_move[dim_y]{
+                let step = velocity_direction[dim_z];
+                let mut block_on_edge = common_corner_block;
+                loop{
+                    block_on_edge[dim_z] = block_on_edge[dim_z].wrapping_add(step as u32);
+                    if !self.is_traversable(block_on_edge){
+                        velocity[dim_x] = 0.;
+                        velocity[dim_y] = 0.;
+                        break;
+                    }
+                    if block_on_edge[dim_z] == opposite_corner_block[dim_z]{
+                        break;
+                    }
+                }
+            }
+        }
+        let friction = if velocity == velocity_before_block_collisions { 1. } else { BLOCK_COLLISION_FRICTION };
+        bone.set_old_center(new_center);
+        bone.set_new_center(new_center + velocity * friction);
+        bone.set_impulse((velocity - velocity_before_block_collisions) * BLOCK_RIGIDITY);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::blocks::block_properties::STONE;
+
+    /**Single chunk of air, with stone placed at the given blocks*/
+    fn world(stone:impl IntoIterator<Item=(usize,usize,usize)>) -> BonePhysics{
+        let mut physics = BonePhysics::empty(WorldSize::new(1, 1));
+        for (x,y,z) in stone{
+            physics.set_block_id_at(x, y, z, STONE);
+        }
+        physics
+    }
+
+    /**Unit cube bone moving with the given velocity*/
+    fn bone(center:glm::Vec3, velocity:glm::Vec3) -> Bone{
+        let mut bone = Bone::limb(center, 0.5, 0.5, 1., u32::MAX, glm::vec3(0., 0., 0.));
+        bone.set_old_center(center - velocity);
+        bone
+    }
+
+    #[test]
+    fn falling_bone_stops_on_solid_layer(){
+        let physics = world((0..16).flat_map(|x|(0..16).map(move |z|(x, 10, z))));
+        let mut b = bone(glm::vec3(8.5, 14.5, 8.5), glm::vec3(0., 0., 0.));
+        for _ in 0..1000{
+            physics.update_bone(&mut b);
+            assert!(b.new_center().y - b.half_height() >= 11., "{}", b.new_center().y);
+        }
+        assert!(b.new_center().y - b.half_height() < 11.01, "{}", b.new_center().y);
+        assert!(b.velocity().y.abs() < 0.002, "{}", b.velocity().y);
+    }
+
+    #[test]
+    fn bone_pushed_into_wall_keeps_tangential_velocity(){
+        // the bone slides on the floor, so that only the wall can stop it
+        let floor = (0..16).flat_map(|x|(0..16).map(move |z|(x, 19, z)));
+        let wall = (20..256).flat_map(|y|(0..16).map(move |z|(8, y, z)));
+        let physics = world(floor.chain(wall));
+        let mut b = bone(glm::vec3(7.4, 20.5, 8.5), glm::vec3(0.2, 0., 0.05));
+        physics.update_bone(&mut b);
+        let velocity = b.velocity();
+        assert_eq!(velocity.x, 0.);
+        assert_eq!(b.new_center().x, 7.4);
+        assert!((velocity.z - 0.05 * DAMPING_COEFFICIENT * BLOCK_COLLISION_FRICTION).abs() < 1e-6, "{}", velocity.z);
+        assert!(b.impulse().x < 0.);
+        for _ in 0..100{
+            physics.update_bone(&mut b);
+            assert!(b.new_center().x + b.half_side_length() <= 8.);
+        }
+    }
+
+    #[test]
+    fn diagonal_move_into_corner_stops_both_axes(){
+        // the stone touches the hitbox only with its edge, so neither of the faces in front of the hitbox sees it
+        let physics = world(vec![(7, 19, 8)]);
+        let center = glm::vec3(8.55, 20.55, 8.5);
+        let mut b = bone(center, glm::vec3(-0.1, -0.1, 0.));
+        physics.update_bone(&mut b);
+        assert_eq!(b.new_center(), center);
+        assert_eq!(b.velocity(), glm::vec3(0., 0., 0.));
+        let mut free = bone(center, glm::vec3(-0.1, -0.1, 0.));
+        world(vec![]).update_bone(&mut free);
+        assert!(free.velocity().x < 0. && free.velocity().y < 0.);
+    }
+
+    #[test]
+    fn bones_beyond_cell_capacity_are_ignored(){
+        let mut grid = CollisionGrid::new(WorldSize::new(1, 1));
+        let extra = 2;
+        let mut bones = vec![bone(glm::vec3(8.5, 20.5, 8.5), glm::vec3(0., 0., 0.)); BROAD_PHASE_CELL_CAPACITY + extra];
+        grid.broad_phase(&bones);
+        assert_eq!(grid.overflows(), extra as u32);
+        let cell_idx = grid.cell_pos_to_cell_idx(CollisionGrid::position_to_cell_pos(&bones[0].new_center()));
+        let cell = grid.cells()[cell_idx];
+        assert_eq!(cell.len as usize, bones.len());
+        assert_eq!(cell.bones(), (0..BROAD_PHASE_CELL_CAPACITY as u32).collect::<Vec<_>>().as_slice());
+        grid.narrow_phase(&mut bones);
+        // the dropped bones overlap all the others, yet they are neither pushed nor push anyone
+        for b in &bones[BROAD_PHASE_CELL_CAPACITY..]{
+            assert_eq!(b.impulse(), glm::vec3(0., 0., 0.));
+        }
+        let total = bones.iter().fold(glm::vec3(0., 0., 0.), |sum, b| sum + b.impulse());
+        assert!(glm::length(&total) < 1e-6, "{:?}", total);
+        // overflows accumulate over steps until they are taken
+        grid.clear();
+        grid.broad_phase(&bones);
+        assert_eq!(grid.take_overflows(), 2 * extra as u32);
+        assert_eq!(grid.overflows(), 0);
+    }
+
+    #[test]
+    fn overlapping_bones_get_equal_and_opposite_impulses(){
+        let mut grid = CollisionGrid::new(WorldSize::new(1, 1));
+        let mut bones = vec![bone(glm::vec3(8.5, 20.5, 8.5), glm::vec3(0., 0., 0.)), bone(glm::vec3(9., 20.7, 8.8), glm::vec3(0., 0., 0.))];
+        grid.broad_phase(&bones);
+        grid.narrow_phase(&mut bones);
+        let (first, second) = (bones[0].impulse(), bones[1].impulse());
+        // overlap of the two hitboxes, pointing from the second bone towards the first one
+        let penetration = glm::vec3(-0.5, -0.8, -0.7);
+        let expected = penetration * glm::dot(&penetration, &penetration) * BONE_COLLISION_FORCE_PER_AREA_UNIT * 0.5;
+        assert!(glm::length(&(first - expected)) < 1e-6, "{:?}", first);
+        assert_eq!(first, -second);
+        // bones that don't overlap are left alone
+        let mut apart = vec![bone(glm::vec3(8.5, 20.5, 8.5), glm::vec3(0., 0., 0.)), bone(glm::vec3(9.6, 20.5, 8.5), glm::vec3(0., 0., 0.))];
+        grid.clear();
+        grid.broad_phase(&apart);
+        grid.narrow_phase(&mut apart);
+        assert_eq!((apart[0].impulse(), apart[1].impulse()), (glm::vec3(0., 0., 0.), glm::vec3(0., 0., 0.)));
+    }
+}
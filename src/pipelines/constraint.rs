@@ -1,5 +1,21 @@
-use crate::blocks::world_size::PARTICLE_DIAMETER;
+use crate::pipelines::bone::Bone;
+use crate::neat::body::constraint_impulse;
 
+/**Keeps the distance between two bones equal to constant_param1. The joint can rotate freely*/
+pub const CONSTRAINT_TYPE_DISTANCE:u32 = 0;
+/**Same as CONSTRAINT_TYPE_DISTANCE but additionally the offset between the bones can't have any component along
+the hinge axis held in constant_param. The axis is expressed in the frame of the root bone of the body*/
+pub const CONSTRAINT_TYPE_HINGE:u32 = 1;
+/**Pulls the bones towards the target length chosen by a Muscle. constant_param1 holds the rest length*/
+pub const CONSTRAINT_TYPE_MUSCLE:u32 = 2;
+/**Keeps the distance between two bones at most constant_param1. Slack ropes don't act at all*/
+pub const CONSTRAINT_TYPE_ROPE:u32 = 3;
+/**Pushes or pulls the bones towards the distance constant_param1 with impulse proportional to the stretch,
+so the bones may overshoot and oscillate*/
+pub const CONSTRAINT_TYPE_SPRING:u32 = 4;
+
+/**Constraints of bodies are solved by update_ann_entities.comp (see Body::apply_joints). Constraints between
+arbitrary bones of the world (rods, ropes and springs) are solved by solve_constraints.comp (see solve_constraints)*/
 #[derive(Copy, Clone, Debug)]
 #[repr(C, packed)]
 pub struct Constraint {
@@ -12,12 +28,176 @@ pub struct Constraint {
 
 }
 
-// impl Constraint{
-//     // pub fn collision(particle1:u32, particle2:u32)->Self{
-//     //     Self{particle1,particle2,constant_param:-PARTICLE_DIAMETER}
-//     // }
-//     pub fn distance(stiffness:f32, particle1:u32, particle2:u32, dist:f32)->Self{
-//         debug_assert!(dist>0f32);
-//         Self{stiffness,particle1,particle2,constant_param:dist}
-//     }
-// }
+impl Constraint{
+    pub fn distance(stiffness:f32, this_bone_idx:u32, other_bone_idx:u32, dist:f32)->Self{
+        debug_assert!(dist>0f32);
+        Self{constant_param:glm::vec3(0.,0.,0.),constant_param1:dist,constraint_type:CONSTRAINT_TYPE_DISTANCE,stiffness,this_bone_idx,other_bone_idx}
+    }
+    pub fn hinge(stiffness:f32, this_bone_idx:u32, other_bone_idx:u32, dist:f32, axis:glm::Vec3)->Self{
+        debug_assert!(dist>0f32);
+        Self{constant_param:glm::normalize(&axis),constant_param1:dist,constraint_type:CONSTRAINT_TYPE_HINGE,stiffness,this_bone_idx,other_bone_idx}
+    }
+    pub fn muscle(stiffness:f32, this_bone_idx:u32, other_bone_idx:u32, rest_length:f32)->Self{
+        Self{constant_param:glm::vec3(0.,0.,0.),constant_param1:rest_length,constraint_type:CONSTRAINT_TYPE_MUSCLE,stiffness,this_bone_idx,other_bone_idx}
+    }
+    pub fn rope(stiffness:f32, this_bone_idx:u32, other_bone_idx:u32, max_length:f32)->Self{
+        Self{constant_param:glm::vec3(0.,0.,0.),constant_param1:max_length,constraint_type:CONSTRAINT_TYPE_ROPE,stiffness,this_bone_idx,other_bone_idx}
+    }
+    pub fn spring(stiffness:f32, this_bone_idx:u32, other_bone_idx:u32, rest_length:f32)->Self{
+        Self{constant_param:glm::vec3(0.,0.,0.),constant_param1:rest_length,constraint_type:CONSTRAINT_TYPE_SPRING,stiffness,this_bone_idx,other_bone_idx}
+    }
+}
+
+/**Stiffness of ropes attached by the player*/
+pub const ROPE_STIFFNESS:f32 = 0.9;
+/**Number of iterations of solve_constraints.comp per simulation step, unless overridden by
+EVOLUTIO_CONSTRAINT_ITERATIONS. Same as CONSTRAINT_SOLVER_ITERATIONS in constants.comp*/
+pub const DEFAULT_CONSTRAINT_SOLVER_ITERATIONS:u32 = 4;
+
+/**Reads the number of solver iterations from EVOLUTIO_CONSTRAINT_ITERATIONS*/
+pub fn constraint_solver_iterations_from_env()->Result<u32,failure::Error>{
+    match std::env::var("EVOLUTIO_CONSTRAINT_ITERATIONS"){
+        Ok(iterations) => match iterations.parse(){
+            Ok(iterations) if iterations > 0 => Ok(iterations),
+            _ => Err(failure::err_msg(format!("EVOLUTIO_CONSTRAINT_ITERATIONS={} is not a positive integer", iterations)))
+        },
+        Err(_) => Ok(DEFAULT_CONSTRAINT_SOLVER_ITERATIONS)
+    }
+}
+
+/**Fraction of the error corrected by every iteration, chosen so that after all iterations the error
+shrinks by the same factor no matter how many iterations there are. Same as stiffness_per_iteration in constants.comp*/
+pub fn stiffness_per_iteration(stiffness:f32, iterations:u32)->f32{
+    1. - (1. - stiffness.min(1.).max(0.)).powf(1. / iterations as f32)
+}
+
+/**Correction of a rod or rope constraint. The "this" bone is displaced by the correction times its weight
+and the "other" bone by the opposite of correction times its weight. Weights are inverse masses.
+Offset is measured from the other bone to this bone. Mirrors position_correction in constants.comp*/
+pub fn position_correction(c:&Constraint, offset:glm::Vec3, total_weight:f32, stiffness:f32)->glm::Vec3{
+    let length = glm::length(&offset);
+    if length <= 0.0001 || total_weight <= 0. || (c.constraint_type == CONSTRAINT_TYPE_ROPE && length <= c.constant_param1){
+        return glm::vec3(0.,0.,0.)
+    }
+    -offset * ((length - c.constant_param1) / length * stiffness / total_weight)
+}
+
+/**CPU reference of solve_constraints.comp, which runs right after update_bones.comp. Rods and ropes move new_center
+of both bones (and thereby their velocity). Springs add a fraction of their impulse (the same impulse as muscles of bodies,
+see constraint_impulse in body.rs) in every iteration, so the whole impulse is added once per step no matter how many
+iterations there are. It takes effect in the next step. All constraints of one iteration see the positions from the end
+of the previous iteration, whereas on the GPU constraints of one iteration run in parallel and may already see some
+corrections of the same iteration*/
+pub fn solve_constraints(constraints:&[Constraint], bones:&mut [Bone], iterations:u32){
+    for _ in 0..iterations{
+        let mut corrections = vec![glm::vec3(0.,0.,0.); bones.len()];
+        for c in constraints{
+            let (this_idx, other_idx) = (c.this_bone_idx as usize, c.other_bone_idx as usize);
+            let offset = bones[this_idx].new_center() - bones[other_idx].new_center();
+            if c.constraint_type == CONSTRAINT_TYPE_SPRING{
+                let relative_velocity = bones[this_idx].velocity() - bones[other_idx].velocity();
+                let impulse = constraint_impulse(c, offset, relative_velocity, c.constant_param1, &glm::identity()) / iterations as f32;
+                bones[this_idx].add_impulse(impulse);
+                bones[other_idx].add_impulse(-impulse);
+            }else{
+                let this_weight = 1. / bones[this_idx].mass();
+                let other_weight = 1. / bones[other_idx].mass();
+                let correction = position_correction(c, offset, this_weight + other_weight, stiffness_per_iteration(c.stiffness, iterations));
+                corrections[this_idx] += correction * this_weight;
+                corrections[other_idx] -= correction * other_weight;
+            }
+        }
+        for (bone, correction) in bones.iter_mut().zip(corrections){
+            bone.set_new_center(bone.new_center() + correction);
+        }
+    }
+}
+
+/**Bone at index last was moved into the slot of the removed bone_idx. Constraints of the removed bone are dropped
+(by moving the last constraint into their slot) and the ones of the moved bone follow it.
+Mirrors remove_bone_from_constraints in update_ann_entities_lifecycle.comp*/
+pub fn remove_bone_from_constraints(constraints:&mut Vec<Constraint>, bone_idx:u32, last:u32){
+    let mut i = 0;
+    while i < constraints.len(){
+        let c = &mut constraints[i];
+        if c.this_bone_idx == bone_idx || c.other_bone_idx == bone_idx{
+            constraints.swap_remove(i);
+        }else{
+            if c.this_bone_idx == last{
+                c.this_bone_idx = bone_idx;
+            }
+            if c.other_bone_idx == last{
+                c.other_bone_idx = bone_idx;
+            }
+            i += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    /**Two resting bones on the x axis, the "this" bone at x and the "other" bone at the origin*/
+    fn pair(x:f32, this_mass:f32, other_mass:f32)->Vec<Bone>{
+        vec![
+            Bone::limb(glm::vec3(x, 0., 0.), 0.5, 0.5, this_mass, u32::MAX, glm::vec3(0., 0., 0.)),
+            Bone::limb(glm::vec3(0., 0., 0.), 0.5, 0.5, other_mass, u32::MAX, glm::vec3(0., 0., 0.)),
+        ]
+    }
+
+    fn length(bones:&[Bone])->f32{
+        glm::length(&(bones[0].new_center() - bones[1].new_center()))
+    }
+
+    fn assert_close(a:f32, b:f32){
+        assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn rod_keeps_its_rest_length(){
+        for &iterations in &[1, 4]{
+            let mut bones = pair(3., 1., 3.);
+            let rod = [Constraint::distance(0.9, 0, 1, 2.)];
+            solve_constraints(&rod, &mut bones, iterations);
+            // the same fraction of the error is left no matter how many iterations there are
+            assert_close(length(&bones), 2. + 0.1);
+            for _ in 0..10{
+                solve_constraints(&rod, &mut bones, iterations);
+            }
+            assert_close(length(&bones), 2.);
+            // the heavier bone moves three times less, so the center of mass stays in place
+            assert_close(bones[0].new_center().x + 3. * bones[1].new_center().x, 3.);
+        }
+        let mut squeezed = pair(1., 1., 1.);
+        solve_constraints(&[Constraint::distance(1., 0, 1, 2.)], &mut squeezed, 4);
+        assert_close(length(&squeezed), 2.);
+    }
+
+    #[test]
+    fn rope_is_slack_below_its_length_and_taut_above_it(){
+        let rope = [Constraint::rope(1., 0, 1, 2.)];
+        let mut slack = pair(1.5, 1., 1.);
+        solve_constraints(&rope, &mut slack, 4);
+        assert_eq!((slack[0].new_center().x, slack[1].new_center().x), (1.5, 0.));
+        let mut taut = pair(3., 1., 1.);
+        solve_constraints(&rope, &mut taut, 4);
+        assert_close(length(&taut), 2.);
+        assert_close(taut[0].new_center().x, 2.5);
+        assert_close(taut[1].new_center().x, 0.5);
+    }
+
+    #[test]
+    fn spring_force_is_proportional_to_extension(){
+        let stiffness = 0.05;
+        let spring = [Constraint::spring(stiffness, 0, 1, 2.)];
+        for &(x, extension) in &[(3., 1.), (4., 2.), (1.5, -0.5)]{
+            let mut bones = pair(x, 1., 1.);
+            solve_constraints(&spring, &mut bones, 4);
+            // springs only push, they never move the bones directly
+            assert_eq!((bones[0].new_center().x, bones[1].new_center().x), (x, 0.));
+            assert_close(bones[0].impulse().x, -stiffness * extension);
+            assert_close(bones[1].impulse().x, stiffness * extension);
+        }
+    }
+}
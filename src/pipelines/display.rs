@@ -32,6 +32,12 @@ use crate::pipelines::computable::{Computable, ComputeResources};
 use std::sync::Arc;
 use crate::render::fence::Fence;
 use crate::pipelines::world_generation::WorldGeneratorInitializer;
+use crate::neat::brain_file::SavedBrain;
+use crate::pipelines::lifecycle_record::LifecycleRecord;
+use crate::pipelines::global_mutables::GlobalMutables;
+use crate::render::submitter::download_ranges;
+use crate::metrics::{MetricsSample, RunHeader};
+use crate::blocks::Block;
 
 
 pub struct Display<P: RenderResources, C:ComputeResources, A:ComputeResources>{
@@ -203,19 +209,67 @@ impl <P: RenderResources, C:ComputeResources, A:ComputeResources> Display<P,C,A>
         Ok(())
     }
 
+    /**Waits until the previous simulation step is finished and then submits the next one. Can be called
+    several times per frame (see PhysicsTimer). The wait can't be skipped, because the player event uniform is a single
+    host visible buffer that update_uniforms rewrites before every step, while the previous step may still be reading it.
+    Skipping the step instead (as long as the fence isn't signaled) would silently drop steps counted by PhysicsTimer.
+    Steps run one after another on the compute queue anyway, so the wait only stalls the render thread
+    for as long as the GPU needs to finish the previous step*/
     pub fn compute(&mut self, player:&mut Player) -> Result<(), failure::Error> {
         let Self{ compute_command_buffer, foundations,compute_pipeline
             , compute_background_command_buffer,compute_background_pipeline ,compute_fence
             , vulkan, .. } = self;
-        if compute_fence.is_signaled()? {
-            compute_fence.reset()?;
-            compute_pipeline.update_uniforms( player, foundations);
-            compute_command_buffer.submit(&[], &[], Some(compute_fence))?;
-            compute_background_pipeline.update_uniforms(player, foundations);
-            compute_background_command_buffer.submit(&[], &[], None)?;
-        }
+        compute_fence.wait(None)?;
+        compute_fence.reset()?;
+        compute_pipeline.update_uniforms( player, foundations);
+        compute_command_buffer.submit(&[], &[], Some(compute_fence))?;
+        compute_background_pipeline.update_uniforms(player, foundations);
+        compute_background_command_buffer.submit(&[], &[], None)?;
         Ok(())
     }
+    /**Waits until GPU is idle and then reads back all ANN entities together with their bones*/
+    pub fn download_brains(&mut self) -> Result<Vec<SavedBrain>, failure::Error> {
+        self.device().device_wait_idle()?;
+        self.foundations.download_brains(&self.compute_cmd_pool)
+    }
+    /**Waits until GPU is idle and then places the brains into free entity slots. Returns the number of injected brains*/
+    pub fn inject_brains(&mut self, brains:&[SavedBrain]) -> Result<usize, failure::Error> {
+        self.device().device_wait_idle()?;
+        self.foundations.inject_brains(&self.compute_cmd_pool, brains)
+    }
+    /**Waits until GPU is idle and then reads back (and clears) all births and deaths recorded since the last call.
+    The second value is the number of records that were lost because the buffer was full. Global mutables are read back
+    behind the same wait and if is_due returns true for their tick, metrics are sampled as well (see Foundations::sample_metrics)*/
+    pub fn drain_lifecycle_events(&mut self, is_due:impl FnOnce(i32)->bool) -> Result<(Vec<LifecycleRecord>, u32, GlobalMutables, Option<MetricsSample>), failure::Error> {
+        self.device().device_wait_idle()?;
+        let (records, lost, mutables) = self.foundations.drain_lifecycle_events(&self.compute_cmd_pool)?;
+        let sample = if is_due(mutables.tick) { Some(self.foundations.sample_metrics(&self.compute_cmd_pool)?) } else { None };
+        Ok((records, lost, mutables, sample))
+    }
+    /**Waits until GPU is idle and then stores the brain as the template of entities spawned by the player*/
+    pub fn set_spawn_template(&mut self, brain:&SavedBrain) -> Result<(), failure::Error> {
+        self.device().device_wait_idle()?;
+        self.foundations.set_spawn_template(&self.compute_cmd_pool, brain)
+    }
+    pub fn has_spawn_template(&self) -> bool {
+        self.foundations.has_spawn_template()
+    }
+    /**Waits until GPU is idle and then reads back the box of blocks from (inclusive) to (exclusive), ordered by y, then z, then x.
+    Every row of the box along x is a separate copy region, so only the blocks around the player travel over the bus*/
+    pub fn download_world_box(&mut self, from:[usize;3], to:[usize;3]) -> Result<Vec<Block>, failure::Error> {
+        self.device().device_wait_idle()?;
+        let world_size = *self.foundations.world_size();
+        let mut ranges = Vec::with_capacity((to[1] - from[1]) * (to[2] - from[2]));
+        for y in from[1]..to[1]{
+            for z in from[2]..to[2]{
+                ranges.push((world_size.block_pos_into_world_idx(from[0], y, z) as u64, (to[0] - from[0]) as u64));
+            }
+        }
+        Ok(download_ranges(&self.compute_cmd_pool, self.foundations.world(), &ranges)?)
+    }
+    pub fn run_header(&self) -> RunHeader {
+        self.foundations.run_header()
+    }
     pub fn render(&mut self, _rerecord_cmd:bool, player:&mut Player) -> Result<bool, failure::Error> {
         let Self{ graphics_command_buffers: command_buffers, graphics_pipeline, render_pass,foundations: _, vulkan,descriptors, uniforms_binding, .. } = self;
         let fence = vulkan.frames_in_flight().current_fence();
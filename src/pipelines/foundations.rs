@@ -1,5 +1,6 @@
 use crate::render::stage_buffer::{StageBuffer, StageSubBuffer, IndirectDispatchSubBuffer, IndirectSubBuffer};
-use crate::pipelines::particle::Particle;
+use crate::pipelines::particle::{Particle, PARTICLE_DAMPING, PARTICLE_DECAY, PARTICLE_GRAVITY, PARTICLE_DEBRIS_SPEED, PARTICLE_TRAIL_MIN_SPEED};
+use crate::pipelines::bone_physics::{CollisionCell, BONE_COLLISION_FORCE_PER_AREA_UNIT, BLOCK_COLLISION_FRICTION, PHYSICS_SIMULATION_DELTA_TIME_PER_STEP, GRAVITY, DAMPING_COEFFICIENT, BLOCK_RIGIDITY, HOLD_STIFFNESS, HOLD_DAMPING};
 use crate::render::command_pool::{CommandPool};
 
 
@@ -9,16 +10,18 @@ use ash::vk;
 use failure::Error;
 
 
-use crate::render::submitter::{Submitter, fill_submit, fill_zeros_submit};
+use crate::render::submitter::{Submitter, fill_submit, fill_zeros_submit, download, upload};
 
 use crate::render::buffer_type::{Cpu, Storage, GpuIndirect, Uniform};
 
-use crate::blocks::world_size::{CHUNK_VOLUME_IN_CELLS, CHUNK_WIDTH, CHUNK_DEPTH, BROAD_PHASE_CHUNK_VOLUME_IN_CELLS, BROAD_PHASE_CELL_CAPACITY};
+use crate::blocks::world_size::{CHUNK_VOLUME_IN_CELLS, CHUNK_WIDTH, CHUNK_DEPTH, BROAD_PHASE_CHUNK_VOLUME_IN_CELLS};
 use crate::render::subbuffer::SubBuffer;
-use crate::pipelines::constraint::Constraint;
+use crate::pipelines::constraint::{Constraint, ROPE_STIFFNESS, constraint_solver_iterations_from_env};
 use crate::render::buffer::Buffer;
 use crate::pipelines::global_mutables::{GlobalMutables};
 use crate::blocks::{WorldSize, Block, Face, BlockMeta};
+use crate::blocks::fluid::{WATER_FLOW_PERIOD, LAVA_FLOW_PERIOD, WATER_SPREAD, LAVA_SPREAD};
+use crate::blocks::climate::{CLIMATE_PERIOD, LAVA_TEMPERATURE, WATER_HUMIDITY, SNOW_MELTING_TEMPERATURE};
 use crate::render::sampler::Sampler;
 use crate::pipelines::bone::Bone;
 use crate::blocks::block_properties::{BLOCKS, BlockProp, BEDROCK, DIRT, GRASS, GLASS, PLANK, AIR, STONE, WATER};
@@ -34,9 +37,14 @@ use crate::render::host_buffer::HostBuffer;
 use crate::pipelines::player_event::PlayerEvent;
 use crate::render::compute::{ComputeDescriptorsBuilder, ComputeDescriptors};
 use crate::render::specialization_constants::SpecializationConstants;
-use crate::neat::htm_entity::{HtmEntity, ENTITY_MAX_LIDAR_COUNT};
+use crate::neat::htm_entity::HtmEntity;
 use std::time::{UNIX_EPOCH, SystemTime};
-use crate::neat::ann_entity::AnnEntity;
+use crate::neat::ann_entity::{AnnEntity, AnnConfig, ANN_CONFIG_NAMES};
+use crate::metrics::{MetricsSample, RunHeader};
+use crate::neat::ecology;
+use crate::neat::brain_file::{SavedBrain, collect_brains};
+use crate::neat::body::Body;
+use crate::pipelines::lifecycle_record::{LifecycleRecord, NO_ENTITY_ID};
 
 pub struct Indirect {
     per_particle: SubBuffer<vk::DispatchIndirectCommand, GpuIndirect>,
@@ -46,6 +54,7 @@ pub struct Indirect {
     per_blocks_to_be_inserted_or_removed: SubBuffer<vk::DispatchIndirectCommand, GpuIndirect>,
     per_htm_entity: SubBuffer<vk::DispatchIndirectCommand, GpuIndirect>,
     per_ann_entity: SubBuffer<vk::DispatchIndirectCommand, GpuIndirect>,
+    per_constraint: SubBuffer<vk::DispatchIndirectCommand, GpuIndirect>,
     draw_bones: SubBuffer<vk::DrawIndirectCommand, GpuIndirect>,
     draw_blocks: SubBuffer<vk::DrawIndirectCommand, GpuIndirect>,
     draw_particles: SubBuffer<vk::DrawIndirectCommand, GpuIndirect>,
@@ -61,6 +70,7 @@ impl Indirect {
         let per_blocks_to_be_inserted_or_removed = indirect_dispatch.gpu().element(4);
         let per_htm_entity = indirect_dispatch.gpu().element(5);
         let per_ann_entity = indirect_dispatch.gpu().element(6);
+        let per_constraint = indirect_dispatch.gpu().element(7);
 
         let draw_bones = indirect_draw.gpu().element(0);
         let draw_blocks = indirect_draw.gpu().element(1);
@@ -68,6 +78,7 @@ impl Indirect {
         Self {
             per_htm_entity,
             per_ann_entity,
+            per_constraint,
             super_indirect_buffer,
             per_particle,
             per_bone,
@@ -85,6 +96,9 @@ impl Indirect {
     pub fn update_ann_entities(&self)->&SubBuffer<vk::DispatchIndirectCommand, GpuIndirect>{
         &self.per_ann_entity
     }
+    pub fn solve_constraints(&self)->&SubBuffer<vk::DispatchIndirectCommand, GpuIndirect>{
+        &self.per_constraint
+    }
     pub fn update_ambience(&self)->&SubBuffer<vk::DispatchIndirectCommand, GpuIndirect>{
         &self.update_ambience
     }
@@ -134,7 +148,7 @@ pub struct FoundationInitializer {
     rand_uint: SubBuffer<u32, Storage>,
     face_count_per_chunk_buffer: SubBuffer<Face, Storage>,
     htm_entities_buffer: SubBuffer<HtmEntity, Storage>,
-    ann_entities_buffer: SubBuffer<AnnEntity, Storage>,
+    ann_entities_buffer: SubBuffer<u32, Storage>,
     opaque_and_transparent_face_buffer: SubBuffer<Face, Storage>,
     tmp_faces_copy: Submitter<SubBuffer<u32, Storage>>,
     blocks_to_be_inserted_or_removed: SubBuffer<u32, Storage>,
@@ -143,6 +157,12 @@ pub struct FoundationInitializer {
     player_event_uniform: HostBuffer<PlayerEvent, Uniform>,
     collision_grid: SubBuffer<u32, Storage>,
     bones: SubBuffer<Bone, Storage>,
+    body_bones: Submitter<StageSubBuffer<Bone, Cpu, Storage>>,
+    body_constraints: Submitter<StageSubBuffer<Constraint, Cpu, Storage>>,
+    body_muscles: Submitter<StageSubBuffer<Muscle, Cpu, Storage>>,
+    lifecycle_events: SubBuffer<LifecycleRecord, Storage>,
+    constraints: SubBuffer<Constraint, Storage>,
+    world_blocks_to_update: SubBuffer<u32, Storage>,
     default_global_mutables:GlobalMutables,
     particles: SubBuffer<Particle, Storage>,
     global_mutables: Submitter<StageSubBuffer<GlobalMutables, Cpu, Storage>>,
@@ -168,17 +188,37 @@ pub struct FoundationsCapacity {
     pub max_htm_entities: u64,
     pub max_particles: u64,
     pub max_ann_entities: u64,
+    /**Births and deaths that can be recorded between two calls to Foundations::drain_lifecycle_events*/
+    pub max_lifecycle_events: u64,
+    /**Rods, ropes and springs between bones of the world (not the joints of bodies)*/
+    pub max_constraints: u64,
+    /**Capacity of each of the two halves of world_blocks_to_update (see GlobalMutables::world_blocks_to_update)*/
+    pub max_world_blocks_to_update: u64,
+    /**Read from EVOLUTIO_CONSTRAINT_ITERATIONS, see constraint_solver_iterations_from_env*/
+    pub constraint_solver_iterations: u32,
+    pub ann_config: AnnConfig,
+    /**Every ANN entity has this body. Its limbs take up additional bones*/
+    pub body: Body,
+    /**Seed of all random values generated on GPU. Read from EVOLUTIO_SEED environment variable, defaults to the current time*/
+    pub seed: u32,
 }
 
 impl FoundationsCapacity {
-    pub fn new(x: usize, z: usize) -> Self {
+    pub fn new(x: usize, z: usize) -> Result<Self, failure::Error> {
+        let body = Body::from_env()?;
         let world_size = WorldSize::new(x, z);
         let faces_to_be_inserted_chunk_capacity = 128;
         let faces_to_be_removed_chunk_capacity = 128;
-        Self {
+        let max_htm_entities = 128u64;
+        let max_ann_entities = 2048u64;
+        let seed = match std::env::var("EVOLUTIO_SEED") {
+            Ok(seed) => seed.parse().map_err(|_| failure::err_msg(format!("EVOLUTIO_SEED={} is not a valid u32", seed)))?,
+            Err(_) => SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_millis() as u32
+        };
+        Ok(Self {
             faces_to_be_inserted_chunk_capacity,
             faces_to_be_removed_chunk_capacity,
-            max_bones: 1024u64,
+            max_bones: max_ann_entities * (1 + body.limbs() as u64) + max_htm_entities,
             max_faces: 16 * 1024u64 * world_size.total_chunks() as u64,
             max_rand_uint: 64 * 1024u64, // used as backing memory for vectors, matrices and
             // tensors that make up various neural networks. Especially, the outputs of recursive neural networks
@@ -188,22 +228,25 @@ impl FoundationsCapacity {
             max_faces_to_be_inserted: faces_to_be_inserted_chunk_capacity as u64 * 2 * world_size.total_chunks() as u64,
             max_faces_to_be_removed: faces_to_be_removed_chunk_capacity as u64 * 2 * world_size.total_chunks() as u64,
             max_sensors: 0u64,
-            max_htm_entities: 128u64,
-            max_ann_entities: 2048u64,
+            max_htm_entities,
+            max_ann_entities,
+            max_lifecycle_events: 2 * max_ann_entities,
+            max_constraints: 1024,
+            max_world_blocks_to_update: world_size.world_volume() as u64 / 16,
+            constraint_solver_iterations: constraint_solver_iterations_from_env()?,
+            ann_config: AnnConfig::from_env().with_body(&body),
             max_faces_copy: 1024u64 * world_size.total_chunks() as u64,
-            max_particles: 1024u64,
+            max_particles: max_ann_entities * 4,// signal particles decay within 1/PARTICLE_DECAY steps, so an entity rarely has more of them alive. Sensing scans all of them for every entity (see MAX_PARTICLES in constants.comp)
             world_size,
-        }
+            body,
+            seed,
+        })
     }
     fn grid_size(&self) -> u64 {
         (self.world_size.total_chunks() * BROAD_PHASE_CHUNK_VOLUME_IN_CELLS) as u64
     }
 }
 
-struct CollisionCell{
-    len:u32,
-    contents:[u32;BROAD_PHASE_CELL_CAPACITY]
-}
 fn append_owned<X>(v: &mut Vec<X>, mut v2: Vec<X>) {
     v.append(&mut v2);
 }
@@ -254,32 +297,64 @@ impl FoundationInitializer {
     }
 
     pub fn new(cmd_pool: &CommandPool) -> Result<Self, failure::Error> {
-        let cap = FoundationsCapacity::new(16,16);
+        let cap = FoundationsCapacity::new(16,16)?;
+        cap.ann_config.validate()?;
+        cap.ann_config.validate_body(&cap.body)?;
         let entity_count = 8*cap.world_size.total_chunks() as u32;
+        let htm_entity_count = cap.max_htm_entities as u32 / 2;
         let mutables =  GlobalMutables {
             blocks_to_be_inserted_or_removed: 0,
-            bones: entity_count,
+            bones: entity_count * (1 + cap.body.limbs() as u32) + htm_entity_count,
             particles: 0,
-            held_bone_idx: 0,
-            htm_entities: 0,
+            held_bone_idx: -1,
+            htm_entities: htm_entity_count,
             tick: 0,
             lidars: 0,
             ann_entities: entity_count,
+            next_entity_id: entity_count,
+            lifecycle_events: entity_count,// births of the initial entities are recorded by generate_world_agents.comp
+            agent_block_edits: 0,
+            collision_grid_overflows: 0,
+            constraints: 0,
+            rope_anchor_bone_idx: -1,
+            world_blocks_to_update: [0, 0],
+            world_blocks_to_update_even: 0,
+            thrown_bone_idx: -1,
         };
         assert!(cap.max_ann_entities>=entity_count as u64, "{} >= {}", cap.max_ann_entities,entity_count);
+        assert!(cap.max_lifecycle_events>=entity_count as u64, "{} >= {}", cap.max_lifecycle_events,entity_count);
+        let bones = mutables.bones;
+        assert!(cap.max_bones>=bones as u64, "{} >= {}", cap.max_bones,bones);
         let bones_in_bytes = std::mem::size_of::<Bone>() as u64 * cap.max_bones;
         let faces_in_bytes = std::mem::size_of::<Face>() as u64 * cap.max_faces;
         let tmp_faces_copy_in_bytes = std::mem::size_of::<u32>() as u64 * 3 * cap.max_faces_copy;
         let grid_in_bytes = std::mem::size_of::<CollisionCell>() as u64 * cap.grid_size();
         let world_in_bytes = (std::mem::size_of::<Block>() * cap.world_size.world_volume()) as u64;
         let blocks_to_be_inserted_or_removed_in_bytes = std::mem::size_of::<u32>() as u64 * cap.max_blocks_to_be_inserted_or_removed;
-        let global_mutables_in_bytes = std::mem::size_of_val(&mutables) as u64;
+        let global_mutables_in_bytes = (std::mem::size_of_val(&mutables) as u64 + 15) / 16 * 16;//padded to keep alignment of the next buffer
         let rand_uint_in_bytes = std::mem::size_of::<f32>() as u64 * cap.max_rand_uint;
         let htm_entities_in_bytes = std::mem::size_of::<HtmEntity>() as u64 * cap.max_htm_entities;
-        let ann_entities_in_bytes = std::mem::size_of::<AnnEntity>() as u64 * cap.max_ann_entities;
+        // The extra slot at index max_ann_entities holds the template of entities spawned by the player (see set_spawn_template)
+        let ann_entities_in_bytes = (cap.ann_config.entity_bytes() as u64 * (cap.max_ann_entities + 1) + 15) / 16 * 16;//entities are packed words, so the total must be padded to keep alignment of the next buffer
         let faces_to_be_inserted_in_bytes = std::mem::size_of::<Face>() as u64 * cap.max_faces_to_be_inserted;
         let faces_to_be_removed_in_bytes = std::mem::size_of::<u32>() as u64 * cap.max_faces_to_be_removed;
         let particles_in_bytes = std::mem::size_of::<Particle>() as u64 * cap.max_particles;
+        let lifecycle_events_in_bytes = std::mem::size_of::<LifecycleRecord>() as u64 * cap.max_lifecycle_events;
+        let constraints_in_bytes = std::mem::size_of::<Constraint>() as u64 * cap.max_constraints;
+        let world_blocks_to_update_in_bytes = std::mem::size_of::<u32>() as u64 * 2 * cap.max_world_blocks_to_update;
+        // Buffers can't be empty, so the body always provides at least one (dummy) constraint and muscle
+        let body_bones_data = cap.body.template_bones();
+        let mut body_constraints_data = cap.body.constraints();
+        if body_constraints_data.is_empty(){
+            body_constraints_data.push(Constraint::distance(0., 0, 0, 1.));
+        }
+        let mut body_muscles_data = cap.body.gpu_muscles();
+        if body_muscles_data.is_empty(){
+            body_muscles_data.push(Muscle::new(0, 1., 1.));
+        }
+        let body_bones_in_bytes = std::mem::size_of_val(body_bones_data.as_slice()) as u64;
+        let body_constraints_in_bytes = std::mem::size_of_val(body_constraints_data.as_slice()) as u64;
+        let body_muscles_in_bytes = (std::mem::size_of_val(body_muscles_data.as_slice()) as u64 + 15) / 16 * 16;//Muscle is 12 bytes long, so the total must be padded to keep alignment of the next buffer
 
         let super_buffer: SubBuffer<u8, Storage> = SubBuffer::with_capacity(cmd_pool.device(),
                                                                             bones_in_bytes +
@@ -294,6 +369,12 @@ impl FoundationInitializer {
                                                                                 faces_to_be_removed_in_bytes +
                                                                                 htm_entities_in_bytes +
                                                                                 ann_entities_in_bytes +
+                                                                                body_bones_in_bytes +
+                                                                                body_constraints_in_bytes +
+                                                                                body_muscles_in_bytes +
+                                                                                lifecycle_events_in_bytes +
+                                                                                constraints_in_bytes +
+                                                                                world_blocks_to_update_in_bytes +
                                                                                 rand_uint_in_bytes
         )?;
         let offset = 0;
@@ -315,7 +396,7 @@ impl FoundationInitializer {
         let blocks_to_be_inserted_or_removed_buffer = super_buffer.sub(offset..offset + blocks_to_be_inserted_or_removed_in_bytes).reinterpret_into::<u32>();
         let offset = offset + blocks_to_be_inserted_or_removed_in_bytes;
         assert_eq!(offset % 16, 0);
-        let global_mutables_buffer = super_buffer.sub(offset..offset + global_mutables_in_bytes).reinterpret_into::<GlobalMutables>();
+        let global_mutables_buffer = super_buffer.sub(offset..offset + std::mem::size_of_val(&mutables) as u64).reinterpret_into::<GlobalMutables>();
         let offset = offset + global_mutables_in_bytes;
         assert_eq!(offset % 16, 0);
         let particles_buffer = super_buffer.sub(offset..offset + particles_in_bytes).reinterpret_into::<Particle>();
@@ -330,9 +411,27 @@ impl FoundationInitializer {
         let htm_entities_buffer = super_buffer.sub(offset..offset + htm_entities_in_bytes).reinterpret_into::<HtmEntity>();
         let offset = offset + htm_entities_in_bytes;
         assert_eq!(offset % 16, 0);
-        let ann_entities_buffer = super_buffer.sub(offset..offset + ann_entities_in_bytes).reinterpret_into::<AnnEntity>();
+        let ann_entities_buffer = super_buffer.sub(offset..offset + ann_entities_in_bytes).reinterpret_into::<u32>();
         let offset = offset + ann_entities_in_bytes;
         assert_eq!(offset % 16, 0);
+        let body_bones_buffer = super_buffer.sub(offset..offset + body_bones_in_bytes).reinterpret_into::<Bone>();
+        let offset = offset + body_bones_in_bytes;
+        assert_eq!(offset % 16, 0);
+        let body_constraints_buffer = super_buffer.sub(offset..offset + body_constraints_in_bytes).reinterpret_into::<Constraint>();
+        let offset = offset + body_constraints_in_bytes;
+        assert_eq!(offset % 16, 0);
+        let body_muscles_buffer = super_buffer.sub(offset..offset + body_muscles_in_bytes).reinterpret_into::<Muscle>();
+        let offset = offset + body_muscles_in_bytes;
+        assert_eq!(offset % 16, 0);
+        let lifecycle_events_buffer = super_buffer.sub(offset..offset + lifecycle_events_in_bytes).reinterpret_into::<LifecycleRecord>();
+        let offset = offset + lifecycle_events_in_bytes;
+        assert_eq!(offset % 16, 0);
+        let constraints_buffer = super_buffer.sub(offset..offset + constraints_in_bytes).reinterpret_into::<Constraint>();
+        let offset = offset + constraints_in_bytes;
+        assert_eq!(offset % 16, 0);
+        let world_blocks_to_update_buffer = super_buffer.sub(offset..offset + world_blocks_to_update_in_bytes).reinterpret_into::<u32>();
+        let offset = offset + world_blocks_to_update_in_bytes;
+        assert_eq!(offset % 16, 0);
         let rand_uint_buffer = super_buffer.sub(offset..offset + rand_uint_in_bytes).reinterpret_into::<u32>();
         let offset = offset + rand_uint_in_bytes;
         assert_eq!(offset % 16, 0);
@@ -341,6 +440,9 @@ impl FoundationInitializer {
         fill_zeros_submit(&mut tmp_faces_copy)?;
 
         let global_mutables = StageBuffer::wrap(cmd_pool, &[mutables], global_mutables_buffer)?;
+        let body_bones = StageBuffer::wrap(cmd_pool, &body_bones_data, body_bones_buffer)?;
+        let body_constraints = StageBuffer::wrap(cmd_pool, &body_constraints_data, body_constraints_buffer)?;
+        let body_muscles = StageBuffer::wrap(cmd_pool, &body_muscles_data, body_muscles_buffer)?;
 
         let face_count_per_chunk_buffer = face_buffer.sub(..std::mem::size_of::<Face>() as u64 * cap.world_size.total_chunks() as u64 * 2);
         let opaque_and_transparent_face_buffer = face_buffer.sub(std::mem::size_of::<Face>() as u64 * cap.world_size.total_chunks() as u64 * 2..);
@@ -370,17 +472,18 @@ impl FoundationInitializer {
             dispatch_indirect(0, cmd_pool.device().get_max_subgroup_size()), // agent_sensory_input_update.comp
             dispatch_indirect(0, cmd_pool.device().get_max_subgroup_size()),// update_ambience.comp
             dispatch_indirect(0, cmd_pool.device().get_max_subgroup_size()),// update_ambience_faces.comp, update_ambience_flush_world_copy.comp
-            dispatch_indirect(0, cmd_pool.device().get_max_subgroup_size()),// update_htm_entities.comp
+            dispatch_indirect(mutables.htm_entities as usize, cmd_pool.device().get_max_subgroup_size()),// update_htm_entities.comp
             vk::DispatchIndirectCommand{
                 x: mutables.ann_entities,
                 y: 1,
                 z: 1
             },// update_ann_entities.comp
+            dispatch_indirect(0, cmd_pool.device().get_max_subgroup_size()),// solve_constraints.comp
         ];
         let indirect_draw_data = vec![
             draw_indirect(36, mutables.bones),// bones.vert
             draw_indirect(6, 0),// block.vert
-            draw_indirect(0, 0),// particles.vert
+            draw_indirect(0, 1),// particles.vert
         ];
         let indirect_dispatch_in_bytes = std::mem::size_of_val(indirect_dispatch_data.as_slice()) as u64;
         let indirect_draw_in_bytes = std::mem::size_of_val(indirect_draw_data.as_slice()) as u64;
@@ -409,14 +512,23 @@ impl FoundationInitializer {
         specialization_constants.entry_uint(5,cap.faces_to_be_removed_chunk_capacity);//
         specialization_constants.entry_uint(6,2);//BROAD_PHASE_CELL_SIZE
         specialization_constants.entry_uint(7,8);//BROAD_PHASE_CELL_CAPACITY
-        specialization_constants.entry_float(100,0.99);//BLOCK_COLLISION_FRICTION
+        specialization_constants.entry_float(100,BLOCK_COLLISION_FRICTION);//BLOCK_COLLISION_FRICTION
         specialization_constants.entry_float(101,0.01);//BLOCK_COLLISION_MINIMUM_BOUNCE
-        specialization_constants.entry_float(102,1.0);//PHYSICS_SIMULATION_DELTA_TIME_PER_STEP
-        specialization_constants.entry_float(103,0.01);//BONE_COLLISION_FORCE_PER_AREA_UNIT
+        specialization_constants.entry_float(102,PHYSICS_SIMULATION_DELTA_TIME_PER_STEP);//PHYSICS_SIMULATION_DELTA_TIME_PER_STEP
+        specialization_constants.entry_float(103,BONE_COLLISION_FORCE_PER_AREA_UNIT);//BONE_COLLISION_FORCE_PER_AREA_UNIT
         specialization_constants.entry_float(104,0.2);//IMPULSE_AVERAGING_OVER_TIMESETP
-        specialization_constants.entry_float(105,0.001f32);//GRAVITY
-        specialization_constants.entry_float(106,0.99);//DAMPING_COEFFICIENT
-        specialization_constants.entry_float(107,0.9);//BLOCK_RIGIDITY
+        specialization_constants.entry_float(105,GRAVITY);//GRAVITY
+        specialization_constants.entry_float(106,DAMPING_COEFFICIENT);//DAMPING_COEFFICIENT
+        specialization_constants.entry_float(107,BLOCK_RIGIDITY);//BLOCK_RIGIDITY
+        specialization_constants.entry_float(108,PARTICLE_DAMPING);//PARTICLE_DAMPING
+        specialization_constants.entry_float(109,PARTICLE_DECAY);//PARTICLE_DECAY
+        specialization_constants.entry_float(110,PARTICLE_GRAVITY);//PARTICLE_GRAVITY
+        specialization_constants.entry_float(111,PARTICLE_DEBRIS_SPEED);//PARTICLE_DEBRIS_SPEED
+        specialization_constants.entry_float(112,PARTICLE_TRAIL_MIN_SPEED);//PARTICLE_TRAIL_MIN_SPEED
+        specialization_constants.entry_uint(113,cap.constraint_solver_iterations);//CONSTRAINT_SOLVER_ITERATIONS
+        specialization_constants.entry_float(114,ROPE_STIFFNESS);//ROPE_STIFFNESS
+        specialization_constants.entry_float(115,HOLD_STIFFNESS);//HOLD_STIFFNESS
+        specialization_constants.entry_float(116,HOLD_DAMPING);//HOLD_DAMPING
 
         specialization_constants.entry_uint(300,cap.max_bones as u32);//MAX_BONES
         specialization_constants.entry_uint(301,cap.max_sensors as u32);//MAX_SENSORS
@@ -424,13 +536,15 @@ impl FoundationInitializer {
         specialization_constants.entry_uint(303,cap.max_rand_uint as u32);//MAX_RAND_UINT
         // specialization_constants.entry_uint(304,cap.max_neural_net_layers as u32);//MAX_NEURAL_NET_LAYERS
         specialization_constants.entry_uint(305,cap.max_tmp_faces_copy as u32);//MAX_TMP_FACES_COPY
-        // specialization_constants.entry_uint(306,cap.max_world_block_meta as u32);//MAX_WORLD_BLOCKS_TO_UPDATE
+        specialization_constants.entry_uint(306,cap.max_world_blocks_to_update as u32);//MAX_WORLD_BLOCKS_TO_UPDATE
         specialization_constants.entry_uint(307,cap.max_blocks_to_be_inserted_or_removed as u32);//MAX_BLOCKS_TO_BE_INSERTED_OR_REMOVED
         specialization_constants.entry_uint(308,cap.max_faces_to_be_inserted as u32);//MAX_FACES_TO_BE_INSERTED
         specialization_constants.entry_uint(309,cap.max_faces_to_be_removed as u32);//MAX_FACES_TO_BE_REMOVED
         specialization_constants.entry_uint(310,cap.max_htm_entities as u32);//MAX_HTM_ENTITIES
         specialization_constants.entry_uint(311,cap.max_ann_entities as u32);//MAX_ANN_ENTITIES
         specialization_constants.entry_uint(312,cap.max_particles as u32);//MAX_PARTICLES
+        specialization_constants.entry_uint(313,cap.max_lifecycle_events as u32);//MAX_LIFECYCLE_EVENTS
+        specialization_constants.entry_uint(314,cap.max_constraints as u32);//MAX_CONSTRAINTS
 
         specialization_constants.entry_uint(400, super::world_generation::SEA_LEVEL);
         specialization_constants.entry_float(401, super::world_generation::FREEZING_TEMPERATURE);
@@ -442,7 +556,60 @@ impl FoundationInitializer {
         specialization_constants.entry_float(407, super::world_generation::HUMIDITY_SCALE);
         specialization_constants.entry_float(408, super::world_generation::RESOURCE_TYPE_SCALE);
         specialization_constants.entry_float(409, super::world_generation::HAS_RESOURCE_SCALE);
-        specialization_constants.entry_uint(410, SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_millis() as u32);//RAND_SEED
+        specialization_constants.entry_uint(410, cap.seed);//RAND_SEED
+        specialization_constants.entry_uint(411, WATER_FLOW_PERIOD);//WATER_FLOW_PERIOD
+        specialization_constants.entry_uint(412, LAVA_FLOW_PERIOD);//LAVA_FLOW_PERIOD
+        specialization_constants.entry_uint(413, WATER_SPREAD);//WATER_SPREAD
+        specialization_constants.entry_uint(414, LAVA_SPREAD);//LAVA_SPREAD
+        specialization_constants.entry_uint(415, CLIMATE_PERIOD);//CLIMATE_PERIOD
+        specialization_constants.entry_float(416, LAVA_TEMPERATURE);//LAVA_TEMPERATURE
+        specialization_constants.entry_float(417, WATER_HUMIDITY);//WATER_HUMIDITY
+        specialization_constants.entry_float(418, SNOW_MELTING_TEMPERATURE);//SNOW_MELTING_TEMPERATURE
+
+        specialization_constants.entry_float(500, ecology::ANN_MOVEMENT_ENERGY_COST);//ANN_MOVEMENT_ENERGY_COST
+        specialization_constants.entry_float(501, ecology::ANN_METABOLISM_ENERGY_COST);//ANN_METABOLISM_ENERGY_COST
+        specialization_constants.entry_float(502, ecology::ANN_FOOD_ENERGY_GAIN);//ANN_FOOD_ENERGY_GAIN
+        specialization_constants.entry_float(503, ecology::ANN_MAX_ENERGY);//ANN_MAX_ENERGY
+        specialization_constants.entry_float(504, ecology::ANN_INITIAL_ENERGY);//ANN_INITIAL_ENERGY
+        specialization_constants.entry_float(505, ecology::ANN_REPRODUCTION_ENERGY_THRESHOLD);//ANN_REPRODUCTION_ENERGY_THRESHOLD
+        specialization_constants.entry_float(506, ecology::ANN_CHILD_ENERGY_FRACTION);//ANN_CHILD_ENERGY_FRACTION
+        specialization_constants.entry_float(507, ecology::ANN_WEIGHT_MUTATION_STRENGTH);//ANN_WEIGHT_MUTATION_STRENGTH
+        specialization_constants.entry_float(508, ecology::ANN_REWIRE_PROBABILITY);//ANN_REWIRE_PROBABILITY
+        specialization_constants.entry_float(509, ecology::ANN_ATTACK_THRESHOLD);//ANN_ATTACK_THRESHOLD
+        specialization_constants.entry_float(510, ecology::ANN_ATTACK_ENERGY_COST);//ANN_ATTACK_ENERGY_COST
+        specialization_constants.entry_float(511, ecology::ANN_ATTACK_ENERGY_DRAIN);//ANN_ATTACK_ENERGY_DRAIN
+        specialization_constants.entry_uint(512, ecology::ANN_ATTACK_COOLDOWN);//ANN_ATTACK_COOLDOWN
+        specialization_constants.entry_float(513, ecology::ANN_ATTACK_RANGE);//ANN_ATTACK_RANGE
+        specialization_constants.entry_float(514, ecology::ANN_LEARNING_RATE_MUTATION_STRENGTH);//ANN_LEARNING_RATE_MUTATION_STRENGTH
+        specialization_constants.entry_float(515, ecology::ANN_MAX_INITIAL_LEARNING_RATE);//ANN_MAX_INITIAL_LEARNING_RATE
+        specialization_constants.entry_float(516, ecology::ANN_BLOCK_EDIT_THRESHOLD);//ANN_BLOCK_EDIT_THRESHOLD
+        specialization_constants.entry_uint(517, ecology::ANN_BLOCK_EDIT_COOLDOWN);//ANN_BLOCK_EDIT_COOLDOWN
+        specialization_constants.entry_float(518, ecology::ANN_BLOCK_EDIT_ENERGY_COST);//ANN_BLOCK_EDIT_ENERGY_COST
+        specialization_constants.entry_float(519, ecology::ANN_BLOCK_REACH);//ANN_BLOCK_REACH
+        specialization_constants.entry_float(520, ecology::ANN_HARVEST_ENERGY_GAIN);//ANN_HARVEST_ENERGY_GAIN
+        specialization_constants.entry_float(521, ecology::ANN_SIGNAL_THRESHOLD);//ANN_SIGNAL_THRESHOLD
+        specialization_constants.entry_float(522, ecology::ANN_SIGNAL_ENERGY_COST);//ANN_SIGNAL_ENERGY_COST
+        specialization_constants.entry_float(523, ecology::ANN_SIGNAL_SENSE_RADIUS);//ANN_SIGNAL_SENSE_RADIUS
+        specialization_constants.entry_float(524, ecology::ANN_SIGNAL_EMISSION_SPEED);//ANN_SIGNAL_EMISSION_SPEED
+
+        let ann = &cap.ann_config;
+        specialization_constants.entry_uint(600, ann.lidar_count as u32);//ANN_LIDAR_COUNT
+        specialization_constants.entry_uint(601, ann.hidden_size as u32);//ANN_HIDDEN_SIZE
+        specialization_constants.entry_uint(602, ann.input_connections_per_hidden_neuron as u32);//ANN_INPUT_CONNECTIONS_PER_HIDDEN_NEURON
+        specialization_constants.entry_uint(603, ann.latent_size as u32);//ANN_LATENT_SIZE
+        specialization_constants.entry_uint(604, ann.hidden_connections_per_latent_neuron as u32);//ANN_HIDDEN_CONNECTIONS_PER_LATENT_NEURON
+        specialization_constants.entry_uint(605, ann.latent_connections_per_latent_neuron as u32);//ANN_LATENT_CONNECTIONS_PER_LATENT_NEURON
+        specialization_constants.entry_uint(606, ann.latent_connections_per_output_neuron as u32);//ANN_LATENT_CONNECTIONS_PER_OUTPUT_NEURON
+        specialization_constants.entry_uint(607, ann.output_attack_muscles_size as u32);//ANN_OUTPUT_ATTACK_MUSCLES_SIZE
+        specialization_constants.entry_uint(608, ann.output_movement_muscles_size as u32);//ANN_OUTPUT_MOVEMENT_MUSCLES_SIZE
+        specialization_constants.entry_uint(609, ann.output_rotation_muscles_size as u32);//ANN_OUTPUT_ROTATION_MUSCLES_SIZE
+        specialization_constants.entry_uint(610, ann.max_size() as u32);//ANN_MAX_SIZE
+        specialization_constants.entry_uint(611, ann.body_limbs as u32);//ANN_BODY_LIMBS
+        specialization_constants.entry_uint(612, ann.output_limb_muscles_size as u32);//ANN_OUTPUT_LIMB_MUSCLES_SIZE
+        specialization_constants.entry_float(613, crate::neat::body::BODY_CONSTRAINT_DAMPING);//BODY_CONSTRAINT_DAMPING
+        specialization_constants.entry_uint(614, ann.plasticity as u32);//ANN_PLASTICITY
+        specialization_constants.entry_uint(615, ann.output_block_muscles_size as u32);//ANN_OUTPUT_BLOCK_MUSCLES_SIZE
+        specialization_constants.entry_uint(616, ann.signal_channels as u32);//ANN_SIGNAL_CHANNELS
         Ok(Self {
             cap,
             specialization_constants,
@@ -466,6 +633,12 @@ impl FoundationInitializer {
             faces_to_be_removed: faces_to_be_removed_buffer,
             indirect,
             bones:bones_buffer,
+            body_bones,
+            body_constraints,
+            body_muscles,
+            lifecycle_events: lifecycle_events_buffer,
+            constraints: constraints_buffer,
+            world_blocks_to_update: world_blocks_to_update_buffer,
             default_global_mutables:mutables,
         })
     }
@@ -488,6 +661,12 @@ impl FoundationInitializer {
             indirect_draw,
             world,
             bones,
+            body_bones,
+            body_constraints,
+            body_muscles,
+            lifecycle_events,
+            constraints,
+            world_blocks_to_update,
             particles,
             collision_grid,
             global_mutables,
@@ -496,6 +675,9 @@ impl FoundationInitializer {
             default_global_mutables
         } = self;
         let global_mutables = global_mutables.take()?.take_gpu();
+        let body_bones = body_bones.take()?.take_gpu();
+        let body_constraints = body_constraints.take()?.take_gpu();
+        let body_muscles = body_muscles.take()?.take_gpu();
         let _ = indirect_dispatch.take()?.take_gpu();
         let _ = indirect_draw.take()?.take_gpu();
         let tmp_faces_copy = tmp_faces_copy.take()?;
@@ -514,20 +696,27 @@ impl FoundationInitializer {
             world,
             cap,
             bones,
+            body_bones,
+            body_constraints,
+            body_muscles,
+            lifecycle_events,
+            constraints,
+            world_blocks_to_update,
             collision_grid,
             global_mutables,
             indirect,
             sampler,
             htm_entities_buffer ,
             ann_entities_buffer,
-            default_global_mutables
+            default_global_mutables,
+            has_spawn_template: false,
         })
     }
 }
 
 pub struct Foundations {
     htm_entities_buffer: SubBuffer<HtmEntity, Storage>,
-    ann_entities_buffer: SubBuffer<AnnEntity, Storage>,
+    ann_entities_buffer: SubBuffer<u32, Storage>,
     specialization_constants: SpecializationConstants,
     faces: SubBuffer<Face, Storage>,
     rand_uint: SubBuffer<u32, Storage>,
@@ -541,19 +730,27 @@ pub struct Foundations {
     player_event_uniform: HostBuffer<PlayerEvent, Uniform>,
     world: SubBuffer<Block, Storage>,
     bones: SubBuffer<Bone, Storage>,
+    body_bones: SubBuffer<Bone, Storage>,
+    body_constraints: SubBuffer<Constraint, Storage>,
+    body_muscles: SubBuffer<Muscle, Storage>,
+    lifecycle_events: SubBuffer<LifecycleRecord, Storage>,
+    constraints: SubBuffer<Constraint, Storage>,
+    world_blocks_to_update: SubBuffer<u32, Storage>,
     particles: SubBuffer<Particle, Storage>,
     global_mutables: SubBuffer<GlobalMutables, Storage>,
     collision_grid: SubBuffer<u32, Storage>,
     indirect: Indirect,
     sampler: Sampler,
     default_global_mutables:GlobalMutables,
+    has_spawn_template:bool,
 }
 
 impl Foundations {
     pub fn htm_entities_buffer(&self)-> &SubBuffer<HtmEntity, Storage>{
         &self.htm_entities_buffer
     }
-    pub fn ann_entities_buffer(&self)-> &SubBuffer<AnnEntity, Storage>{
+    /**Every entity occupies cap().ann_config.entity_words() words. See AnnEntity::to_words*/
+    pub fn ann_entities_buffer(&self)-> &SubBuffer<u32, Storage>{
         &self.ann_entities_buffer
     }
     pub fn specialization_constants(&self) -> &SpecializationConstants{
@@ -589,6 +786,29 @@ impl Foundations {
     pub fn bones(&self) -> &SubBuffer<Bone, Storage> {
         &self.bones
     }
+    /**Bones of the body at rest pose. See Body::template_bones*/
+    pub fn body_bones(&self) -> &SubBuffer<Bone, Storage> {
+        &self.body_bones
+    }
+    /**Joints of limbs followed by constraints of muscles. See Body::constraints*/
+    pub fn body_constraints(&self) -> &SubBuffer<Constraint, Storage> {
+        &self.body_constraints
+    }
+    pub fn body_muscles(&self) -> &SubBuffer<Muscle, Storage> {
+        &self.body_muscles
+    }
+    /**Births and deaths of ANN entities. Only the first global_mutables.lifecycle_events records are valid*/
+    pub fn lifecycle_events(&self) -> &SubBuffer<LifecycleRecord, Storage> {
+        &self.lifecycle_events
+    }
+    /**Rods, ropes and springs between bones of the world. Only the first global_mutables.constraints are valid*/
+    pub fn constraints(&self) -> &SubBuffer<Constraint, Storage> {
+        &self.constraints
+    }
+    /**Blocks that update_ambience.comp should check for falling. Holds two halves, see GlobalMutables::world_blocks_to_update*/
+    pub fn world_blocks_to_update(&self) -> &SubBuffer<u32, Storage> {
+        &self.world_blocks_to_update
+    }
     pub fn faces_to_be_inserted(&self) -> &SubBuffer<Face, Storage> {
         &self.faces_to_be_inserted
     }
@@ -616,4 +836,190 @@ impl Foundations {
     pub fn collision_grid(&self) -> &SubBuffer<u32, Storage> {
         &self.collision_grid
     }
+    /**Reads back all ANN entities together with their bones. GPU must be idle.*/
+    pub fn download_brains(&self, cmd_pool: &CommandPool) -> Result<Vec<SavedBrain>, Error> {
+        let mutables = download(cmd_pool, self.global_mutables())?[0];
+        let config = self.cap.ann_config;
+        let words = config.entity_words() as u64;
+        let entities: Vec<AnnEntity> = if mutables.ann_entities > 0 {
+            download(cmd_pool, &self.ann_entities_buffer().sub_elem(0, mutables.ann_entities as u64 * words))?
+                .chunks(words as usize).map(|w| AnnEntity::from_words(config, w)).collect()
+        } else {
+            vec![]
+        };
+        let bones = download(cmd_pool, self.bones())?;
+        Ok(collect_brains(&entities, &bones[..mutables.bones as usize], None))
+    }
+    /**Places saved brains into free entity and bone slots. Every brain gets its root bone followed by the limbs of the body.
+    GPU must be idle. Returns the number of injected brains*/
+    pub fn inject_brains(&self, cmd_pool: &CommandPool, brains: &[SavedBrain]) -> Result<usize, Error> {
+        let config = self.cap.ann_config;
+        if let Some(b) = brains.iter().find(|b| b.entity.config() != &config) {
+            return Err(failure::err_msg(format!("Brain dimensions {:?} do not match dimensions of this run {:?}", b.entity.config(), config)));
+        }
+        let mut mutables = download(cmd_pool, self.global_mutables())?[0];
+        let bones_per_brain = 1 + self.cap.body.limbs();
+        let n = brains.len()
+            .min(self.cap.max_ann_entities as usize - mutables.ann_entities as usize)
+            .min((self.cap.max_bones as usize - mutables.bones as usize) / bones_per_brain);
+        if n == 0 {
+            return Ok(0);
+        }
+        let words = config.entity_words() as u64;
+        let mut entities = Vec::with_capacity(n * words as usize);
+        let mut bones = Vec::with_capacity(n * bones_per_brain);
+        let mut births = Vec::with_capacity(n);
+        let tick = mutables.tick as u32;
+        for (i, b) in brains[..n].iter().enumerate() {
+            let root_bone_idx = mutables.bones + (i * bones_per_brain) as u32;
+            let (mut entity, bone) = b.placed_at(mutables.ann_entities + i as u32, root_bone_idx);
+            for (l, limb) in entity.limbs_mut().iter_mut().enumerate() {
+                *limb = root_bone_idx + 1 + l as u32;
+            }
+            // ids from the file are meaningless in this run, so every injected brain starts a new lineage
+            entity.set_lineage(mutables.next_entity_id, NO_ENTITY_ID, tick);
+            births.push(LifecycleRecord::birth(mutables.next_entity_id, NO_ENTITY_ID, tick));
+            mutables.next_entity_id += 1;
+            entity.to_words(&mut entities);
+            bones.push(bone);
+            bones.extend(self.cap.body.spawn_limbs(&bone, root_bone_idx, root_bone_idx + 1));
+        }
+        upload(cmd_pool, &entities, &self.ann_entities_buffer().sub_elem(mutables.ann_entities as u64 * words, n as u64 * words))?;
+        upload(cmd_pool, &bones, &self.bones().sub_elem(mutables.bones as u64, bones.len() as u64))?;
+        let free_records = (self.cap.max_lifecycle_events as usize).saturating_sub(mutables.lifecycle_events as usize).min(n);
+        if free_records > 0 {
+            upload(cmd_pool, &births[..free_records], &self.lifecycle_events().sub_elem(mutables.lifecycle_events as u64, free_records as u64))?;
+        }
+        mutables.lifecycle_events += n as u32;
+        mutables.ann_entities += n as u32;
+        mutables.bones += bones.len() as u32;
+        upload(cmd_pool, &[mutables], self.global_mutables())?;
+        let group_size = cmd_pool.device().get_max_subgroup_size();
+        upload(cmd_pool, &[vk::DispatchIndirectCommand { x: (mutables.bones + group_size - 1) / group_size, y: 1, z: 1 }], self.indirect().update_bones())?;
+        upload(cmd_pool, &[vk::DispatchIndirectCommand { x: mutables.ann_entities, y: 1, z: 1 }], self.indirect().update_ann_entities())?;
+        upload(cmd_pool, &[vk::DrawIndirectCommand { vertex_count: 36, instance_count: mutables.bones, first_vertex: 0, first_instance: 0 }], self.indirect().draw_bones())?;
+        Ok(n)
+    }
+    /**Stores the brain in the template slot, which is copied whenever the player spawns an entity with SpawnBrain::Template.
+    The entity gets a new id, bone and limbs when spawned, so only the brain, energy and speed matter. GPU must be idle.*/
+    pub fn set_spawn_template(&mut self, cmd_pool: &CommandPool, brain: &SavedBrain) -> Result<(), Error> {
+        let config = self.cap.ann_config;
+        if brain.entity.config() != &config {
+            return Err(failure::err_msg(format!("Brain dimensions {:?} do not match dimensions of this run {:?}", brain.entity.config(), config)));
+        }
+        let words = config.entity_words() as u64;
+        let mut template = Vec::with_capacity(words as usize);
+        brain.entity.to_words(&mut template);
+        upload(cmd_pool, &template, &self.ann_entities_buffer().sub_elem(self.cap.max_ann_entities * words, words))?;
+        self.has_spawn_template = true;
+        Ok(())
+    }
+    pub fn has_spawn_template(&self) -> bool {
+        self.has_spawn_template
+    }
+    /**Reads back the state of the simulation. Births and deaths are left at 0, see MetricsLogger. GPU must be idle.*/
+    pub fn sample_metrics(&self, cmd_pool: &CommandPool) -> Result<MetricsSample, Error> {
+        let mutables = download(cmd_pool, self.global_mutables())?[0];
+        let config = self.cap.ann_config;
+        let words = config.entity_words();
+        let energy_offset = config.energy_offset();
+        let entities = if mutables.ann_entities > 0 {
+            download(cmd_pool, &self.ann_entities_buffer().sub_elem(0, mutables.ann_entities as u64 * words as u64))?
+        } else {
+            vec![]
+        };
+        // Every element of face_count_per_chunk_buffer holds the number of faces of a chunk in its first word
+        let face_counts = download(cmd_pool, self.face_count_per_chunk_buffer().reinterpret_as::<[u32; 2]>())?;
+        let mut sample = MetricsSample {
+            tick: mutables.tick,
+            ann_entities: mutables.ann_entities,
+            htm_entities: mutables.htm_entities,
+            bones: mutables.bones,
+            max_bones: self.cap.max_bones as u32,
+            faces: face_counts.iter().map(|c| c[0]).sum(),
+            max_faces: self.cap.max_faces as u32,
+            pending_block_edits: mutables.blocks_to_be_inserted_or_removed,
+            ..MetricsSample::default()
+        };
+        sample.set_energies(entities.chunks(words).map(|e| f32::from_bits(e[energy_offset])));
+        Ok(sample)
+    }
+    /**Configuration of this run, written at the top of metrics logs*/
+    pub fn run_header(&self) -> RunHeader {
+        let cap = &self.cap;
+        let mut header = RunHeader::new(cap.seed);
+        header.entry("world_width", cap.world_size.width())
+            .entry("world_depth", cap.world_size.depth())
+            .entry("max_bones", cap.max_bones)
+            .entry("max_faces", cap.max_faces)
+            .entry("max_ann_entities", cap.max_ann_entities)
+            .entry("max_htm_entities", cap.max_htm_entities)
+            .entry("max_particles", cap.max_particles)
+            .entry("max_lifecycle_events", cap.max_lifecycle_events)
+            .entry("max_constraints", cap.max_constraints)
+            .entry("max_world_blocks_to_update", cap.max_world_blocks_to_update)
+            .entry("constraint_solver_iterations", cap.constraint_solver_iterations)
+            .entry("body_parts", cap.body.parts().len())
+            .entry("body_muscles", cap.body.muscles().len())
+            .entry("ann_metabolism_energy_cost", ecology::ANN_METABOLISM_ENERGY_COST)
+            .entry("ann_movement_energy_cost", ecology::ANN_MOVEMENT_ENERGY_COST)
+            .entry("ann_food_energy_gain", ecology::ANN_FOOD_ENERGY_GAIN)
+            .entry("ann_reproduction_energy_threshold", ecology::ANN_REPRODUCTION_ENERGY_THRESHOLD)
+            .entry("ann_weight_mutation_strength", ecology::ANN_WEIGHT_MUTATION_STRENGTH)
+            .entry("ann_rewire_probability", ecology::ANN_REWIRE_PROBABILITY)
+            .entry("ann_learning_rate_mutation_strength", ecology::ANN_LEARNING_RATE_MUTATION_STRENGTH)
+            .entry("ann_max_initial_learning_rate", ecology::ANN_MAX_INITIAL_LEARNING_RATE)
+            .entry("ann_block_edit_threshold", ecology::ANN_BLOCK_EDIT_THRESHOLD)
+            .entry("ann_block_edit_cooldown", ecology::ANN_BLOCK_EDIT_COOLDOWN)
+            .entry("ann_block_edit_energy_cost", ecology::ANN_BLOCK_EDIT_ENERGY_COST)
+            .entry("ann_block_reach", ecology::ANN_BLOCK_REACH)
+            .entry("ann_harvest_energy_gain", ecology::ANN_HARVEST_ENERGY_GAIN)
+            .entry("ann_signal_threshold", ecology::ANN_SIGNAL_THRESHOLD)
+            .entry("ann_signal_energy_cost", ecology::ANN_SIGNAL_ENERGY_COST)
+            .entry("ann_signal_sense_radius", ecology::ANN_SIGNAL_SENSE_RADIUS)
+            .entry("ann_signal_emission_speed", ecology::ANN_SIGNAL_EMISSION_SPEED)
+            .entry("gravity", GRAVITY)
+            .entry("damping_coefficient", DAMPING_COEFFICIENT)
+            .entry("block_collision_friction", BLOCK_COLLISION_FRICTION)
+            .entry("block_rigidity", BLOCK_RIGIDITY)
+            .entry("bone_collision_force_per_area_unit", BONE_COLLISION_FORCE_PER_AREA_UNIT)
+            .entry("particle_damping", PARTICLE_DAMPING)
+            .entry("particle_decay", PARTICLE_DECAY)
+            .entry("particle_gravity", PARTICLE_GRAVITY)
+            .entry("particle_debris_speed", PARTICLE_DEBRIS_SPEED)
+            .entry("particle_trail_min_speed", PARTICLE_TRAIL_MIN_SPEED)
+            .entry("hold_stiffness", HOLD_STIFFNESS)
+            .entry("hold_damping", HOLD_DAMPING)
+            .entry("water_flow_period", WATER_FLOW_PERIOD)
+            .entry("lava_flow_period", LAVA_FLOW_PERIOD)
+            .entry("water_spread", WATER_SPREAD)
+            .entry("lava_spread", LAVA_SPREAD)
+            .entry("climate_period", CLIMATE_PERIOD)
+            .entry("lava_temperature", LAVA_TEMPERATURE)
+            .entry("water_humidity", WATER_HUMIDITY)
+            .entry("snow_melting_temperature", SNOW_MELTING_TEMPERATURE);
+        for (name, dim) in ANN_CONFIG_NAMES.iter().zip(cap.ann_config.as_array().iter()) {
+            header.entry(&format!("ann_{}", name), dim);
+        }
+        header
+    }
+    /**Reads back all births and deaths recorded since the last call and clears the buffer. GPU must be idle.
+    Returns the records in the order in which they were appended, together with the number of records that
+    didn't fit into the buffer and were lost and with the global mutables that were read back along the way.
+    Collision grid overflows accumulated since the last call are drained as well, so the returned
+    GlobalMutables::collision_grid_overflows covers all physics steps in between*/
+    pub fn drain_lifecycle_events(&self, cmd_pool: &CommandPool) -> Result<(Vec<LifecycleRecord>, u32, GlobalMutables), Error> {
+        let mutables = download(cmd_pool, self.global_mutables())?[0];
+        let appended = mutables.lifecycle_events;
+        if appended == 0 && mutables.collision_grid_overflows == 0 {
+            return Ok((vec![], 0, mutables));
+        }
+        let kept = appended.min(self.cap.max_lifecycle_events as u32);
+        let records = if kept > 0 { download(cmd_pool, &self.lifecycle_events().sub_elem(0, kept as u64))? } else { vec![] };
+        let mut drained = mutables;
+        drained.lifecycle_events = 0;
+        drained.collision_grid_overflows = 0;
+        upload(cmd_pool, &[drained], self.global_mutables())?;
+        Ok((records, appended - kept, mutables))
+    }
 }
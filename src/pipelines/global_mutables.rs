@@ -5,9 +5,33 @@ pub struct GlobalMutables {
     pub blocks_to_be_inserted_or_removed: i32,
     pub bones: u32,
     pub lidars: u32,
+    /**Bone grabbed by the player and pulled towards PlayerEvent::hold_target, or -1 if there is none*/
     pub held_bone_idx:i32,
     pub tick:i32,
     pub htm_entities:u32,
     pub ann_entities:u32,
     pub particles:u32,
+    /**Stable id that will be given to the next ANN entity. Ids are never reused*/
+    pub next_entity_id:u32,
+    /**Number of records appended to the lifecycle events buffer since it was last drained.
+    It may exceed the capacity of the buffer, in which case the excess records were lost*/
+    pub lifecycle_events:u32,
+    /**Number of blocks that ANN entities enqueued into blocks_to_be_inserted_or_removed during the last physics step.
+    It may exceed the capacity of the buffer, in which case the excess edits were rejected*/
+    pub agent_block_edits:u32,
+    /**Number of bones that did not fit into their cell of the collision grid (see BROAD_PHASE_CELL_CAPACITY), summed over
+    all physics steps since it was last drained (see Foundations::drain_lifecycle_events). Such bones are ignored by collision
+    detection and may pass through other bones*/
+    pub collision_grid_overflows:u32,
+    /**Number of rods, ropes and springs in the constraints buffer*/
+    pub constraints:u32,
+    /**Bone chosen by the player as the first end of the next rope, or -1 if there is none*/
+    pub rope_anchor_bone_idx:i32,
+    /**Lengths of the two halves of the world_blocks_to_update buffer. Blocks in half world_blocks_to_update_even
+    are checked by update_ambience.comp in this tick, while blocks changed in this tick (and their neighbours) are
+    pushed into the other half, to be checked in the next tick. May exceed the capacity, in which case the excess blocks were dropped*/
+    pub world_blocks_to_update:[u32;2],
+    pub world_blocks_to_update_even:u32,
+    /**Bone thrown by the player (PLAYER_EVENT_THROW) that leaves a trail of particles until it slows down, or -1 if there is none*/
+    pub thrown_bone_idx:i32,
 }
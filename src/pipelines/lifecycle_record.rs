@@ -0,0 +1,43 @@
+/**Entity has just been spawned (or generated together with the world, or injected from a brain file)*/
+pub const LIFECYCLE_EVENT_BIRTH:u32 = 0;
+/**Entity ran out of energy and was removed*/
+pub const LIFECYCLE_EVENT_DEATH:u32 = 1;
+/**Parent of entities that were generated together with the world or injected from a brain file*/
+pub const NO_ENTITY_ID:u32 = u32::MAX;
+
+/**Single birth or death of an ANN entity, as appended by update_ann_entities_lifecycle.comp.
+Entities are identified by their stable ids (see AnnEntity::id), never by their slot in the buffer,
+because slots get reused.*/
+#[repr(C, packed)]
+#[derive(Copy,Clone,Debug,PartialEq,Eq)]
+pub struct LifecycleRecord{
+    event_type:u32,
+    entity_id:u32,
+    parent_id:u32,
+    tick:u32,
+}
+
+impl LifecycleRecord{
+    pub fn birth(entity_id:u32, parent_id:u32, tick:u32)->Self{
+        Self{ event_type:LIFECYCLE_EVENT_BIRTH, entity_id, parent_id, tick }
+    }
+    pub fn death(entity_id:u32, tick:u32)->Self{
+        Self{ event_type:LIFECYCLE_EVENT_DEATH, entity_id, parent_id:NO_ENTITY_ID, tick }
+    }
+    pub fn event_type(&self)->u32{
+        self.event_type
+    }
+    pub fn is_birth(&self)->bool{
+        self.event_type == LIFECYCLE_EVENT_BIRTH
+    }
+    pub fn entity_id(&self)->u32{
+        self.entity_id
+    }
+    /**NO_ENTITY_ID for deaths and for entities without a parent*/
+    pub fn parent_id(&self)->u32{
+        self.parent_id
+    }
+    pub fn tick(&self)->u32{
+        self.tick
+    }
+}
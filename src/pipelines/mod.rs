@@ -11,6 +11,8 @@ pub mod bone;
 pub mod constraint;
 pub mod global_mutables;
 pub mod bones;
+pub mod bone_physics;
+pub mod player_physics;
 pub mod physics;
 pub mod renderable;
 pub mod computable;
@@ -19,6 +21,7 @@ pub mod compute_thread;
 pub mod sensor;
 pub mod neural_net_layer;
 pub mod muscle;
+pub mod lifecycle_record;
 pub mod ambience;
 pub mod perlin_noise_map;
 pub mod world_generation;
@@ -17,5 +17,18 @@ impl Muscle{
             max_length
         }
     }
+    pub fn constraint_id(&self)->u32{
+        self.constraint_id
+    }
+    pub fn min_length(&self)->f32{
+        self.min_length
+    }
+    pub fn max_length(&self)->f32{
+        self.max_length
+    }
+    /**Maps activation of the muscle output (in range [0,1]) onto the target length of its constraint*/
+    pub fn target_length(&self, activation:f32)->f32{
+        self.min_length + (self.max_length - self.min_length) * activation.max(0.).min(1.)
+    }
 
-}
\ No newline at end of file
+}
@@ -3,6 +3,8 @@
 pub struct MvpUniforms {
     pub mvp: glm::Mat4,
     pub mv: glm::Mat4,
+    /**Fraction of the way from old_center to new_center at which bones are rendered. See PhysicsTimer::interpolation*/
+    pub bone_interpolation: f32,
 }
 
 impl MvpUniforms {
@@ -10,6 +12,7 @@ impl MvpUniforms {
         Self {
             mvp: glm::identity(),
             mv: glm::identity(),
+            bone_interpolation: 1.,
         }
     }
 }
@@ -3,7 +3,40 @@ use ash::vk::VertexInputAttributeDescription;
 use ash::vk;
 use rand::random;
 use crate::render::data::VertexAttrib;
+use crate::blocks::WorldSize;
 
+/**Velocity of particles is multiplied by this factor every step. Same as PARTICLE_DAMPING in constants.comp*/
+pub const PARTICLE_DAMPING:f32 = 0.95;
+/**Strength lost by a signal particle every step. Particles are removed once their strength drops to 0*/
+pub const PARTICLE_DECAY:f32 = 0.01;
+/**Downward acceleration of debris particles. Signals and trails are not affected by gravity. Same as PARTICLE_GRAVITY in constants.comp*/
+pub const PARTICLE_GRAVITY:f32 = 0.01;
+/**Maximum initial speed of debris particles. Same as PARTICLE_DEBRIS_SPEED in constants.comp*/
+pub const PARTICLE_DEBRIS_SPEED:f32 = 0.15;
+/**Bone thrown by the player leaves a trail of particles as long as it moves faster than this.
+Same as PARTICLE_TRAIL_MIN_SPEED in constants.comp*/
+pub const PARTICLE_TRAIL_MIN_SPEED:f32 = 0.05;
+/**Number of debris particles emitted by every broken block. Same as PARTICLE_DEBRIS_PER_BLOCK in constants.comp*/
+pub const PARTICLE_DEBRIS_PER_BLOCK:u32 = 4;
+/**Emitter of particles produced by broken blocks. Same as PARTICLE_EMITTER_DEBRIS in constants.comp*/
+pub const PARTICLE_EMITTER_DEBRIS:u32 = u32::MAX;
+/**Emitter of particles left behind by the thrown bone. Same as PARTICLE_EMITTER_TRAIL in constants.comp*/
+pub const PARTICLE_EMITTER_TRAIL:u32 = u32::MAX - 1;
+
+/**Same as hash_uint in constants.comp*/
+pub fn hash_uint(mut x:u32)->u32{
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846ca68b);
+    x ^= x >> 16;
+    x
+}
+
+/**Particles are either signals emitted by ANN entities, debris of broken blocks or the trail of a thrown bone.
+The emitter_entity tells them apart (see is_signal). Energy encodes both the channel and the strength
+as channel+strength, where strength lies in range (0,1]. Debris and trails always use channel 0.
+Particles with no energy left are dead and get removed by update_ann_entities_lifecycle.comp*/
 #[repr(C, packed)]
 #[derive(Copy,Clone,Debug)]
 pub struct Particle{
@@ -19,15 +52,92 @@ impl Particle{
     pub fn rand_vec3()->glm::Vec3{
         glm::vec3(Self::rand_f32(),Self::rand_f32(),Self::rand_f32())
     }
-    pub fn random()->Self{
-        let position = glm::vec3(random::<f32>()*16.,3.+random::<f32>()*8.,random::<f32>()*16.);
+    /**Debris particle at a random position anywhere in the world*/
+    pub fn random(world_size:&WorldSize)->Self{
+        let position = glm::vec3(random::<f32>()*world_size.world_width() as f32,random::<f32>()*world_size.height() as f32,random::<f32>()*world_size.world_depth() as f32);
+        Self::debris(position, Self::rand_vec3()*PARTICLE_DEBRIS_SPEED)
+    }
+    pub fn debris(position:glm::Vec3, velocity:glm::Vec3)->Self{
         Self{
             position,
-            emitter_entity: 0,
-            velocity: Self::rand_vec3()*0.1,
-            energy: 0.,
+            emitter_entity: PARTICLE_EMITTER_DEBRIS,
+            velocity,
+            energy: 1.,
+        }
+    }
+    /**Mirrors emit_block_debris in update_ambience_flush_world_copy.comp. Returns the debris particles of the block
+    at the given world index (see WorldSize::block_pos_into_world_idx)*/
+    pub fn block_debris(world_size:&WorldSize, block_idx:u32)->Vec<Self>{
+        let block_idx = block_idx as usize;
+        let y = block_idx / world_size.world_area();
+        let y_rem = block_idx - y * world_size.world_area();
+        let z = y_rem / world_size.world_width();
+        let x = y_rem - z * world_size.world_width();
+        let center = glm::vec3(x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5);
+        (0..PARTICLE_DEBRIS_PER_BLOCK).map(|i|{
+            let seed = hash_uint(block_idx as u32).wrapping_add(i * 3);
+            let rand = |j:u32| hash_uint(seed.wrapping_add(j)) as f32 / u32::MAX as f32 * 2. - 1.;
+            let velocity = glm::vec3(rand(0), rand(1).abs(), rand(2)) * PARTICLE_DEBRIS_SPEED;
+            Self::debris(center, velocity)
+        }).collect()
+    }
+    /**Mirrors the emission of the trail in update_bones.comp. Bones slower than PARTICLE_TRAIL_MIN_SPEED leave no trail*/
+    pub fn trail(bone_center:glm::Vec3, bone_velocity:&glm::Vec3)->Option<Self>{
+        if glm::length(bone_velocity) > PARTICLE_TRAIL_MIN_SPEED{
+            Some(Self{
+                position: bone_center,
+                emitter_entity: PARTICLE_EMITTER_TRAIL,
+                velocity: glm::zero(),
+                energy: 1.,
+            })
+        }else{
+            None
+        }
+    }
+    /**Only signals can be sensed by ANN entities. Same as is_signal_particle in constants.comp*/
+    pub fn is_signal(&self)->bool{
+        self.emitter_entity < PARTICLE_EMITTER_TRAIL
+    }
+    /**Mirrors emit_signal in update_ann_entities.comp. Emitter is the encoded index of the emitting entity (see encode_ann_entity_idx)*/
+    pub fn signal(position:glm::Vec3, velocity:glm::Vec3, emitter_entity:u32, channel:u32, strength:f32)->Self{
+        assert!(strength > 0. && strength <= 1., "{}", strength);
+        Self{
+            position,
+            emitter_entity,
+            velocity,
+            energy: channel as f32 + strength,
         }
     }
+    pub fn is_alive(&self)->bool{
+        self.energy > 0.
+    }
+    /**Same as particle_channel in constants.comp*/
+    pub fn channel(&self)->u32{
+        (self.energy.ceil() - 1.).max(0.) as u32
+    }
+    /**Same as particle_strength in constants.comp*/
+    pub fn strength(&self)->f32{
+        (self.energy - self.channel() as f32).max(0.)
+    }
+    /**CPU reference of update_particles.comp. The particle drifts along its damped velocity (debris also falls down),
+    unless it would enter a block for which is_blocked returns true, in which case it stops. Its strength decays
+    and once there is none left, the particle dies*/
+    pub fn update(&mut self, is_blocked:impl Fn(&glm::Vec3)->bool){
+        if self.emitter_entity == PARTICLE_EMITTER_DEBRIS{
+            let mut velocity = self.velocity;
+            velocity.y -= PARTICLE_GRAVITY;
+            self.velocity = velocity;
+        }
+        let new_position = self.position + self.velocity;
+        if is_blocked(&new_position){
+            self.velocity = glm::zero();
+        }else{
+            self.position = new_position;
+            self.velocity = self.velocity * PARTICLE_DAMPING;
+        }
+        let strength = self.strength() - PARTICLE_DECAY;
+        self.energy = if strength > 0. { self.channel() as f32 + strength } else { 0. };
+    }
     pub fn new(pos:glm::Vec3)->Self{
         Self{
             position: pos,
@@ -37,6 +147,18 @@ impl Particle{
         }
     }
 }
+/**Removes particles that ran out of energy by moving the last particle into their slot,
+the same way update_ann_entities_lifecycle.comp does it*/
+pub fn remove_dead_particles(particles:&mut Vec<Particle>){
+    let mut i = 0;
+    while i < particles.len(){
+        if particles[i].is_alive(){
+            i += 1;
+        }else{
+            particles.swap_remove(i);
+        }
+    }
+}
 impl VertexSource for Particle{
     fn get_attribute_descriptions(binding: u32) -> Vec<VertexInputAttributeDescription> {
         vec![
@@ -51,7 +173,101 @@ impl VertexSource for Particle{
                 location: 1,
                 format:  f32::FORMAT,
                 offset: offset_of!(Self, energy) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding,
+                location: 2,
+                format:  u32::FORMAT,
+                offset: offset_of!(Self, emitter_entity) as u32,
             }
         ]
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn nothing_blocks(_:&glm::Vec3)->bool{
+        false
+    }
+
+    #[test]
+    fn particles_decay_to_death(){
+        let mut p = Particle::debris(glm::vec3(0., 100., 0.), glm::zero());
+        let mut steps = 0;
+        while p.is_alive(){
+            p.update(nothing_blocks);
+            steps += 1;
+        }
+        // rounding errors of repeated subtraction may cost one extra step
+        let expected = (1. / PARTICLE_DECAY).round() as u32;
+        assert!(steps == expected || steps == expected + 1, "{}", steps);
+        assert_eq!({p.energy}, 0.);
+    }
+
+    #[test]
+    fn only_debris_falls_down(){
+        let velocity = glm::vec3(0.1, 0., 0.);
+        let mut debris = Particle::debris(glm::vec3(5., 5., 5.), velocity);
+        let mut signal = Particle::signal(glm::vec3(5., 5., 5.), velocity, 3, 1, 1.);
+        let mut trail = Particle::trail(glm::vec3(5., 5., 5.), &glm::vec3(1., 0., 0.)).unwrap();
+        debris.update(nothing_blocks);
+        signal.update(nothing_blocks);
+        trail.update(nothing_blocks);
+        assert_eq!({debris.position}, glm::vec3(5.1, 5. - PARTICLE_GRAVITY, 5.));
+        assert_eq!({debris.velocity}, glm::vec3(0.1, -PARTICLE_GRAVITY, 0.) * PARTICLE_DAMPING);
+        assert_eq!({signal.position}, glm::vec3(5.1, 5., 5.));
+        assert_eq!({signal.velocity}, velocity * PARTICLE_DAMPING);
+        assert_eq!({trail.position}, glm::vec3(5., 5., 5.));
+        assert!(Particle::trail(glm::vec3(5., 5., 5.), &glm::vec3(PARTICLE_TRAIL_MIN_SPEED, 0., 0.)).is_none());
+        assert!(signal.is_signal() && !debris.is_signal() && !trail.is_signal());
+    }
+
+    #[test]
+    fn particles_stop_at_blocks(){
+        let below_ground = |p:&glm::Vec3|p.y < 1.;
+        let mut p = Particle::debris(glm::vec3(2.5, 1.05, 2.5), glm::vec3(0.1, -0.1, 0.));
+        p.update(below_ground);
+        assert_eq!({p.position}, glm::vec3(2.5, 1.05, 2.5));
+        assert_eq!({p.velocity}, glm::vec3(0., 0., 0.));
+        assert!(p.is_alive());
+        // gravity keeps pulling the debris down, but it never enters the block
+        while p.is_alive(){
+            p.update(below_ground);
+            assert!({p.position}.y >= 1., "{}", {p.position}.y);
+        }
+        assert_eq!({p.position}.x, 2.5);
+    }
+
+    #[test]
+    fn decay_keeps_the_channel(){
+        let mut p = Particle::signal(glm::zero(), glm::zero(), 0, 2, 0.5);
+        assert_eq!((p.channel(), p.strength()), (2, 0.5));
+        for _ in 0..10{
+            p.update(nothing_blocks);
+            assert_eq!(p.channel(), 2);
+        }
+        assert!((p.strength() - (0.5 - 10. * PARTICLE_DECAY)).abs() < 1e-5, "{}", p.strength());
+        let mut full = Particle::signal(glm::zero(), glm::zero(), 0, 1, 1.);
+        full.update(nothing_blocks);
+        assert_eq!(full.channel(), 1);
+        while full.is_alive(){
+            assert_eq!(full.channel(), 1);
+            full.update(nothing_blocks);
+        }
+    }
+
+    #[test]
+    fn dead_particles_are_swap_removed(){
+        let particle = |x:f32, energy:f32|{
+            let mut p = Particle::debris(glm::vec3(x, 0., 0.), glm::zero());
+            p.energy = energy;
+            p
+        };
+        let mut particles = vec![particle(0., 0.), particle(1., 1.), particle(2., 0.), particle(3., 1.), particle(4., 0.), particle(5., 1.)];
+        remove_dead_particles(&mut particles);
+        // slot 0 takes particle 5. Slot 2 first takes the dead particle 4, which is removed as well, and then particle 3
+        let xs:Vec<f32> = particles.iter().map(|p|{p.position}.x).collect();
+        assert_eq!(xs, vec![5., 1., 3.]);
+    }
+}
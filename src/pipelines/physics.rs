@@ -22,7 +22,7 @@ use crate::render::host_buffer::HostBuffer;
 use crate::pipelines::player::Player;
 use crate::render::uniform_types::Vec3;
 use crate::render::buffer_type::{Storage, Cpu, Uniform, GpuIndirect};
-use crate::render::buffer::{make_shader_buffer_barrier, Buffer};
+use crate::render::buffer::{make_shader_buffer_barrier, make_shader_dispatch_buffer_barrier, Buffer};
 use crate::pipelines::foundations::{Foundations, FoundationInitializer};
 use crate::pipelines::computable::{ComputeResources, Computable};
 use crate::render::submitter::Submitter;
@@ -36,6 +36,9 @@ pub struct PhysicsResources {
     update_bones: ShaderModule<Compute>,
     update_particles: ShaderModule<Compute>,
     update_ann_entities: ShaderModule<Compute>,
+    update_ann_entities_lifecycle: ShaderModule<Compute>,
+    update_htm_entities: ShaderModule<Compute>,
+    solve_constraints: ShaderModule<Compute>,
     // update_entity_lidars: ShaderModule<Compute>,
 }
 
@@ -50,14 +53,19 @@ impl PhysicsResources {
         let update_particles = ShaderModule::new(include_glsl!("assets/shaders/update_particles.comp", kind: comp) as &[u32], cmd_pool.device())?;
         let update_bones = ShaderModule::new(include_glsl!("assets/shaders/update_bones.comp", kind: comp) as &[u32], cmd_pool.device())?;
         let update_ann_entities = ShaderModule::new(include_glsl!("assets/shaders/update_ann_entities.comp", kind: comp, target: vulkan1_1) as &[u32], cmd_pool.device())?;
-        // let update_htm_entities = ShaderModule::new(include_glsl!("assets/shaders/update_htm_entities.comp", kind: comp) as &[u32], cmd_pool.device())?;
+        let update_ann_entities_lifecycle = ShaderModule::new(include_glsl!("assets/shaders/update_ann_entities_lifecycle.comp", kind: comp) as &[u32], cmd_pool.device())?;
+        let update_htm_entities = ShaderModule::new(include_glsl!("assets/shaders/update_htm_entities.comp", kind: comp) as &[u32], cmd_pool.device())?;
+        let solve_constraints = ShaderModule::new(include_glsl!("assets/shaders/solve_constraints.comp", kind: comp) as &[u32], cmd_pool.device())?;
         Ok(Self {
             broad_phase_collision_detection,
             broad_phase_collision_detection_cleanup,
             narrow_phase_collision_detection,
             update_particles,
             update_bones,
-            update_ann_entities
+            update_ann_entities,
+            update_ann_entities_lifecycle,
+            update_htm_entities,
+            solve_constraints,
         })
     }
 }
@@ -72,6 +80,9 @@ impl ComputeResources for PhysicsResources{
             narrow_phase_collision_detection,
             update_bones,
             update_ann_entities,
+            update_ann_entities_lifecycle,
+            update_htm_entities,
+            solve_constraints,
             // feed_forward_net,
         } = self;
         let mut descriptors = ComputeDescriptorsBuilder::new();
@@ -86,18 +97,26 @@ impl ComputeResources for PhysicsResources{
         descriptors.storage_buffer(foundations.htm_entities_buffer());//9
         descriptors.storage_buffer(foundations.ann_entities_buffer());//10
         descriptors.storage_buffer(foundations.rand_uint());//11
+        descriptors.storage_buffer(foundations.body_bones());//12
+        descriptors.storage_buffer(foundations.body_constraints());//13
+        descriptors.storage_buffer(foundations.body_muscles());//14
+        descriptors.storage_buffer(foundations.lifecycle_events());//15
+        descriptors.storage_buffer(foundations.blocks_to_be_inserted_or_removed());//16
+        descriptors.storage_buffer(foundations.constraints());//17
         let descriptors = descriptors.build(cmd_pool.device())?;
         // descriptors.storage_buffer(foundations.block_properties());
         // descriptors.storage_buffer(foundations.particles());
         // descriptors.storage_buffer(foundations.sensors());
 
-        // descriptors.storage_buffer(foundations.constraints());
         // descriptors.storage_buffer(foundations.muscles());
         let sc = foundations.specialization_constants().build();
         let broad_phase_collision_detection = descriptors.build("main", broad_phase_collision_detection,&sc)?;
         let broad_phase_collision_detection_cleanup = descriptors.build("main", broad_phase_collision_detection_cleanup,&sc)?;
         let update_bones = descriptors.build("main", update_bones,&sc)?;
         let update_ann_entities = descriptors.build("main", update_ann_entities,&sc)?;
+        let update_ann_entities_lifecycle = descriptors.build("main", update_ann_entities_lifecycle,&sc)?;
+        let update_htm_entities = descriptors.build("main", update_htm_entities,&sc)?;
+        let solve_constraints = descriptors.build("main", solve_constraints,&sc)?;
         // let feed_forward_net = descriptors.build("main", feed_forward_net)?;
         let update_particles = descriptors.build("main", update_particles,&sc)?;
         let narrow_phase_collision_detection = descriptors.build("main", narrow_phase_collision_detection,&sc)?;
@@ -105,6 +124,9 @@ impl ComputeResources for PhysicsResources{
             narrow_phase_collision_detection,
             update_particles,
             update_ann_entities,
+            update_ann_entities_lifecycle,
+            update_htm_entities,
+            solve_constraints,
             // feed_forward_net,
             broad_phase_collision_detection,
             broad_phase_collision_detection_cleanup,
@@ -123,6 +145,9 @@ pub struct Physics {
     update_bones: ComputePipeline,
     update_particles: ComputePipeline,
     update_ann_entities: ComputePipeline,
+    update_ann_entities_lifecycle: ComputePipeline,
+    update_htm_entities: ComputePipeline,
+    solve_constraints: ComputePipeline,
     // feed_forward_net: ComputePipeline,
 }
 
@@ -133,7 +158,8 @@ impl Computable for Physics {
             .bind_compute_pipeline(&self.broad_phase_collision_detection_cleanup)
             .dispatch_indirect(foundations.indirect().broad_phase_collision_detection_cleanup(), 0)
             .buffer_barriers(vk::PipelineStageFlags::COMPUTE_SHADER, vk::PipelineStageFlags::COMPUTE_SHADER, &[
-                make_shader_buffer_barrier(foundations.collision_grid())
+                make_shader_buffer_barrier(foundations.collision_grid()),
+                make_shader_buffer_barrier(foundations.global_mutables())
             ])
             .bind_compute_pipeline(&self.broad_phase_collision_detection)
             .dispatch_indirect(foundations.indirect().broad_phase_collision_detection(), 0)
@@ -142,33 +168,45 @@ impl Computable for Physics {
             ])
             .bind_compute_pipeline(&self.narrow_phase_collision_detection)
             .dispatch_indirect(foundations.indirect().narrow_phase_collision_detection(), 0)
+            .bind_compute_pipeline(&self.update_particles)
+            .dispatch_indirect(foundations.indirect().update_particles(), 0)
             .buffer_barriers(vk::PipelineStageFlags::COMPUTE_SHADER, vk::PipelineStageFlags::COMPUTE_SHADER, &[
-                make_shader_buffer_barrier(foundations.bones())
+                make_shader_buffer_barrier(foundations.bones()),
+                make_shader_buffer_barrier(foundations.particles()),
             ])
             .bind_compute_pipeline(&self.update_ann_entities)
             .dispatch_indirect(foundations.indirect().update_ann_entities(), 0)
+            .bind_compute_pipeline(&self.update_htm_entities)
+            .dispatch_indirect(foundations.indirect().update_htm_entities(), 0)
             .buffer_barriers(vk::PipelineStageFlags::COMPUTE_SHADER, vk::PipelineStageFlags::COMPUTE_SHADER, &[
                 make_shader_buffer_barrier(foundations.bones())
             ])
             .bind_compute_pipeline(&self.update_bones)
             .dispatch_indirect(foundations.indirect().update_bones(), 0)
+            .buffer_barriers(vk::PipelineStageFlags::COMPUTE_SHADER, vk::PipelineStageFlags::COMPUTE_SHADER, &[
+                make_shader_buffer_barrier(foundations.bones()),
+                make_shader_buffer_barrier(foundations.ann_entities_buffer()),
+                make_shader_buffer_barrier(foundations.particles()),
+            ])
+            .bind_compute_pipeline(&self.solve_constraints);
+        for _ in 0..foundations.cap().constraint_solver_iterations {
+            cmd.dispatch_indirect(foundations.indirect().solve_constraints(), 0)
+                .buffer_barriers(vk::PipelineStageFlags::COMPUTE_SHADER, vk::PipelineStageFlags::COMPUTE_SHADER, &[
+                    make_shader_buffer_barrier(foundations.bones())
+                ]);
+        }
+        cmd
+            .bind_compute_pipeline(&self.update_ann_entities_lifecycle)
+            .dispatch_1d(1)
+            .buffer_barriers(vk::PipelineStageFlags::COMPUTE_SHADER, vk::PipelineStageFlags::DRAW_INDIRECT | vk::PipelineStageFlags::COMPUTE_SHADER, &[
+                make_shader_dispatch_buffer_barrier(foundations.indirect().super_buffer()),
+                make_shader_buffer_barrier(foundations.constraints())
+            ])
 
-            // .bind_compute_pipeline(&self.update_particles)
-            // .dispatch_indirect(foundations.indirect().update_particles(), 0)
             // .bind_compute_pipeline(&self.update_entity_lidars)
             // .dispatch_indirect(foundations.indirect().update_entity_lidars(), 0)
 
 
-            // .buffer_barriers(vk::PipelineStageFlags::COMPUTE_SHADER, vk::PipelineStageFlags::COMPUTE_SHADER, &[
-            //     make_shader_buffer_barrier(foundations.particles()),
-            //     make_shader_buffer_barrier(foundations.constraints())
-            // ])
-            // .bind_compute_pipeline(&self.solve_constraints)
-            // .dispatch_indirect(foundations.indirect().solve_constraints(), 0)
-            // .buffer_barriers(vk::PipelineStageFlags::COMPUTE_SHADER, vk::PipelineStageFlags::COMPUTE_SHADER, &[
-            //     make_shader_buffer_barrier(foundations.particles()),
-            // ])
-
             // .bind_compute_pipeline(&self.agent_sensory_inputs)
             // .dispatch_indirect(foundations.indirect().agent_sensory_input_update(), 0)
             // .buffer_barriers(vk::PipelineStageFlags::COMPUTE_SHADER, vk::PipelineStageFlags::COMPUTE_SHADER, &[
@@ -181,11 +219,13 @@ impl Computable for Physics {
     }
 
     fn update_uniforms(&mut self, player: &mut Player,foundations:&mut Foundations) {
+        let player_event = &mut foundations.player_event_uniform_mut().as_slice_mut()[0];
         if let Some(event) = player.pop_event(){
-            foundations.player_event_uniform_mut().as_slice_mut()[0] = event;
+            *player_event = event;
         }else {
-            foundations.player_event_uniform_mut().as_slice_mut()[0].make_nothing();
+            player_event.make_nothing();
         }
+        player_event.set_hold_target(player.hold_target());
     }
 
 }
\ No newline at end of file
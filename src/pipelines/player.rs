@@ -5,12 +5,21 @@ use crate::pipelines::mvp_uniforms::MvpUniforms;
 use crate::pipelines::display::Display;
 use crate::pipelines::game::GameResources;
 use std::collections::VecDeque;
-use crate::pipelines::player_event::PlayerEvent;
+use crate::pipelines::player_event::{PlayerEvent, SpawnBrain};
 use crate::blocks::block_properties::{AIR, GLASS, DIRT, PLANK, STONE, SAND, COBBLESTONE, COAL_ORE, OAK_WOOD, GRASS, BRICK, WATER};
 use crate::pipelines::physics::PhysicsResources;
 use crate::pipelines::renderable::RenderResources;
 use crate::pipelines::computable::ComputeResources;
 use num_traits::FloatConst;
+use crate::pipelines::player_physics::{NearbyBlocks, Hitbox, move_and_collide, PLAYER_HALF_WIDTH, PLAYER_HEIGHT, PLAYER_CROUCH_HEIGHT, PLAYER_EYE_OFFSET, PLAYER_GRAVITY, PLAYER_TERMINAL_VELOCITY, PLAYER_JUMP_SPEED, PLAYER_WALK_SPEED, PLAYER_SPRINT_SPEED, PLAYER_CROUCH_SPEED, PLAYER_SWIM_SPEED, PLAYER_BUOYANCY};
+
+/**Longest time (in seconds) simulated by a single frame of a walking player. Longer frames (for example when the window
+is being dragged) would otherwise throw the player far away*/
+const MAX_WALK_DELTA_TIME:f32 = 0.1;
+/**Bones can be grabbed from this far away*/
+const GRAB_REACH:f32 = 16.;
+/**Held bone is pulled towards the point this far in front of the player*/
+const HOLD_DISTANCE:f32 = 3.;
 
 pub struct Player {
     projection_matrix: glm::Mat4,
@@ -24,6 +33,15 @@ pub struct Player {
     rotation_speed: f32,
     events:VecDeque<PlayerEvent>,
     ray_trace_vector: glm::Vec4,
+    /**Free-flying camera that passes through blocks. Otherwise the player walks (see player_physics.rs)*/
+    fly_mode: bool,
+    /**Velocity of the walking player in blocks per second*/
+    velocity: glm::Vec3,
+    on_ground: bool,
+    /**Current height of the hitbox. Lower while crouching*/
+    hitbox_height: f32,
+    /**Point towards which the held bone is pulled, or None if the player doesn't hold anything*/
+    hold_target: Option<glm::Vec3>,
 }
 
 impl Player {
@@ -33,9 +51,73 @@ impl Player {
     pub fn mvp_uniforms(&self) -> &MvpUniforms{
         &self.mvp_uniforms
     }
+    pub fn set_bone_interpolation(&mut self, bone_interpolation:f32){
+        self.mvp_uniforms.bone_interpolation = bone_interpolation
+    }
     pub fn location(&self) -> &glm::Vec3{
         &self.location
     }
+    pub fn hold_target(&self) -> Option<glm::Vec3>{
+        self.hold_target
+    }
+    pub fn is_fly_mode(&self) -> bool{
+        self.fly_mode
+    }
+    fn feet(&self) -> glm::Vec3{
+        self.location - glm::vec3(0., self.hitbox_height - PLAYER_EYE_OFFSET, 0.)
+    }
+    /**Applies gravity, jumping, swimming and walking to the velocity and then moves the hitbox through
+    the blocks around the player, which are read back from the GPU*/
+    fn walk<P: RenderResources, C: ComputeResources, A: ComputeResources>(&mut self, display:&mut Display<P,C, A>, input: &Input, inverse_rotation:&glm::Quat, delta_time:f32) ->Result<(),failure::Error>{
+        let feet = self.feet();
+        let world_size = *display.foundations().world_size();
+        let reach = (glm::length(&self.velocity) + PLAYER_JUMP_SPEED) * delta_time + 1.;
+        let blocks = match NearbyBlocks::reachable_box(&world_size, &Hitbox::from_feet(&feet, PLAYER_HALF_WIDTH, PLAYER_HEIGHT), reach){
+            Some((from, to)) => NearbyBlocks::new(world_size, from, to, display.download_world_box(from, to)?),
+            // the player is far above (or beside) the world, so there is nothing to collide with
+            None => NearbyBlocks::empty(world_size),
+        };
+        let is_solid = |x, y, z| blocks.is_solid_at(x, y, z);
+
+        let standing = Hitbox::from_feet(&feet, PLAYER_HALF_WIDTH, PLAYER_HEIGHT);
+        // a crouching player stands up only if there is enough room above
+        let height = if input.is_down() || standing.any_block(is_solid) { PLAYER_CROUCH_HEIGHT } else { PLAYER_HEIGHT };
+        let mut hitbox = Hitbox::from_feet(&feet, PLAYER_HALF_WIDTH, height);
+        let in_fluid = hitbox.any_block(|x, y, z| blocks.is_fluid_at(x, y, z));
+        let crouching = height < PLAYER_HEIGHT && !in_fluid;
+
+        let direction = input.get_direction_unit_vector();
+        let direction = glm::quat_rotate_vec3(inverse_rotation, &glm::vec3(direction.x, 0., direction.z));
+        let direction = glm::vec3(direction.x, 0., direction.z);
+        let direction = if glm::length(&direction) == 0. { direction } else { glm::normalize(&direction) };
+        let speed = if in_fluid {
+            PLAYER_SWIM_SPEED
+        } else if crouching {
+            PLAYER_CROUCH_SPEED
+        } else if input.is_sprint() {
+            PLAYER_SPRINT_SPEED
+        } else {
+            PLAYER_WALK_SPEED
+        };
+        self.velocity.x = direction.x * speed;
+        self.velocity.z = direction.z * speed;
+        if in_fluid {
+            self.velocity.y -= PLAYER_GRAVITY * PLAYER_BUOYANCY * delta_time;
+            if input.is_up() {
+                self.velocity.y = PLAYER_SWIM_SPEED;
+            }
+            self.velocity.y = self.velocity.y.max(-PLAYER_SWIM_SPEED);
+        } else {
+            if input.is_up() && self.on_ground {
+                self.velocity.y = PLAYER_JUMP_SPEED;
+            }
+            self.velocity.y = (self.velocity.y - PLAYER_GRAVITY * delta_time).max(-PLAYER_TERMINAL_VELOCITY);
+        }
+        self.on_ground = move_and_collide(&mut hitbox, &mut self.velocity, delta_time, crouching, &is_solid);
+        self.hitbox_height = height;
+        self.location = hitbox.feet() + glm::vec3(0., height - PLAYER_EYE_OFFSET, 0.);
+        Ok(())
+    }
     pub fn new() -> Self {
         Self {
             projection_matrix: glm::identity::<f32, 4>(),
@@ -48,7 +130,12 @@ impl Player {
             player_reach: 4f32,
             rotation_speed: 1f32/1024f32,
             events: VecDeque::with_capacity(4),
-            ray_trace_vector: glm::zero()
+            ray_trace_vector: glm::zero(),
+            fly_mode: true,
+            velocity: glm::zero(),
+            on_ground: false,
+            hitbox_height: PLAYER_HEIGHT,
+            hold_target: None,
         }
     }
 
@@ -68,16 +155,48 @@ impl Player {
                 * glm::quat_angle_axis(normalized_x, &glm::vec3(0f32, 1f32, 0f32));
         }
 
-        let movement_vector = input.get_direction_unit_vector() * self.movement_speed * fps_counter.delta_f32();
+        if input.toggle_fly() {
+            self.fly_mode = !self.fly_mode;
+            self.velocity = glm::zero();
+            self.on_ground = false;
+            println!("Fly mode: {}", self.fly_mode);
+        }
         let inverse_rotation = glm::quat_inverse(&self.rotation);
-        let movement_vector = glm::quat_rotate_vec3(&inverse_rotation, &movement_vector);
-        // display.pipeline().a().world().blocks().zero_out_velocity_vector_on_hitbox_collision(&mut movement_vector, &(location-glm::vec3(0.4f32,1.5,0.4)),&(location+glm::vec3(0.4f32,0.3,0.4)));
-        self.location += movement_vector;
+        if self.fly_mode {
+            let movement_vector = input.get_direction_unit_vector() * self.movement_speed * fps_counter.delta_f32();
+            let movement_vector = glm::quat_rotate_vec3(&inverse_rotation, &movement_vector);
+            self.location += movement_vector;
+        } else {
+            self.walk(display, input, &inverse_rotation, (fps_counter.delta_f32() / 1000.).min(MAX_WALK_DELTA_TIME))?;
+        }
         self.ray_trace_vector = glm::quat_rotate_vec(&inverse_rotation, &glm::vec4(0f32, 0., -self.player_reach, 0.));
         if input.has_mouse_left_click() || input.has_mouse_right_click() {
             let block = if input.has_mouse_left_click() { AIR } else { self.block_in_hand };
             self.events.push_back(PlayerEvent::set_block(self.location,self.ray_trace_vector.xyz().clone_owned(), block));
         }
+        let spawn = if input.spawn_random() {
+            Some(SpawnBrain::Random)
+        } else if input.spawn_clone() {
+            Some(SpawnBrain::Clone)
+        } else if input.spawn_from_file() && display.has_spawn_template() {
+            Some(SpawnBrain::Template)
+        } else {
+            None
+        };
+        if let Some(brain) = spawn {
+            self.events.push_back(PlayerEvent::spawn_entity(self.location, self.ray_trace_vector.xyz(), brain));
+        }
+        if input.attach_rope(){
+            self.events.push_back(PlayerEvent::attach_rope(self.location, self.ray_trace_vector.xyz()));
+        }
+        if input.detach_ropes(){
+            self.events.push_back(PlayerEvent::detach_ropes());
+        }
+        let view_direction = glm::normalize(&self.ray_trace_vector.xyz());
+        if input.has_mouse_middle_click(){
+            self.events.push_back(PlayerEvent::grab(self.location, view_direction * GRAB_REACH));
+        }
+        self.hold_target = if input.has_mouse_middle_down() { Some(self.location + view_direction * HOLD_DISTANCE) } else { None };
         if input.is_q(){
             self.events.push_back(PlayerEvent::throw(self.location, self.ray_trace_vector.xyz()*0.03));
         }
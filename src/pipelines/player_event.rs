@@ -9,6 +9,21 @@ pub enum EventType{
     Nothing = 0,
     Throw = 1,
     SetBlock = 2,
+    SpawnEntity = 3,
+    AttachRope = 4,
+    DetachRopes = 5,
+    Grab = 6,
+}
+/**Where the brain of a spawned entity comes from*/
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum SpawnBrain{
+    /**Randomly initialised, just like the entities generated together with the world*/
+    Random = 0,
+    /**Copy of the ANN entity hit by the ray cast. The copy is placed next to the original*/
+    Clone = 1,
+    /**Copy of the spawn template, see Foundations::set_spawn_template*/
+    Template = 2,
 }
 #[derive(Copy, Clone)]
 #[repr(C, packed)]
@@ -19,6 +34,9 @@ pub struct PlayerEvent {
     u32_slot1: u32,
     uvec3_slot1: glm::UVec3,
     event_type: EventType,
+    /**Not a part of the event. Point towards which the held bone is pulled, see set_hold_target*/
+    hold_target: glm::Vec3,
+    is_holding: u32,
 }
 impl Debug for PlayerEvent{
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -36,6 +54,25 @@ impl Debug for PlayerEvent{
                 let b = self.u32_slot1;
                 write!(f,"SetBlock{{block_idx={}, block_id={}}}",a,b)
             }
+            EventType::SpawnEntity => {
+                let a = self.vec3_slot0;
+                let b = self.vec3_slot1;
+                let c = self.u32_slot0;
+                write!(f,"SpawnEntity{{position={}, ray_cast_direction={}, brain={}}}",a,b,c)
+            }
+            EventType::AttachRope => {
+                let a = self.vec3_slot0;
+                let b = self.vec3_slot1;
+                write!(f,"AttachRope{{position={}, ray_cast_direction={}}}",a,b)
+            }
+            EventType::DetachRopes => {
+                write!(f,"DetachRopes")
+            }
+            EventType::Grab => {
+                let a = self.vec3_slot0;
+                let b = self.vec3_slot1;
+                write!(f,"Grab{{position={}, ray_cast_direction={}}}",a,b)
+            }
         }
     }
 }
@@ -47,7 +84,9 @@ impl PlayerEvent{
             u32_slot0: 0,
             vec3_slot1: Default::default(),
             u32_slot1: 0,
-            uvec3_slot1: Default::default()
+            uvec3_slot1: Default::default(),
+            hold_target: Default::default(),
+            is_holding: 0
         }
     }
     pub fn make_nothing(&mut self){
@@ -60,12 +99,63 @@ impl PlayerEvent{
             u32_slot0: block.id(),
             vec3_slot1: ray_cast_direction,
             u32_slot1: 0,
-            uvec3_slot1: Default::default()
+            uvec3_slot1: Default::default(),
+            hold_target: Default::default(),
+            is_holding: 0
         }
     }
     pub fn break_block(position:glm::Vec3,ray_cast_direction:glm::Vec3)->Self{
         Self::set_block(position,ray_cast_direction,AIR)
     }
+    /**Spawns an ANN entity in the last air block before the ray cast hits the ground (or at the end of the ray if it hits nothing)*/
+    pub fn spawn_entity(position:glm::Vec3,ray_cast_direction:glm::Vec3, brain:SpawnBrain)->Self{
+        Self{
+            event_type: EventType::SpawnEntity,
+            vec3_slot0: position,
+            u32_slot0: brain as u32,
+            vec3_slot1: ray_cast_direction,
+            u32_slot1: 0,
+            uvec3_slot1: Default::default(),
+            hold_target: Default::default(),
+            is_holding: 0
+        }
+    }
+    /**The first event picks the bone hit by the ray cast as one end of a rope. The next event that hits
+    a different bone ties the rope between the two. The rope is as long as the distance between the bones at that moment*/
+    pub fn attach_rope(position:glm::Vec3,ray_cast_direction:glm::Vec3)->Self{
+        Self{
+            event_type: EventType::AttachRope,
+            vec3_slot0: position,
+            u32_slot0: 0,
+            vec3_slot1: ray_cast_direction,
+            u32_slot1: 0,
+            uvec3_slot1: Default::default(),
+            hold_target: Default::default(),
+            is_holding: 0
+        }
+    }
+    /**Removes all rods, ropes and springs of the world*/
+    pub fn detach_ropes()->Self{
+        Self{
+            event_type: EventType::DetachRopes,
+            ..Self::nothing()
+        }
+    }
+    /**Picks the bone hit by the ray cast as the one held by the player (or releases the held bone if nothing is hit)*/
+    pub fn grab(position:glm::Vec3,ray_cast_direction:glm::Vec3)->Self{
+        Self{
+            event_type: EventType::Grab,
+            vec3_slot0: position,
+            vec3_slot1: ray_cast_direction,
+            ..Self::nothing()
+        }
+    }
+    /**Set in every step, no matter which event is sent. The held bone is pulled towards the target.
+    None releases the held bone*/
+    pub fn set_hold_target(&mut self, hold_target:Option<glm::Vec3>){
+        self.is_holding = hold_target.is_some() as u32;
+        self.hold_target = hold_target.unwrap_or_default();
+    }
     pub fn throw(position:glm::Vec3,velocity:glm::Vec3)->Self{
         Self{
             event_type: EventType::Throw,
@@ -73,7 +163,9 @@ impl PlayerEvent{
             u32_slot0: 0,
             vec3_slot1: velocity,
             u32_slot1: 0,
-            uvec3_slot1: Default::default()
+            uvec3_slot1: Default::default(),
+            hold_target: Default::default(),
+            is_holding: 0
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,293 @@
+use crate::blocks::{Block, BlockId, WorldSize};
+use crate::blocks::block_properties::{AIR, BEDROCK};
+
+/**Half of the width (and depth) of the player's hitbox, in blocks*/
+pub const PLAYER_HALF_WIDTH:f32 = 0.3;
+/**Height of the hitbox of a standing player*/
+pub const PLAYER_HEIGHT:f32 = 1.8;
+/**Height of the hitbox of a crouching player*/
+pub const PLAYER_CROUCH_HEIGHT:f32 = 1.5;
+/**Distance between the eyes (the camera) and the top of the hitbox*/
+pub const PLAYER_EYE_OFFSET:f32 = 0.2;
+/**All speeds below are measured in blocks per second and accelerations in blocks per second squared*/
+pub const PLAYER_GRAVITY:f32 = 32.;
+pub const PLAYER_TERMINAL_VELOCITY:f32 = 78.;
+/**Jumps approximately 1.25 blocks high*/
+pub const PLAYER_JUMP_SPEED:f32 = 9.;
+pub const PLAYER_WALK_SPEED:f32 = 4.3;
+pub const PLAYER_SPRINT_SPEED:f32 = 5.6;
+pub const PLAYER_CROUCH_SPEED:f32 = 1.3;
+pub const PLAYER_SWIM_SPEED:f32 = 2.;
+/**Gravity is weakened by this factor in fluids and the player sinks no faster than PLAYER_SWIM_SPEED*/
+pub const PLAYER_BUOYANCY:f32 = 0.25;
+/**Gap kept between the hitbox and the blocks that stopped it, so that it never ends up inside of them*/
+const COLLISION_EPSILON:f32 = 0.001;
+
+/**CPU copy of a small box of the world around the player read back from the GPU (see Display::download_world_box).
+Blocks below the world and beyond its sides are solid. Other blocks outside of the box are air*/
+pub struct NearbyBlocks{
+    world_size:WorldSize,
+    from:[usize;3],
+    to:[usize;3],
+    blocks:Vec<Block>,
+}
+
+impl NearbyBlocks{
+    /**Blocks of the box from (inclusive) to (exclusive), ordered by y, then z, then x*/
+    pub fn new(world_size:WorldSize, from:[usize;3], to:[usize;3], blocks:Vec<Block>) -> Self{
+        assert_eq!(blocks.len(), (0..3).map(|axis| to[axis].saturating_sub(from[axis])).product::<usize>());
+        Self{world_size, from, to, blocks}
+    }
+    /**No blocks are known, so everything inside of the world is air*/
+    pub fn empty(world_size:WorldSize) -> Self{
+        Self::new(world_size, [0;3], [0;3], Vec::new())
+    }
+    /**Box of blocks that a hitbox can reach when moving by at most reach blocks along every axis, clipped to the world.
+    Returns None if nothing of the world is in reach*/
+    pub fn reachable_box(world_size:&WorldSize, hitbox:&Hitbox, reach:f32) -> Option<([usize;3],[usize;3])>{
+        let dimensions = [world_size.world_width(), world_size.height(), world_size.world_depth()];
+        let mut from = [0;3];
+        let mut to = [0;3];
+        for axis in 0..3{
+            let limit = dimensions[axis] as f32;
+            from[axis] = (hitbox.min[axis] - reach).floor().max(0.).min(limit) as usize;
+            to[axis] = (hitbox.max[axis] + reach).ceil().max(0.).min(limit) as usize;
+            if to[axis] <= from[axis]{
+                return None
+            }
+        }
+        Some((from, to))
+    }
+    pub fn block_id_at(&self, x:i32, y:i32, z:i32) -> BlockId{
+        if y < 0 || x < 0 || z < 0 || x as usize >= self.world_size.world_width() || z as usize >= self.world_size.world_depth() {
+            return BEDROCK
+        }
+        let pos = [x as usize, y as usize, z as usize];
+        if (0..3).any(|axis| pos[axis] < self.from[axis] || pos[axis] >= self.to[axis]) {
+            AIR
+        } else {
+            let width = self.to[0] - self.from[0];
+            let depth = self.to[2] - self.from[2];
+            let idx = ((pos[1] - self.from[1]) * depth + pos[2] - self.from[2]) * width + pos[0] - self.from[0];
+            self.blocks[idx].block_id()
+        }
+    }
+    pub fn is_solid_at(&self, x:i32, y:i32, z:i32) -> bool{
+        !self.block_id_at(x, y, z).is_traversable()
+    }
+    pub fn is_fluid_at(&self, x:i32, y:i32, z:i32) -> bool{
+        self.block_id_at(x, y, z).is_fluid()
+    }
+}
+
+/**Axis aligned box of the player. Min is the corner with the smallest coordinates*/
+#[derive(Copy, Clone, Debug)]
+pub struct Hitbox{
+    pub min:glm::Vec3,
+    pub max:glm::Vec3,
+}
+
+impl Hitbox{
+    /**Hitbox standing on the given point*/
+    pub fn from_feet(feet:&glm::Vec3, half_width:f32, height:f32) -> Self{
+        Self{
+            min:glm::vec3(feet.x - half_width, feet.y, feet.z - half_width),
+            max:glm::vec3(feet.x + half_width, feet.y + height, feet.z + half_width),
+        }
+    }
+    pub fn feet(&self) -> glm::Vec3{
+        glm::vec3((self.min.x + self.max.x) / 2., self.min.y, (self.min.z + self.max.z) / 2.)
+    }
+    /**Blocks overlapped by the hitbox, as inclusive ranges of coordinates along every axis*/
+    fn block_range(&self) -> [(i32,i32);3]{
+        let mut range = [(0,0);3];
+        for axis in 0..3{
+            range[axis] = (self.min[axis].floor() as i32, (self.max[axis].ceil() as i32 - 1).max(self.min[axis].floor() as i32));
+        }
+        range
+    }
+    /**True if any of the overlapped blocks satisfies the predicate*/
+    pub fn any_block(&self, mut f:impl FnMut(i32,i32,i32)->bool) -> bool{
+        let [(x0,x1),(y0,y1),(z0,z1)] = self.block_range();
+        for y in y0..=y1{
+            for z in z0..=z1{
+                for x in x0..=x1{
+                    if f(x, y, z){
+                        return true
+                    }
+                }
+            }
+        }
+        false
+    }
+    fn translated(&self, axis:usize, distance:f32) -> Self{
+        let mut moved = *self;
+        moved.min[axis] += distance;
+        moved.max[axis] += distance;
+        moved
+    }
+    /**Moves the hitbox along one axis and stops it just before the first solid block in the way.
+    All blocks between the start and the end are checked, so fast movement can't tunnel through walls.
+    Returns the distance actually travelled*/
+    pub fn move_along_axis(&mut self, axis:usize, distance:f32, is_solid:&impl Fn(i32,i32,i32)->bool) -> f32{
+        if distance == 0.{
+            return 0.
+        }
+        let mut swept = *self;
+        if distance > 0. {
+            swept.min[axis] = self.max[axis];
+            swept.max[axis] = self.max[axis] + distance;
+        } else {
+            swept.min[axis] = self.min[axis] + distance;
+            swept.max[axis] = self.min[axis];
+        }
+        let mut travelled = distance;
+        swept.any_block(|x, y, z| {
+            let block = [x, y, z][axis] as f32;
+            // blocks that the hitbox is already stuck in don't stop it, so that it can get out
+            let is_ahead = if distance > 0. { block >= self.max[axis] - 2. * COLLISION_EPSILON } else { block + 1. <= self.min[axis] + 2. * COLLISION_EPSILON };
+            if is_ahead && is_solid(x, y, z){
+                travelled = if distance > 0. {
+                    travelled.min(block - self.max[axis] - COLLISION_EPSILON).max(0.)
+                } else {
+                    travelled.max(block + 1. - self.min[axis] + COLLISION_EPSILON).min(0.)
+                };
+            }
+            false
+        });
+        *self = self.translated(axis, travelled);
+        travelled
+    }
+    /**True if there is a solid block right under the hitbox*/
+    pub fn is_on_ground(&self, is_solid:&impl Fn(i32,i32,i32)->bool) -> bool{
+        let mut below = *self;
+        below.max.y = self.min.y;
+        below.min.y = self.min.y - 2. * COLLISION_EPSILON;
+        below.any_block(|x, y, z| is_solid(x, y, z))
+    }
+}
+
+/**Moves the hitbox by velocity*delta_time, first vertically and then horizontally, and zeroes the components of
+velocity that were stopped by blocks. A crouching player on the ground never walks off an edge.
+Returns true if the hitbox ends up standing on the ground*/
+pub fn move_and_collide(hitbox:&mut Hitbox, velocity:&mut glm::Vec3, delta_time:f32, crouching:bool, is_solid:&impl Fn(i32,i32,i32)->bool) -> bool{
+    let was_on_ground = hitbox.is_on_ground(is_solid);
+    for &axis in [1usize, 0, 2].iter(){
+        let distance = velocity[axis] * delta_time;
+        let before = *hitbox;
+        let travelled = hitbox.move_along_axis(axis, distance, is_solid);
+        if axis != 1 && crouching && was_on_ground && !hitbox.is_on_ground(is_solid){
+            *hitbox = before;
+            velocity[axis] = 0.;
+        } else if travelled != distance{
+            velocity[axis] = 0.;
+        }
+    }
+    hitbox.is_on_ground(is_solid)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::blocks::block_properties::STONE;
+
+    fn standing_at(x:f32, y:f32, z:f32) -> Hitbox{
+        Hitbox::from_feet(&glm::vec3(x, y, z), PLAYER_HALF_WIDTH, PLAYER_HEIGHT)
+    }
+
+    fn assert_close(a:f32, b:f32){
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn falling_player_lands_on_the_ground(){
+        let is_solid = |_x, y, _z| y < 1;
+        let mut hitbox = standing_at(0.5, 3., 0.5);
+        let mut velocity = glm::vec3(0., 0., 0.);
+        let mut on_ground = false;
+        for _ in 0..100{
+            velocity.y = (velocity.y - PLAYER_GRAVITY * 0.02).max(-PLAYER_TERMINAL_VELOCITY);
+            on_ground = move_and_collide(&mut hitbox, &mut velocity, 0.02, false, &is_solid);
+            if on_ground{
+                break
+            }
+        }
+        assert!(on_ground);
+        assert_close(hitbox.min.y, 1. + COLLISION_EPSILON);
+        assert_eq!(velocity.y, 0.);
+    }
+
+    #[test]
+    fn player_stops_flush_against_a_wall(){
+        let is_solid = |x, y, _z| y < 1 || x >= 5;
+        let mut hitbox = standing_at(3.5, 1. + COLLISION_EPSILON, 0.5);
+        let mut velocity = glm::vec3(PLAYER_SPRINT_SPEED, 0., 1.);
+        assert!(move_and_collide(&mut hitbox, &mut velocity, 1., false, &is_solid));
+        assert_close(hitbox.max.x, 5. - COLLISION_EPSILON);
+        assert_eq!(velocity.x, 0.);
+        // sliding along the wall is not affected
+        assert_eq!(velocity.z, 1.);
+        assert_close(hitbox.feet().z, 1.5);
+    }
+
+    #[test]
+    fn jump_stops_at_the_ceiling(){
+        let is_solid = |_x, y, _z| y < 1 || y >= 3;
+        let mut hitbox = standing_at(0.5, 1. + COLLISION_EPSILON, 0.5);
+        let mut velocity = glm::vec3(0., PLAYER_JUMP_SPEED, 0.);
+        assert!(!move_and_collide(&mut hitbox, &mut velocity, 0.1, false, &is_solid));
+        assert_close(hitbox.max.y, 3. - COLLISION_EPSILON);
+        assert_eq!(velocity.y, 0.);
+    }
+
+    #[test]
+    fn crouching_player_does_not_walk_off_an_edge(){
+        let is_solid = |x, y, _z| y < 1 && x < 2;
+        let start = standing_at(1.5, 1. + COLLISION_EPSILON, 0.5);
+        let mut hitbox = start;
+        let mut velocity = glm::vec3(PLAYER_WALK_SPEED, 0., 0.);
+        assert!(move_and_collide(&mut hitbox, &mut velocity, 0.5, true, &is_solid));
+        assert_eq!(hitbox.min, start.min);
+        assert_eq!(velocity.x, 0.);
+        let mut velocity = glm::vec3(PLAYER_WALK_SPEED, 0., 0.);
+        assert!(!move_and_collide(&mut hitbox, &mut velocity, 0.5, false, &is_solid));
+        assert!(hitbox.min.x > 2.);
+    }
+
+    #[test]
+    fn fast_fall_does_not_tunnel_through_a_thin_floor(){
+        let is_solid = |_x, y, _z| y == 0;
+        let mut hitbox = standing_at(0.5, 60., 0.5);
+        let mut velocity = glm::vec3(0., -PLAYER_TERMINAL_VELOCITY, 0.);
+        assert!(move_and_collide(&mut hitbox, &mut velocity, 1., false, &is_solid));
+        assert_close(hitbox.min.y, 1. + COLLISION_EPSILON);
+        assert_eq!(velocity.y, 0.);
+    }
+
+    #[test]
+    fn nearby_blocks_cover_only_the_reachable_box(){
+        let world_size = WorldSize::new(1, 1);
+        let hitbox = standing_at(4.5, 10., 4.5);
+        let (from, to) = NearbyBlocks::reachable_box(&world_size, &hitbox, 1.).unwrap();
+        assert_eq!((from, to), ([3, 9, 3], [6, 13, 6]));
+        let mut blocks = vec![Block::new(AIR);3 * 4 * 3];
+        // block (5,11,3) is the last one of the first row of the third layer
+        blocks[2 * 3 * 3 + 2] = Block::new(STONE);
+        let blocks = NearbyBlocks::new(world_size, from, to, blocks);
+        assert!(blocks.is_solid_at(5, 11, 3));
+        assert!(!blocks.is_solid_at(4, 11, 3));
+        assert_eq!(blocks.block_id_at(0, 0, 0), AIR);
+        assert_eq!(blocks.block_id_at(-1, 10, 4), BEDROCK);
+        assert_eq!(blocks.block_id_at(4, -1, 4), BEDROCK);
+    }
+
+    #[test]
+    fn nothing_is_reachable_above_the_world(){
+        let world_size = WorldSize::new(1, 1);
+        let hitbox = standing_at(4.5, world_size.height() as f32 + 5., 4.5);
+        assert!(NearbyBlocks::reachable_box(&world_size, &hitbox, 1.).is_none());
+        let blocks = NearbyBlocks::empty(world_size);
+        assert!(!blocks.is_solid_at(4, 10, 4));
+        assert!(blocks.is_solid_at(4, -1, 4));
+    }
+}
@@ -34,8 +34,6 @@ use crate::render::host_buffer::HostBuffer;
 use crate::pipelines::player_event::PlayerEvent;
 use crate::render::compute::{ComputeDescriptorsBuilder, ComputeDescriptors};
 use crate::render::specialization_constants::SpecializationConstants;
-use crate::neat::htm_entity::{HtmEntity, ENTITY_MAX_LIDAR_COUNT};
-use crate::neat::ann_entity::AnnEntity;
 use crate::pipelines::foundations::{FoundationInitializer, Foundations};
 use crate::pipelines::perlin_noise_map::{PerlinNoiseMap, RandomMap};
 use crate::render::shader_module::{ShaderModule, Compute};
@@ -181,6 +179,9 @@ impl WorldGeneratorInitializer {
         descriptors.storage_buffer(&random_vals_buffer);//5
         descriptors.storage_buffer(foundations.rand_uint());//6
         descriptors.storage_buffer(foundations.ann_entities_buffer());//7
+        descriptors.storage_buffer(foundations.htm_entities_buffer());//8
+        descriptors.storage_buffer(foundations.body_bones());//9
+        descriptors.storage_buffer(foundations.lifecycle_events());//10
         let descriptors = descriptors.build(cmd_pool.device())?;
         let large_scale = large_scale.take()?.take_gpu();
         let chunk_scale = chunk_scale.take()?.take_gpu();
@@ -202,9 +203,10 @@ impl WorldGeneratorInitializer {
         let subgroup_size = cmd_pool.device().get_max_subgroup_size();
         let world_area = foundations.world_size().world_area() as u32;
         let world_volume = foundations.world_size().world_volume() ;
-        let agents_groups = (foundations.default_global_mutables().ann_entities+subgroup_size-1)/subgroup_size;
+        let agents = foundations.default_global_mutables().ann_entities+foundations.default_global_mutables().htm_entities;
+        let agents_groups = (agents+subgroup_size-1)/subgroup_size;
         assert_eq!(world_area%subgroup_size,0,"World area {} is not divisible by subgroup size {}",world_area,cmd_pool.device().get_max_subgroup_size());
-        assert!(foundations.default_global_mutables().ann_entities<foundations.cap().max_rand_uint as u32/ /*xz dimensions*/2);
+        assert!(agents<foundations.cap().max_rand_uint as u32/ /*xz dimensions*/2);
 
         println!("Generating world!");
         let area_groups = world_area/subgroup_size;
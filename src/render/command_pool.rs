@@ -66,6 +66,32 @@ impl CommandBuffer {
         self
     }
 
+    /**Copies the given ranges of elements (offset into src and length) one after another to the beginning of dst*/
+    pub fn copy_ranges<V:Copy, T1: BufferType, T2: BufferType>(&mut self, src: &impl Buffer<V, T1>, dst: &impl Buffer<V, T2>, ranges: &[(vk::DeviceSize, vk::DeviceSize)]) -> &mut Self {
+        let elem = std::mem::size_of::<V>() as vk::DeviceSize;
+        let mut dst_offset = dst.offset();
+        let regions:Vec<vk::BufferCopy> = ranges.iter().map(|&(offset, len)| {
+            assert!((offset + len) * elem <= src.bytes());
+            let region = vk::BufferCopy {
+                src_offset: src.offset() + offset * elem,
+                dst_offset,
+                size: len * elem,
+            };
+            dst_offset += len * elem;
+            region
+        }).collect();
+        assert!(dst_offset - dst.offset() <= dst.bytes());
+        unsafe {
+            self.device.inner().cmd_copy_buffer(
+                self.raw,
+                src.raw(),
+                dst.raw(),
+                &regions,
+            )
+        }
+        self
+    }
+
     pub fn copy_to_image<V:Copy, T: BufferType, D: Dim>(&mut self, src: &OwnedBuffer<V, T>, dst: &Texture<D, Color>, img_layout: vk::ImageLayout) -> &mut Self {
         // assert_eq!(src.capacity(),dst.capacity());
         unsafe {
@@ -5,8 +5,9 @@ use std::ops::{Deref, DerefMut};
 use ash::prelude::VkResult;
 use crate::render::device::Device;
 
-use crate::render::buffer_type::BufferType;
-use crate::render::buffer::Buffer;
+use crate::render::buffer_type::{BufferType, Cpu};
+use crate::render::buffer::{Buffer, map_whole, unmap};
+use crate::render::owned_buffer::OwnedBuffer;
 
 pub struct SubmitterCmd {
     pool: CommandPool,
@@ -122,4 +123,53 @@ pub fn fill_zeros_submit<V: Copy, T: BufferType, B: Buffer<V, T>>(sub: &mut Subm
     let (inner, val) = sub.inner_val();
     inner.cmd().begin(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)?.fill_zeros(val).end()?;
     inner.submit()
-}
\ No newline at end of file
+}
+
+/**Copies contents of GPU buffer back to host memory. Blocks until the transfer is finished.
+Make sure that no other command buffer is writing to the buffer in the meantime.*/
+pub fn download<V: Copy, T: BufferType>(cmd_pool: &CommandPool, src: &impl Buffer<V, T>) -> VkResult<Vec<V>> {
+    let len = src.len();
+    let mut sub = Submitter::new(OwnedBuffer::<V, Cpu>::with_capacity(cmd_pool.device(), len)?, cmd_pool)?;
+    let (inner, dst) = sub.inner_val();
+    inner.cmd().begin(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)?.copy(src, &*dst).end()?;
+    inner.submit()?;
+    sub.wait(None)?;
+    unsafe {
+        let ptr = map_whole(&mut *sub)?;
+        let data = std::slice::from_raw_parts(ptr, len as usize).to_vec();
+        unmap(&mut *sub);
+        Ok(data)
+    }
+}
+
+/**Same as download but reads back only the given ranges of elements (offset and length) and concatenates them.
+Ranges must not be empty*/
+pub fn download_ranges<V: Copy, T: BufferType>(cmd_pool: &CommandPool, src: &impl Buffer<V, T>, ranges: &[(u64, u64)]) -> VkResult<Vec<V>> {
+    let len:u64 = ranges.iter().map(|&(_, len)| len).sum();
+    let mut sub = Submitter::new(OwnedBuffer::<V, Cpu>::with_capacity(cmd_pool.device(), len)?, cmd_pool)?;
+    let (inner, dst) = sub.inner_val();
+    inner.cmd().begin(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)?.copy_ranges(src, &*dst, ranges).end()?;
+    inner.submit()?;
+    sub.wait(None)?;
+    unsafe {
+        let ptr = map_whole(&mut *sub)?;
+        let data = std::slice::from_raw_parts(ptr, len as usize).to_vec();
+        unmap(&mut *sub);
+        Ok(data)
+    }
+}
+
+/**Overwrites the beginning of GPU buffer with the given data. Blocks until the transfer is finished.*/
+pub fn upload<V: Copy, T: BufferType>(cmd_pool: &CommandPool, data: &[V], dst: &impl Buffer<V, T>) -> VkResult<()> {
+    assert!(data.len() as u64 <= dst.len());
+    let mut sub = Submitter::new(OwnedBuffer::<V, Cpu>::with_capacity(cmd_pool.device(), data.len() as u64)?, cmd_pool)?;
+    unsafe {
+        let ptr = map_whole(&mut *sub)?;
+        ptr.copy_from_nonoverlapping(data.as_ptr(), data.len());
+        unmap(&mut *sub);
+    }
+    let (inner, src) = sub.inner_val();
+    inner.cmd().begin(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)?.copy(&*src, dst).end()?;
+    inner.submit()?;
+    sub.wait(None)
+}